@@ -0,0 +1,42 @@
+use async_std::io::{ReadExt, WriteExt};
+
+fn hello(_param: rust_web::web::Json) -> rust_web::web::HttpResponse {
+    rust_web::web::HttpResponse::json(rust_web::web::Json::from("hello"))
+}
+
+async fn get_hello(addr: std::net::SocketAddr) -> usize {
+    let mut stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"GET /hello HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    response.len()
+}
+
+fn main() {
+    let idle_count = std::env::var("IDLE_CONNECTIONS").ok().and_then(|val| val.parse::<usize>().ok()).unwrap_or(5000);
+
+    async_std::task::block_on(async {
+        let mut server = rust_web::web::HttpServer::new("127.0.0.1:0").await.unwrap();
+        server.get_router().register_url("GET", "/hello", hello);
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        //每个空闲连接只发出请求行, 让服务端一直等待剩下的头部
+        let begin = std::time::Instant::now();
+        let mut idle = Vec::with_capacity(idle_count);
+        for _ in 0..idle_count {
+            let mut stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"GET /hello HTTP/1.1\r\n").await.unwrap();
+            idle.push(stream);
+        }
+        println!("opened {} idle connections in {:?}", idle.len(), begin.elapsed());
+
+        let begin = std::time::Instant::now();
+        let requests = (0..100).map(|_| async_std::task::spawn(get_hello(addr))).collect::<Vec<_>>();
+        for request in requests {
+            assert!(request.await > 0);
+        }
+        println!("served 100 requests while holding {} idle connections in {:?}", idle.len(), begin.elapsed());
+    });
+}
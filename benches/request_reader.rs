@@ -0,0 +1,154 @@
+use rust_web::web::{HttpRequest, HttpRequestReader, RequestLimits};
+
+//改写前的 HttpRequestReader: 每读出一段就拷贝剩余缓存, 每个 header 递归一次
+#[derive(PartialEq)]
+enum LegacyState {
+    Method,
+    Uri,
+    Version,
+    Header,
+    Body,
+    End,
+}
+
+struct LegacyReader {
+    cur_state: LegacyState,
+    http_request: HttpRequest,
+    cache: Vec<u8>,
+}
+
+impl LegacyReader {
+    fn new() -> Self {
+        Self {
+            cur_state: LegacyState::Method,
+            http_request: Default::default(),
+            cache: Default::default(),
+        }
+    }
+
+    fn read(&mut self, mut buf: Vec<u8>) -> Result<&mut Self, Box<dyn std::error::Error>> {
+        self.cache.append(&mut buf);
+        if self.cache.is_empty() { return Ok(self); }
+
+        match self.cur_state {
+            LegacyState::Method => {
+                if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
+                    self.http_request.set_method(std::str::from_utf8(&self.cache[..pos])?);
+                    self.cur_state = LegacyState::Uri;
+                    self.cache = self.cache[pos + 1..].to_vec();
+                    return self.read(vec![]);
+                }
+            },
+            LegacyState::Uri => {
+                if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
+                    self.http_request.set_uri(std::str::from_utf8(&self.cache[..pos])?);
+                    self.cur_state = LegacyState::Version;
+                    self.cache = self.cache[pos + 1..].to_vec();
+                    return self.read(vec![]);
+                }
+            },
+            LegacyState::Version => {
+                if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
+                    self.http_request.set_version(std::str::from_utf8(&self.cache[..pos - 1])?);
+                    self.cur_state = LegacyState::Header;
+                    self.cache = self.cache[pos + 1..].to_vec();
+                    return self.read(vec![]);
+                }
+            },
+            LegacyState::Header => {
+                if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
+                    if pos == 1 {
+                        self.cache = self.cache[pos + 1..].to_vec();
+                        let body_len = self.http_request.get_body_len();
+                        self.cur_state = if body_len > 0 { LegacyState::Body } else { LegacyState::End };
+                        return self.read(vec![]);
+                    }
+                    else if let Some(key_pos) = self.cache.iter().position(|&item| item == b':') {
+                        let content = std::str::from_utf8(&self.cache[..pos])?;
+                        self.http_request.insert_header(content[..key_pos].trim(), content[key_pos + 1..pos].trim());
+                        self.cache = self.cache[pos + 1..].to_vec();
+                        return self.read(vec![]);
+                    }
+                }
+            },
+            LegacyState::Body => {
+                if self.cache.len() == self.http_request.get_body_len() {
+                    self.http_request.set_body(std::mem::take(&mut self.cache));
+                    self.cur_state = LegacyState::End;
+                }
+            },
+            LegacyState::End => {},
+        }
+
+        Ok(self)
+    }
+}
+
+fn make_request(header_count: usize, body_len: usize) -> Vec<u8> {
+    let mut request = String::from("POST /api/v1/users?page=2 HTTP/1.1\r\nHost: localhost:8080\r\nUser-Agent: Mozilla/5.0 (X11; Linux x86_64)\r\n");
+    for i in 0..header_count {
+        request += &format!("x-custom-{i}: value-{i}-abcdefghijklmnopqrstuvwxyz\r\n");
+    }
+    request += &format!("content-length: {body_len}\r\n\r\n");
+
+    let mut request = request.into_bytes();
+    request.extend(std::iter::repeat_n(b'a', body_len));
+    request
+}
+
+//按 chunk_size 切成多次 read, 模拟从 socket 分批收到数据
+fn run<F: FnMut(Vec<u8>) -> bool>(request: &[u8], chunk_size: usize, iterations: usize, mut read: F) -> std::time::Duration {
+    let begin = std::time::Instant::now();
+    for _ in 0..iterations {
+        let finished = request.chunks(chunk_size).fold(false, |_, chunk| read(chunk.to_vec()));
+        assert!(finished);
+    }
+    begin.elapsed() / iterations as u32
+}
+
+//只比较解析速度, 不受请求大小限制影响
+fn unlimited() -> RequestLimits {
+    *RequestLimits::new().max_header_count(usize::MAX).max_header_size(usize::MAX).max_body_size(usize::MAX)
+}
+
+fn compare(name: &str, request: &[u8], chunk_size: usize, iterations: usize) {
+    let current = run(request, chunk_size, iterations, {
+        let mut reader = HttpRequestReader::with_limits(unlimited());
+        move |chunk| {
+            reader.read(chunk).unwrap();
+            let finished = reader.is_finished();
+            if finished { reader.take_request().unwrap(); }
+            finished
+        }
+    });
+
+    let legacy = run(request, chunk_size, iterations, {
+        let mut reader = LegacyReader::new();
+        move |chunk| {
+            reader.read(chunk).unwrap();
+            let finished = reader.cur_state == LegacyState::End;
+            if finished { reader = LegacyReader::new(); }
+            finished
+        }
+    });
+
+    println!("{name:<36} current {current:>12?}  legacy {legacy:>12?}  x{:.1}", legacy.as_secs_f64() / current.as_secs_f64());
+}
+
+fn main() {
+    compare("10 headers, 1KB reads", &make_request(10, 0), 1024, 20000);
+    compare("10 headers + 64KB body, 1KB reads", &make_request(10, 64 * 1024), 1024, 2000);
+    compare("100 headers, 1KB reads", &make_request(100, 0), 1024, 2000);
+    compare("1000 headers, 16KB reads", &make_request(1000, 0), 16 * 1024, 100);
+    compare("100 headers, 1 byte reads", &make_request(100, 0), 1, 100);
+
+    //改写前每个 header 递归一次, 这里只跑新的实现
+    let request = make_request(100000, 0);
+    let begin = std::time::Instant::now();
+    let mut reader = HttpRequestReader::with_limits(unlimited());
+    for chunk in request.chunks(16 * 1024) {
+        reader.read(chunk.to_vec()).unwrap();
+    }
+    assert!(reader.is_finished());
+    println!("{:<36} current {:>12?}", "100000 headers, 16KB reads", begin.elapsed());
+}
@@ -1,11 +1,14 @@
+#[rust_web::web::route(GET, "/")]
 fn home(_param: rust_web::web::Json) -> rust_web::web::HttpResponse {
     return rust_web::web::HttpResponse::view("index.html").unwrap();
 }
 
-fn test_response(param: rust_web::web::Json) -> rust_web::web::HttpResponse {
+#[rust_web::web::route(POST, "/test")]
+async fn test_response(mut ctx: rust_web::web::RequestContext) -> rust_web::web::HttpResponse {
+    let param = ctx.take_json();
     println!("test_response!!!param:{}", param);
 
-    std::thread::sleep(std::time::Duration::from_millis(5000));
+    async_std::task::sleep(std::time::Duration::from_millis(5000)).await;
 
     return rust_web::web::HttpResponse::json(param);
 }
@@ -15,12 +18,7 @@ fn main() {
     match server_res {
         Ok(mut server) => {  
             server.use_ssl(true);
-            let mut router_arc = server.get_router();
-
-            let router = std::sync::Arc::get_mut(&mut router_arc).unwrap();
-
-            router.register_url("GET", "/", &home);
-            router.register_url("POST", "/test", &test_response);
+            server.get_router().register_routes(rust_web::routes![home, test_response]);
 
             if let Err(e) = async_std::task::block_on(server.listen()) {
                 panic!("{}", e);
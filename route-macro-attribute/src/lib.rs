@@ -1,6 +1,498 @@
+use quote::quote;
+
+const METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "CONNECT", "TRACE"];
+
+//#[route(GET, POST, "/users/:id")]
+struct RouteAttr {
+    methods: Vec<String>,
+    path: syn::LitStr,
+}
+
+impl syn::parse::Parse for RouteAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut methods = Vec::new();
+        while input.peek(syn::Ident) {
+            let method: syn::Ident = input.parse()?;
+            let name = method.to_string();
+            if !METHODS.contains(&name.as_str()) {
+                return Err(syn::Error::new_spanned(method, format!("unknown http method `{}`, expected one of {}", name, METHODS.join(", "))));
+            }
+            if methods.contains(&name) {
+                return Err(syn::Error::new_spanned(method, format!("duplicate http method `{}`", name)));
+            }
+            methods.push(name);
+            input.parse::<syn::Token![,]>()?;
+        }
+
+        if methods.is_empty() {
+            return Err(input.error("expected http method, for example #[route(GET, \"/\")]"));
+        }
+
+        let path: syn::LitStr = input.parse()?;
+        if let Err(desc) = check_path(&path.value()) {
+            return Err(syn::Error::new_spanned(&path, desc));
+        }
+
+        if input.peek(syn::Token![,]) { input.parse::<syn::Token![,]>()?; }
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after path"));
+        }
+
+        Ok(Self { methods, path })
+    }
+}
+
+//与 Router 的匹配规则保持一致, 在编译期提前报错
+fn check_path(path: &str) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(format!("path `{}` must start with `/`", path));
+    }
+
+    let segments = path.split('/').collect::<Vec<&str>>();
+    let mut names: Vec<&str> = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let name = if let Some(name) = segment.strip_prefix('*') {
+            if index + 1 != segments.len() {
+                return Err(format!("wildcard `{}` must be the last segment", segment));
+            }
+            name
+        }
+        else if let Some(name) = segment.strip_prefix(':') {
+            name.strip_suffix('?').unwrap_or(name)
+        }
+        else if segment.contains([':', '*', '?']) {
+            return Err(format!("invalid segment `{}`, `:`, `*` and `?` are only allowed as `:param`, `:param?` and `*wildcard`", segment));
+        }
+        else { continue; };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("invalid parameter name in segment `{}`", segment));
+        }
+        if names.contains(&name) {
+            return Err(format!("duplicate parameter name `{}`", name));
+        }
+        names.push(name);
+    }
+
+    Ok(())
+}
+
+//按函数签名选择 Router 上对应的注册方法
+fn register_method(sig: &syn::Signature) -> proc_macro2::Ident {
+    let type_name = |arg: &syn::FnArg| match arg {
+        syn::FnArg::Typed(arg) => match &*arg.ty {
+            syn::Type::Path(ty) => ty.path.segments.last().map(|segment| segment.ident.to_string()),
+            syn::Type::Reference(syn::TypeReference { elem, .. }) => match &**elem {
+                syn::Type::Path(ty) => ty.path.segments.last().map(|segment| format!("&{}", segment.ident)),
+                _ => None,
+            },
+            _ => None,
+        },
+        syn::FnArg::Receiver(_) => None,
+    };
+    let args = sig.inputs.iter().map(type_name).collect::<Vec<_>>();
+
+    let is = |arg: &Option<String>, name: &str| arg.as_deref() == Some(name);
+    let name = match (sig.asyncness.is_some(), args.as_slice()) {
+        (true, [ctx]) if is(ctx, "RequestContext") => "register_async",
+        (false, [ctx]) if is(ctx, "RequestContext") => "register_handler",
+        (false, [json]) if is(json, "Json") => "register_url",
+        (false, [json, params]) if is(json, "Json") && is(params, "&PathParams") => "register_url_with_params",
+        //其余的参数都按提取器处理, 类型不对时由 Handler 的约束报错
+        _ => "register",
+    };
+
+    proc_macro2::Ident::new(name, proc_macro2::Span::call_site())
+}
+
 #[proc_macro_attribute]
 pub fn route(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-        println!("test route:{}", attr.to_string());
-        println!("item:{}", item.to_string());
-        item
+    let attr = syn::parse_macro_input!(attr as RouteAttr);
+    let func = syn::parse_macro_input!(item as syn::ItemFn);
+    expand_route(attr, func).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_route(attr: RouteAttr, func: syn::ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    if !func.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&func.sig.generics, "route handler can not be generic"));
+    }
+    if let Some(receiver) = func.sig.receiver() {
+        return Err(syn::Error::new_spanned(receiver, "route handler must be a free function"));
+    }
+
+    let register = register_method(&func.sig);
+    let name = &func.sig.ident;
+    let vis = &func.vis;
+    let methods = &attr.methods;
+    let path = &attr.path;
+
+    //同名的空结构体只占用类型命名空间, routes! 与 #[route_module] 通过它拿到路由信息
+    Ok(quote! {
+        #func
+
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis struct #name {}
+
+        impl ::core::convert::From<#name> for ::rust_web::web::RouteDef {
+            fn from(_: #name) -> Self {
+                ::rust_web::web::RouteDef::new(&[#(#methods),*], #path, |router, method, path| { router.#register(method, path, #name); })
+            }
+        }
+    })
+}
+
+//收集模块内所有 #[route] 函数, 生成 pub fn routes()
+#[proc_macro_attribute]
+pub fn route_module(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let module = syn::parse_macro_input!(item as syn::ItemMod);
+    if !attr.is_empty() {
+        return syn::Error::new(proc_macro2::Span::call_site(), "#[route_module] does not take arguments").into_compile_error().into();
+    }
+    expand_route_module(module).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_route_module(mut module: syn::ItemMod) -> syn::Result<proc_macro2::TokenStream> {
+    let Some((_, items)) = &mut module.content else {
+        return Err(syn::Error::new_spanned(&module, "#[route_module] only works on inline modules"));
+    };
+
+    let handlers = items.iter().filter_map(|item| match item {
+        syn::Item::Fn(func) if func.attrs.iter().any(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == "route")) => Some(&func.sig.ident),
+        _ => None,
+    }).cloned().collect::<Vec<_>>();
+
+    items.push(syn::parse_quote! {
+        pub fn routes() -> ::std::vec::Vec<::rust_web::web::RouteDef> {
+            ::std::vec![#(::rust_web::web::RouteDef::from(#handlers {})),*]
+        }
+    });
+
+    Ok(quote! { #module })
+}
+
+#[proc_macro_derive(ToJson, attributes(json))]
+pub fn derive_to_json(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    expand_to_json(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(FromJson, attributes(json))]
+pub fn derive_from_json(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    expand_from_json(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+//字段上的 #[json(...)] 属性
+#[derive(Default)]
+struct FieldAttr {
+    rename: Option<String>,
+    default: Option<Option<syn::ExprPath>>,
+    skip: bool,
+    flatten: bool,
+}
+
+impl FieldAttr {
+    fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: syn::LitStr = meta.value()?.parse()?;
+                    result.rename = Some(name.value());
+                }
+                else if meta.path.is_ident("default") {
+                    if meta.input.peek(syn::Token![=]) {
+                        let func: syn::LitStr = meta.value()?.parse()?;
+                        result.default = Some(Some(func.parse()?));
+                    }
+                    else {
+                        result.default = Some(None);
+                    }
+                }
+                else if meta.path.is_ident("skip") {
+                    result.skip = true;
+                }
+                else if meta.path.is_ident("flatten") {
+                    result.flatten = true;
+                }
+                else {
+                    return Err(meta.error("unknown json attribute, expected `rename`, `default`, `skip` or `flatten`"));
+                }
+                Ok(())
+            })?;
+
+            if result.flatten && result.rename.is_some() {
+                return Err(syn::Error::new_spanned(attr, "`flatten` can not be combined with `rename`"));
+            }
+            if result.skip && (result.flatten || result.rename.is_some()) {
+                return Err(syn::Error::new_spanned(attr, "`skip` can not be combined with `rename` or `flatten`"));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn default_value(&self) -> proc_macro2::TokenStream {
+        match &self.default {
+            Some(Some(func)) => quote! { #func() },
+            _ => quote! { ::core::default::Default::default() },
+        }
+    }
+}
+
+fn add_bounds(generics: &syn::Generics, bound: proc_macro2::TokenStream) -> syn::Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(#bound));
+    }
+    generics
+}
+
+fn no_field_attrs(fields: &syn::Fields) -> syn::Result<()> {
+    for field in fields.iter() {
+        if let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("json")) {
+            return Err(syn::Error::new_spanned(attr, "json attributes are only supported on named fields"));
+        }
+    }
+    Ok(())
+}
+
+fn unit_variants(data: &syn::DataEnum) -> syn::Result<Vec<(syn::Ident, String)>> {
+    data.variants.iter().map(|variant| {
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            return Err(syn::Error::new_spanned(variant, "only enums with unit variants can derive json conversions"));
+        }
+
+        let attr = FieldAttr::parse(&variant.attrs)?;
+        if attr.default.is_some() || attr.skip || attr.flatten {
+            return Err(syn::Error::new_spanned(variant, "only `rename` is supported on enum variants"));
+        }
+
+        Ok((variant.ident.clone(), attr.rename.unwrap_or_else(|| variant.ident.to_string())))
+    }).collect()
+}
+
+fn expand_to_json(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let generics = add_bounds(&input.generics, quote! { ::rust_web::web::ToJson });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        syn::Data::Struct(data) => match &data.fields {
+            syn::Fields::Named(fields) => {
+                let mut inserts = Vec::new();
+                for field in fields.named.iter() {
+                    let attr = FieldAttr::parse(&field.attrs)?;
+                    let ident = field.ident.as_ref().unwrap();
+
+                    if attr.skip { continue; }
+
+                    //展开的字段必须实现 ToJsonObject, 转换结果不是对象的类型在编译时报错
+                    if attr.flatten {
+                        inserts.push(quote::quote_spanned! {syn::spanned::Spanned::span(&field.ty)=>
+                            if let Some(son) = ::rust_web::web::ToJsonObject::to_json_object(&self.#ident) {
+                                attr.extend(son);
+                            }
+                        });
+                    }
+                    else {
+                        let key = attr.rename.unwrap_or_else(|| ident.to_string());
+                        inserts.push(quote! {
+                            attr.insert(#key.to_string(), ::rust_web::web::ToJson::to_json(&self.#ident));
+                        });
+                    }
+                }
+
+                return Ok(quote! {
+                    impl #impl_generics ::rust_web::web::ToJsonObject for #name #ty_generics #where_clause {
+                        fn to_json_object(&self) -> Option<::std::collections::HashMap<String, ::rust_web::web::Json>> {
+                            let mut attr = ::std::collections::HashMap::new();
+                            #(#inserts)*
+                            Some(attr)
+                        }
+                    }
+
+                    impl #impl_generics ::rust_web::web::ToJson for #name #ty_generics #where_clause {
+                        fn to_json(&self) -> ::rust_web::web::Json {
+                            let attr = ::rust_web::web::ToJsonObject::to_json_object(self).unwrap_or_default();
+                            ::rust_web::web::Json::new(::rust_web::web::JsonType::Object(attr))
+                        }
+                    }
+                });
+            },
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                no_field_attrs(&data.fields)?;
+                quote! { ::rust_web::web::ToJson::to_json(&self.0) }
+            },
+            syn::Fields::Unnamed(fields) => {
+                no_field_attrs(&data.fields)?;
+                let index = (0..fields.unnamed.len()).map(syn::Index::from);
+                quote! {
+                    ::rust_web::web::Json::new(::rust_web::web::JsonType::Vec(vec![#(::rust_web::web::ToJson::to_json(&self.#index)),*]))
+                }
+            },
+            syn::Fields::Unit => quote! { ::rust_web::web::Json::new(::rust_web::web::JsonType::Null) },
+        },
+        syn::Data::Enum(data) => {
+            let (idents, keys): (Vec<_>, Vec<_>) = unit_variants(data)?.into_iter().unzip();
+            quote! {
+                let name = match self {
+                    #(Self::#idents => #keys,)*
+                };
+                ::rust_web::web::Json::new(::rust_web::web::JsonType::String(name.to_string()))
+            }
+        },
+        syn::Data::Union(data) => return Err(syn::Error::new_spanned(data.union_token, "unions can not derive ToJson")),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::rust_web::web::ToJson for #name #ty_generics #where_clause {
+            fn to_json(&self) -> ::rust_web::web::Json {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_from_json(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let generics = add_bounds(&input.generics, quote! { ::rust_web::web::FromJson });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    //from_json 与 from_form 的结构相同, 只是字段的解码方法不同
+    let build = |decode: proc_macro2::Ident| -> syn::Result<proc_macro2::TokenStream> {
+        Ok(match &input.data {
+            syn::Data::Struct(data) => match &data.fields {
+                syn::Fields::Named(fields) => {
+                    let mut values = Vec::new();
+                    for field in fields.named.iter() {
+                        let attr = FieldAttr::parse(&field.attrs)?;
+                        let ident = field.ident.as_ref().unwrap();
+
+                        let value = if attr.skip {
+                            attr.default_value()
+                        }
+                        else if attr.flatten {
+                            let form = decode == "from_form";
+                            quote! { ::rust_web::web::FromJson::from_flattened(json, #form)? }
+                        }
+                        else {
+                            let key = attr.rename.clone().unwrap_or_else(|| ident.to_string());
+                            let missing = match attr.default {
+                                Some(_) => attr.default_value(),
+                                None => quote! { ::rust_web::web::FromJson::from_missing().map_err(|err: ::rust_web::web::JsonDecodeError| err.with_key(#key))? },
+                            };
+                            quote! {
+                                match attr.get(#key) {
+                                    Some(val) => ::rust_web::web::FromJson::#decode(val).map_err(|err: ::rust_web::web::JsonDecodeError| err.with_key(#key))?,
+                                    None => #missing,
+                                }
+                            }
+                        };
+                        values.push(quote! { #ident: #value });
+                    }
+
+                    quote! {
+                        #[allow(unused_variables)]
+                        let attr = match json.get() {
+                            ::rust_web::web::JsonType::Object(attr) => attr,
+                            other => return Err(::rust_web::web::JsonDecodeError::invalid_type("object", other)),
+                        };
+                        Ok(Self { #(#values),* })
+                    }
+                },
+                syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    no_field_attrs(&data.fields)?;
+                    quote! { Ok(Self(::rust_web::web::FromJson::#decode(json)?)) }
+                },
+                syn::Fields::Unnamed(fields) => {
+                    no_field_attrs(&data.fields)?;
+                    let len = fields.unnamed.len();
+                    let index = 0..len;
+                    quote! {
+                        match json.get() {
+                            ::rust_web::web::JsonType::Vec(arr) if arr.len() == #len => {
+                                Ok(Self(#(::rust_web::web::FromJson::#decode(&arr[#index]).map_err(|err: ::rust_web::web::JsonDecodeError| err.with_index(#index))?),*))
+                            },
+                            ::rust_web::web::JsonType::Vec(arr) => Err(::rust_web::web::JsonDecodeError::new(format!("invalid length: expected array of {}, found {}", #len, arr.len()))),
+                            other => Err(::rust_web::web::JsonDecodeError::invalid_type("array", other)),
+                        }
+                    }
+                },
+                syn::Fields::Unit => quote! {
+                    match json.get() {
+                        ::rust_web::web::JsonType::Null => Ok(Self),
+                        other => Err(::rust_web::web::JsonDecodeError::invalid_type("null", other)),
+                    }
+                },
+            },
+            syn::Data::Enum(data) => {
+                let (idents, keys): (Vec<_>, Vec<_>) = unit_variants(data)?.into_iter().unzip();
+                let expected = keys.iter().map(|key| format!("`{}`", key)).collect::<Vec<_>>().join(", ");
+                quote! {
+                    match json.get() {
+                        ::rust_web::web::JsonType::String(name) => match name.as_str() {
+                            #(#keys => Ok(Self::#idents),)*
+                            other => Err(::rust_web::web::JsonDecodeError::new(format!("unknown variant `{}`, expected one of {}", other, #expected))),
+                        },
+                        other => Err(::rust_web::web::JsonDecodeError::invalid_type("string", other)),
+                    }
+                }
+            },
+            syn::Data::Union(data) => return Err(syn::Error::new_spanned(data.union_token, "unions can not derive FromJson")),
+        })
+    };
+
+    let body = build(quote::format_ident!("from_json"))?;
+    let form = match &input.data {
+        syn::Data::Struct(data) if !matches!(data.fields, syn::Fields::Unit) => {
+            let body = build(quote::format_ident!("from_form"))?;
+            quote! {
+                fn from_form(json: &::rust_web::web::Json) -> Result<Self, ::rust_web::web::JsonDecodeError> {
+                    #body
+                }
+            }
+        },
+        _ => quote! {},
+    };
+
+    //对象结构体列出会读取的字段名, 展开的字段加上内层的
+    let field_names = match &input.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => {
+            let mut names = Vec::new();
+            for field in fields.named.iter() {
+                let attr = FieldAttr::parse(&field.attrs)?;
+                let ty = &field.ty;
+                if attr.flatten {
+                    names.push(quote! { names.extend(<#ty as ::rust_web::web::FromJson>::field_names().unwrap_or_default()); });
+                }
+                else if !attr.skip {
+                    let key = attr.rename.clone().unwrap_or_else(|| field.ident.as_ref().unwrap().to_string());
+                    names.push(quote! { names.push(#key); });
+                }
+            }
+            quote! {
+                fn field_names() -> Option<Vec<&'static str>> {
+                    let mut names = Vec::new();
+                    #(#names)*
+                    Some(names)
+                }
+            }
+        },
+        _ => quote! {},
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::rust_web::web::FromJson for #name #ty_generics #where_clause {
+            fn from_json(json: &::rust_web::web::Json) -> Result<Self, ::rust_web::web::JsonDecodeError> {
+                #body
+            }
+
+            #form
+
+            #field_names
+        }
+    })
 }
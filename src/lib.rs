@@ -1,10 +1,13 @@
+extern crate self as rust_web;
+
 pub mod web {
     use std::io::Read;
     use std::io::Write;
-    use num_enum::TryFromPrimitive;
-  	//use async_std::stream::StreamExt;   
-	//use async_std::io::ReadExt;
-	//use async_std::io::WriteExt;
+    use num_enum::{FromPrimitive, IntoPrimitive};
+    use futures::FutureExt;
+    use async_std::stream::StreamExt;
+    use async_std::io::ReadExt;
+    use async_std::io::WriteExt;
     
     pub fn urldecode<T: AsRef<str>>(content: T) -> Result<String, BacktraceError> {
     	let mut result = std::string::String::new();
@@ -65,10 +68,12 @@ pub mod web {
 
     #[derive(PartialEq)]
     #[derive(Debug)]
+    #[derive(Clone)]
     #[allow(non_camel_case_types)]
     pub enum JsonType {
         i64(i64),
         f64(f64),
+        Bool(bool),
         String(String),
         Vec(Vec<Json>),
         Object(std::collections::HashMap<String, Json>),
@@ -136,28 +141,11 @@ pub mod web {
 
     impl std::fmt::Display for JsonType {
         fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            match self {
-                JsonType::i64(val) => write!(f, "{:?}", val),
-                JsonType::f64(val) => write!(f, "{:?}", val),
-                JsonType::String(val) => write!(f, "{:?}", val),
-                JsonType::Vec(val) => {
-                    write!(f, "[")?;
-                    for (count, v) in val.iter().enumerate() {
-                        if count != 0 { write!(f, ", ")?; }
-                        write!(f, "{}", v)?;
-                    }
-                    write!(f, "]")
-                },
-                JsonType::Object(attr) => { 
-                    write!(f, "{{")?;
-                    for (count, (key, val)) in attr.iter().enumerate() {
-                        if count != 0 { write!(f, ", ")?; }
-                        write!(f, "{:?}: {}", key, val)?;
-                    }
-                    write!(f, "}}")
-                },
-                JsonType::Null => write!(f, "null"),
-            }
+            let format = JsonFormat::new();
+            let mut writer = JsonWriter::new(&format);
+            writer.write_value(self, 0).map_err(|_| std::fmt::Error)?;
+
+            f.write_str(&writer.out)
         }
     }
 
@@ -169,6 +157,7 @@ pub mod web {
 
     #[derive(PartialEq)]
     #[derive(Debug)]
+    #[derive(Clone)]
     pub struct Json {
         val: Box<JsonType>,
     }
@@ -197,6 +186,12 @@ pub mod web {
         }
     }
 
+    impl From<bool> for Json {
+        fn from(item: bool) -> Self {
+            Json::new(JsonType::Bool(item))
+        }
+    }
+
     //impl From<std::collections::HashMap<String, Json>> for Json {
     //    fn from(item: std::collections::HashMap<String, Json>) -> Self {
     //        Json::new(JsonType::Object(item))
@@ -253,6 +248,14 @@ pub mod web {
             return self.val.as_mut();
         }
 
+        pub fn into_inner(self) -> JsonType {
+            *self.val
+        }
+
+        pub fn decode<T: FromJson>(&self) -> Result<T, JsonDecodeError> {
+            T::from_json(self)
+        }
+
 		pub fn index(&self, index: usize) -> Option<&Json> {
 			if let JsonType::Vec(ref vec) = *self.val {
 				Some(&vec[index])
@@ -267,260 +270,30 @@ pub mod web {
 			else { panic!("not array type");}
 		}
 
-        fn parse_core_number(json_iter: &mut (impl Iterator<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-            let mut cache = String::new();
-            let mut is_decimal = false;
-
-            let mut copy_iter;
-            loop {
-                copy_iter = json_iter.clone();
-                if let Some(c) = json_iter.next() {
-                    match c {
-                        '.' => {
-                            if is_decimal == true { return Err(std::io::Error::new(std::io::ErrorKind::Other, "double dot").into());}
-                            is_decimal = true; 
-                        },
-                        _=> {
-                            if !c.is_numeric() {break;}
-                        }
-                    }
-                cache.push(c);
-                }
-                else {
-                    break;
-                }
-            }
-
-            if is_decimal { *cur_json = Json::new(JsonType::f64(cache.parse::<f64>().unwrap())); }
-            else { *cur_json = Json::new(JsonType::i64(cache.parse::<i64>().unwrap()));}
-            
-            *json_iter = copy_iter;
-            return Ok(());
-        }
-
-        fn parse_core_string(json_iter: &mut (impl Iterator<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-            let mut cache = String::new();
-            let mut is_turn = false;//转义
-
-            loop {
-                if let Some(c) = json_iter.next() {
-                    //println!("parse_core_string:[{}]", c);
-                    if is_turn {
-                        let turn_code: char = match c {
-                            'a' => 7 as char,
-                            'b' => 8 as char,
-                            'f' => 12 as char, 
-                            'n' => 10 as char,
-                            'r' => 13 as char,
-                            't' => 9 as char,
-                            'v' => 11 as char,
-                            '\\' => 92 as char, 
-                            '\'' => 39 as char,
-                            '"' => 34 as char,
-                            '?' => 64 as char,
-                            '0' => 0 as char,
-                            _=> return Err(std::io::Error::new(std::io::ErrorKind::Other, "not in turn code map").into()),
-                        };
-                        
-                        cache.push(turn_code);
-                        is_turn = false;
-                    }
-                    else {
-                        match c {
-                            '"' => {
-                                *cur_json = Json::new(JsonType::String(cache));
-                                return Ok(());
-                            },
-                            '\\' => {
-                                is_turn = true;  
-                            },
-                            _ => {
-                                cache.push(c);
-                            }
-                        }
-                    }
-                }
-                else {
-                    break;
-                }
-            }
-            
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "not a vaild string").into())
-        }
-
-        fn parse_core_object(json_iter: &mut (impl Iterator<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-            #[derive(PartialEq, Debug)]
-            enum ReadState {
-                KeyNameStartSign,
-                KeyName,
-                SplitSign,
-                EndSign,
-            }
-
-            let mut cache = String::new();
-            let mut key_name = String::new();
-            let mut cur_state = ReadState::KeyNameStartSign;
-
-            *cur_json = Json::new(JsonType::Object(Default::default()));
-            loop {
-                if let Some(c) = json_iter.next() { 
-                    //println!("parse_core_object:[{}], state:[{:?}]", c, cur_state);
-                    if c == ' ' || c == '\n' || c == '\t' { 
-                        continue; 
-                    }
-
-                    match cur_state {
-                        ReadState::KeyNameStartSign => {
-                            if c != '\"' { return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("'{}' not key name start sign", c)).into());}
-
-                            cur_state = ReadState::KeyName;
-                        },
-                        ReadState::KeyName => {
-                            match c {
-                                '"' => {
-                                    key_name = cache;
-                                    cache = String::new();
-                                    cur_state = ReadState::SplitSign;
-                                },
-                                _ => {
-                                    cache.push(c);
-                                }
-                            }
-                        },
-                        ReadState::SplitSign => {
-                            if c != ':' { return Err(std::io::Error::new(std::io::ErrorKind::Other, "not key name start sign").into());}
-
-                            let mut temp = Json::new(JsonType::Null);
-                            Self::parse_core(json_iter, &mut temp)?;
-                            cur_json.set_val(key_name, temp);
-                            key_name = String::new();
-                            cur_state = ReadState::EndSign;
-                        },
-                        ReadState::EndSign => {
-                            match c {
-                                '}' => {
-                                    return Ok(());
-                                },
-                                ',' => {
-                                    cur_state = ReadState::KeyNameStartSign;
-                                },
-                                _=> {
-                                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "not vaild end sign").into());
-                                }
-                            }
-                        },
-                    }
-                }
-                else {
-                    break;
-                }
-            }
-            
-            Ok(())
-        }
-
-        fn parse_core_array(json_iter: &mut (impl Iterator<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-
-            *cur_json = Json::new(JsonType::Vec(std::vec::Vec::<Json>::new()));
-            loop {
-                let mut copy = json_iter.clone();
-                if let Some(c) = json_iter.next() {
-                    //println!("parse_core_array:[{}]", c);
-                    match c {
-                        ',' => {
-                        },
-                        ']' => {
-                            return Ok(());
-                        },
-                        _ => {
-                            let mut temp =  Json::new(JsonType::Null);
-                            Self::parse_core(&mut copy, &mut temp)?;
-                            *json_iter = copy;
-                            cur_json.push(temp);
-                        }
-                    }
-                }
-                else {
-                    break; 
-                }
-            }
-
-            panic!("should not in here");
-        }
+        pub fn to_string_with(&self, format: &JsonFormat) -> Result<String, BacktraceError> {
+            let mut writer = JsonWriter::new(format);
+            writer.write_value(self.get(), 0)?;
 
-        fn parse_core_null(json_iter: &mut (impl Iterator<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-            for i in 1..4 {
-                if "null".chars().nth(i).unwrap() != json_iter.next().unwrap() { 
-                    //println!("not null key");
-                }
-            }
-            *cur_json = Json::new(JsonType::Null);
-            Ok(())
+            Ok(writer.out)
         }
 
-        fn parse_core(json_iter: &mut (impl Iterator::<Item = char> + Clone), cur_json: &mut Json) -> Result<(), BacktraceError> {
-            loop {
-                let mut cur_iter = json_iter.clone();
-                if let Some(c) = json_iter.next() {
-                    //println!("parse_core:[{}]", c);
-                    match c {
-                        ' ' => {}, '\n' => {}, '\t' => {},
-                        '{' => {
-                            Self::parse_core_object(json_iter, cur_json)?;
-                            return Ok(());
-                        },
-                        '[' => {
-                            Self::parse_core_array(json_iter, cur_json)?;
-                            return Ok(());
-                        },
-                        '"' => {
-                            if let JsonType::Vec(vec) = cur_json.get_mut() {
-                                    let mut temp = Json::new(JsonType::Null);
-                                    Self::parse_core_string(json_iter, &mut temp)?;
-                                    vec.push(temp);
-                            }
-                            else {
-                                Self::parse_core_string(json_iter, cur_json)?;
-                                return Ok(());
-                            }
-                        },
-                        'n' => {
-                            Self::parse_core_null(json_iter, cur_json)?;
-                            return Ok(());
-                        },
-                        _ => {
-                            if c.is_numeric() {
-                                if let JsonType::Vec(vec) = cur_json.get_mut() {
-                                    let mut temp = Json::new(JsonType::Null);
-                                    Self::parse_core_number(&mut cur_iter, &mut temp)?;
-                                    vec.push(temp);
-                                }
-                                else {
-                                    Self::parse_core_number(&mut cur_iter, cur_json)?;
-                                    *json_iter = cur_iter;
-                                    return Ok(());
-                                }
-                            }
-                            else {
-                                //println!("parse_core faild:[{}]", c);
-                                return Err(std::io::Error::new(std::io::ErrorKind::Other, "not vaild value").into());
-                            }
-                        },
-                    }
-                }
-                else {
-                    break;
-                }
-            }
+        pub fn to_pretty_string(&self) -> String {
+            let format = JsonFormat::pretty(2);
+            let mut writer = JsonWriter::new(&format);
+            let _ = writer.write_value(self.get(), 0);
 
-            Ok(())
+            writer.out
         }
 
+        //按 RFC 8259 严格解析, 错误信息带行列号
         pub fn parse<T: AsRef<str>>(json_str: T) -> Result<Json, BacktraceError> {
-            let mut result = Json::new(JsonType::Null);
-            Self::parse_core(&mut json_str.as_ref().chars(), &mut result)?;
+            let mut parser = JsonParser::new(json_str.as_ref());
+
+            parser.skip_whitespace();
+            let result = parser.parse_value()?;
+            parser.skip_whitespace();
 
-            //if index < json_str.trim_end().chars().count() { return Err("not vaild json"); }
+            if parser.pos < parser.bytes.len() { return Err(parser.error("unexpected trailing characters")); }
 
             Ok(result)
         }
@@ -639,1063 +412,6140 @@ pub mod web {
         }
     }
 
-    #[derive(Debug, Default, Clone)]
-    pub struct HttpRequest {
-        method: String,
-        uri: String,
-        query_string: String,
-        version: String,
-        header: std::collections::HashMap<String, String>,
-        body: std::vec::Vec<u8>,
+    //嵌套过深的输入直接报错, 避免递归把栈耗尽
+    const JSON_MAX_DEPTH: usize = 512;
+
+    struct JsonParser<'a> {
+        src: &'a str,
+        bytes: &'a [u8],
+        pos: usize,
+        depth: usize,
     }
 
-    impl HttpRequest {
-        pub fn set_method<T: Into<String>>(&mut self, method: T) {
-            self.method = method.into(); 
+    impl<'a> JsonParser<'a> {
+        fn new(src: &'a str) -> Self {
+            Self {
+                src,
+                bytes: src.as_bytes(),
+                pos: 0,
+                depth: 0,
+            }
         }
 
-        pub fn get_method(&self) -> &String {
-            &self.method 
+        fn error<T: std::fmt::Display>(&self, desc: T) -> BacktraceError {
+            let consumed = &self.src[..self.pos.min(self.src.len())];
+            let line = consumed.matches('\n').count() + 1;
+            let column = consumed.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+
+            std::io::Error::other(format!("{desc} at line {line} column {column}")).into()
         }
 
-        pub fn set_uri<T: Into<String>>(&mut self, uri: T) {
-            let uri: String = uri.into();
-            if let Some(pos) = uri.find("?") {
-                let (uri, query_string) = uri.split_at(pos);
-                self.uri = uri.to_string();
-                self.query_string = query_string[1..].to_string();
-            }
-            else {
-                self.uri = uri;
+        fn unexpected(&self) -> BacktraceError {
+            match self.src[self.pos..].chars().next() {
+                Some(c) => self.error(format!("unexpected character {c:?}")),
+                None => self.error("unexpected end of input"),
             }
         }
 
-        pub fn get_uri(&self) -> &String {
-            &self.uri
+        fn peek(&self) -> Option<u8> {
+            self.bytes.get(self.pos).copied()
         }
 
-        pub fn get_query_string(&self) -> &String {
-            &self.query_string 
+        fn skip_whitespace(&mut self) {
+            while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+                self.pos += 1;
+            }
         }
 
-        pub fn get_version(&self) -> &String {
-            &self.version
+        fn expect(&mut self, c: u8) -> Result<(), BacktraceError> {
+            if self.peek() != Some(c) { return Err(self.unexpected()); }
+
+            self.pos += 1;
+            Ok(())
         }
 
-        pub fn set_version<T: Into<String>>(&mut self, version: T) {
-            self.version = version.into();
+        fn parse_value(&mut self) -> Result<Json, BacktraceError> {
+            match self.peek() {
+                Some(b'{') => self.parse_object(),
+                Some(b'[') => self.parse_array(),
+                Some(b'"') => Ok(Json::new(JsonType::String(self.parse_string()?))),
+                Some(b't') => self.parse_literal("true", JsonType::Bool(true)),
+                Some(b'f') => self.parse_literal("false", JsonType::Bool(false)),
+                Some(b'n') => self.parse_literal("null", JsonType::Null),
+                Some(b'-' | b'0'..=b'9') => self.parse_number(),
+                _ => Err(self.unexpected()),
+            }
         }
 
-        pub fn insert_header<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
-            self.header.insert(key.into().to_lowercase(), val.into());
+        fn parse_literal(&mut self, literal: &str, val: JsonType) -> Result<Json, BacktraceError> {
+            for &c in literal.as_bytes() {
+                self.expect(c)?;
+            }
+
+            Ok(Json::new(val))
         }
 
-        pub fn get_headers(&self) -> &std::collections::hash_map::HashMap<String, String> {
-            &self.header
+        fn enter(&mut self) -> Result<(), BacktraceError> {
+            self.depth += 1;
+            if self.depth > JSON_MAX_DEPTH { return Err(self.error("nesting too deep")); }
+
+            self.pos += 1;
+            Ok(())
         }
 
-        pub fn set_body(&mut self, buffer: std::vec::Vec<u8>) {
-            self.body = buffer;
+        fn parse_object(&mut self) -> Result<Json, BacktraceError> {
+            self.enter()?;
+            let mut attr = std::collections::HashMap::new();
 
-            if !self.header.contains_key("transfer-encoding") {
-                self.insert_header("content-length", self.body.len().to_string());
+            self.skip_whitespace();
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok(Json::new(JsonType::Object(attr)));
+            }
+
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') { return Err(self.unexpected()); }
+                let key = self.parse_string()?;
+
+                self.skip_whitespace();
+                self.expect(b':')?;
+                self.skip_whitespace();
+                attr.insert(key, self.parse_value()?);
+
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        self.depth -= 1;
+                        return Ok(Json::new(JsonType::Object(attr)));
+                    },
+                    _ => return Err(self.unexpected()),
+                }
             }
         }
 
-        pub fn get_body(&self) -> &std::vec::Vec<u8> {
-           &self.body
+        fn parse_array(&mut self) -> Result<Json, BacktraceError> {
+            self.enter()?;
+            let mut vec = Vec::new();
+
+            self.skip_whitespace();
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok(Json::new(JsonType::Vec(vec)));
+            }
+
+            loop {
+                self.skip_whitespace();
+                vec.push(self.parse_value()?);
+
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b']') => {
+                        self.pos += 1;
+                        self.depth -= 1;
+                        return Ok(Json::new(JsonType::Vec(vec)));
+                    },
+                    _ => return Err(self.unexpected()),
+                }
+            }
         }
 
-        pub fn get_body_len(&self) -> usize {
-            if let Some(len_str) = self.get_headers().get("content-length") {
-                return str::parse::<usize>(len_str).unwrap()
+        fn parse_hex4(&mut self) -> Result<u16, BacktraceError> {
+            let hex = self.src.get(self.pos..self.pos + 4).filter(|hex| hex.bytes().all(|c| c.is_ascii_hexdigit()));
+            let Some(hex) = hex else { return Err(self.error("invalid \\u escape")); };
+
+            self.pos += 4;
+            Ok(u16::from_str_radix(hex, 16)?)
+        }
+
+        fn parse_string(&mut self) -> Result<String, BacktraceError> {
+            self.pos += 1;
+            let mut result = String::new();
+
+            loop {
+                //没有转义的部分整段拷贝
+                let begin = self.pos;
+                while let Some(c) = self.peek() {
+                    if c == b'"' || c == b'\\' || c < 0x20 { break; }
+                    self.pos += 1;
+                }
+                result += &self.src[begin..self.pos];
+
+                match self.peek() {
+                    Some(b'"') => {
+                        self.pos += 1;
+                        return Ok(result);
+                    },
+                    Some(b'\\') => {
+                        self.pos += 1;
+                        let turn_code = match self.peek() {
+                            Some(b'"') => '"',
+                            Some(b'\\') => '\\',
+                            Some(b'/') => '/',
+                            Some(b'b') => '\u{8}',
+                            Some(b'f') => '\u{c}',
+                            Some(b'n') => '\n',
+                            Some(b'r') => '\r',
+                            Some(b't') => '\t',
+                            Some(b'u') => {
+                                self.pos += 1;
+                                let high = self.parse_hex4()?;
+                                let code = if (0xD800..0xDC00).contains(&high) {
+                                    //代理对: 必须紧跟一个低位代理
+                                    if !self.src[self.pos..].starts_with("\\u") { return Err(self.error("unpaired surrogate")); }
+                                    self.pos += 2;
+                                    let low = self.parse_hex4()?;
+                                    if !(0xDC00..0xE000).contains(&low) { return Err(self.error("unpaired surrogate")); }
+
+                                    0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00)
+                                }
+                                else {
+                                    high as u32
+                                };
+
+                                result.push(char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))?);
+                                continue;
+                            },
+                            _ => return Err(self.error("invalid escape")),
+                        };
+
+                        result.push(turn_code);
+                        self.pos += 1;
+                    },
+                    Some(_) => return Err(self.error("control character in string")),
+                    None => return Err(self.error("unterminated string")),
+                }
             }
-            else {
-                return 0;
+        }
+
+        fn parse_number(&mut self) -> Result<Json, BacktraceError> {
+            let begin = self.pos;
+            let digits = |parser: &mut Self| {
+                let start = parser.pos;
+                while let Some(b'0'..=b'9') = parser.peek() {
+                    parser.pos += 1;
+                }
+                parser.pos - start
+            };
+
+            if self.peek() == Some(b'-') { self.pos += 1; }
+
+            match self.peek() {
+                Some(b'0') => self.pos += 1,
+                Some(b'1'..=b'9') => { digits(self); },
+                _ => return Err(self.unexpected()),
+            }
+
+            let mut is_decimal = false;
+            if self.peek() == Some(b'.') {
+                self.pos += 1;
+                is_decimal = true;
+                if digits(self) == 0 { return Err(self.unexpected()); }
+            }
+
+            if let Some(b'e' | b'E') = self.peek() {
+                self.pos += 1;
+                is_decimal = true;
+                if let Some(b'+' | b'-') = self.peek() { self.pos += 1; }
+                if digits(self) == 0 { return Err(self.unexpected()); }
             }
+
+            let number = &self.src[begin..self.pos];
+            if !is_decimal {
+                //超出 i64 范围的整数按 f64 处理
+                if let Ok(val) = number.parse::<i64>() { return Ok(Json::new(JsonType::i64(val))); }
+            }
+
+            let val = number.parse::<f64>()?;
+            if !val.is_finite() { return Err(self.error("number out of range")); }
+
+            Ok(Json::new(JsonType::f64(val)))
         }
     }
 
-    #[derive(Debug)]
-    #[derive(PartialEq)]
-    enum RequestReaderState {
-        Method,
-        URI,
-        Version,
-        Header,
-        Body,
-        End,
+    //NaN 与 inf 不是合法的 JSON 数字, 序列化时按策略处理
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
+    pub enum NonFiniteFloat {
+        #[default]
+        Null,
+        Error,
     }
 
-    #[derive(Debug)]
-    pub struct HttpRequestReader {
-        //当前等待读取的内容
-        cur_state: RequestReaderState, 
-        http_request: HttpRequest,
-        cache: Vec<u8>,
+    #[derive(Debug, Default, Clone)]
+    pub struct JsonFormat {
+        indent: Option<String>,
+        ascii_only: bool,
+        non_finite: NonFiniteFloat,
     }
 
-    impl HttpRequestReader {
+    impl JsonFormat {
         pub fn new() -> Self {
+            Default::default()
+        }
+
+        pub fn pretty(indent: usize) -> Self {
             Self {
-                cur_state: RequestReaderState::Method,
-                http_request: Default::default(),
-                cache: Default::default(),
-            } 
+                indent: Some(" ".repeat(indent)),
+                ..Default::default()
+            }
         }
 
-        pub fn read(&mut self, mut buf: Vec<u8>) -> Result<&mut Self, BacktraceError> {
-            self.cache.append(&mut buf);
-            drop(buf);
+        pub fn indent<T: Into<String>>(&mut self, indent: T) -> &mut Self {
+            self.indent = Some(indent.into());
+            self
+        }
 
-            //println!("cur_state:{:?}", self.cur_state);
-            if self.cache.len() == 0 { return Ok(self); }
+        //非 ASCII 字符输出为 \uXXXX, 超出 BMP 的用代理对
+        pub fn ascii_only(&mut self, ascii_only: bool) -> &mut Self {
+            self.ascii_only = ascii_only;
+            self
+        }
 
-            match self.cur_state {
-                RequestReaderState::Method => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
-                        self.http_request.set_method(std::str::from_utf8(&self.cache[..pos])?);
-                        self.cur_state = RequestReaderState::URI;
-                        self.cache = self.cache[pos + 1 ..].to_vec();
-                
-                        return self.read(vec![]);
-                    }
+        pub fn non_finite(&mut self, policy: NonFiniteFloat) -> &mut Self {
+            self.non_finite = policy;
+            self
+        }
+    }
+
+    struct JsonWriter<'a> {
+        format: &'a JsonFormat,
+        out: String,
+    }
+
+    impl<'a> JsonWriter<'a> {
+        fn new(format: &'a JsonFormat) -> Self {
+            Self {
+                format,
+                out: String::new(),
+            }
+        }
+
+        fn newline(&mut self, depth: usize) {
+            if let Some(indent) = &self.format.indent {
+                self.out.push('\n');
+                for _ in 0..depth { self.out.push_str(indent); }
+            }
+        }
+
+        fn write_value(&mut self, val: &JsonType, depth: usize) -> Result<(), BacktraceError> {
+            use std::fmt::Write;
+
+            match val {
+                JsonType::i64(val) => { write!(self.out, "{}", val)?; },
+                JsonType::f64(val) if val.is_finite() => { write!(self.out, "{:?}", val)?; },
+                JsonType::f64(val) => match self.format.non_finite {
+                    NonFiniteFloat::Null => self.out.push_str("null"),
+                    NonFiniteFloat::Error => return Err(std::io::Error::other(format!("can not serialize {} as json number", val)).into()),
                 },
-                RequestReaderState::URI => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
-                        self.http_request.set_uri(std::str::from_utf8(&self.cache[..pos])?);
-                        self.cur_state = RequestReaderState::Version;
-                        self.cache = self.cache[pos + 1 ..].to_vec();
-                
-                        return self.read(vec![]);
+                JsonType::Bool(val) => self.out.push_str(if *val { "true" } else { "false" }),
+                JsonType::String(val) => self.write_string(val),
+                JsonType::Vec(arr) => {
+                    self.out.push('[');
+                    for (count, item) in arr.iter().enumerate() {
+                        if count != 0 { self.out.push(','); }
+                        self.newline(depth + 1);
+                        self.write_value(item.get(), depth + 1)?;
                     }
+                    if !arr.is_empty() { self.newline(depth); }
+                    self.out.push(']');
                 },
-                RequestReaderState::Version => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
-                        self.http_request.set_version(std::str::from_utf8(&self.cache[..pos - 1])?);
-                        self.cur_state = RequestReaderState::Header;
-                        self.cache = self.cache[pos + 1 ..].to_vec();
-                
-                        return self.read(vec![]);
+                JsonType::Object(attr) => {
+                    self.out.push('{');
+                    for (count, (key, item)) in attr.iter().enumerate() {
+                        if count != 0 { self.out.push(','); }
+                        self.newline(depth + 1);
+                        self.write_string(key);
+                        self.out.push_str(if self.format.indent.is_some() { ": " } else { ":" });
+                        self.write_value(item.get(), depth + 1)?;
                     }
+                    if !attr.is_empty() { self.newline(depth); }
+                    self.out.push('}');
                 },
-                RequestReaderState::Header => {
-                    //println!("*{:?}*", std::str::from_utf8(&self.cache)?);
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
-                         //println!("pos:{pos}");
-                        if pos == 1 {
-                            self.cache = self.cache[pos + 1..].to_vec();
-                            let body_len = self.http_request.get_body_len();
-                            if body_len > 0 {
-                                self.cur_state = RequestReaderState::Body; 
-                                return self.read(vec![]);
-                            }
-                            else {
-                                self.cur_state = RequestReaderState::End;
-                                return Ok(self)
-                            }
-                
-                        }
-                        else if let Some(key_pos) = self.cache.iter().position(|&item| item == b':') {
-                            let content = std::str::from_utf8(&self.cache[..pos])?;
-                            self.http_request.insert_header(content[..key_pos].trim(), content[key_pos + 1..pos].trim());
-                            self.cache = self.cache[pos + 1 ..].to_vec();
-                
-                            return self.read(vec![]);
-                        }
-                        else {
-                            return Ok(self);
-                            //return Err(std::io::Error::new(std::io::ErrorKind::Other, "header not correct").into());
+                JsonType::Null => self.out.push_str("null"),
+            }
+
+            Ok(())
+        }
+
+        fn write_string(&mut self, val: &str) {
+            use std::fmt::Write;
+
+            self.out.push('"');
+            for c in val.chars() {
+                match c {
+                    '"' => self.out.push_str("\\\""),
+                    '\\' => self.out.push_str("\\\\"),
+                    '\n' => self.out.push_str("\\n"),
+                    '\r' => self.out.push_str("\\r"),
+                    '\t' => self.out.push_str("\\t"),
+                    '\u{8}' => self.out.push_str("\\b"),
+                    '\u{c}' => self.out.push_str("\\f"),
+                    c if (c as u32) < 0x20 || (self.format.ascii_only && !c.is_ascii()) => {
+                        let mut buf = [0u16; 2];
+                        for unit in c.encode_utf16(&mut buf) {
+                            let _ = write!(self.out, "\\u{:04x}", unit);
                         }
-                    } 
-                    else {
-                        return Ok(self); 
-                            //return Err(std::io::Error::new(std::io::ErrorKind::Other, "header not correct").into());
-                    }
-                },
-                RequestReaderState::Body => {
-                    //println!("cache_len:{}",self.cache.len());
-                    //println!("in body:{}", urldecode(std::str::from_utf8(self.cache.as_slice())?)?);
-                    if self.cache.len() == self.http_request.get_headers().get("content-length").unwrap().parse::<usize>()? {
-                         self.http_request.set_body(std::mem::take(&mut self.cache));
-                        self.cur_state = RequestReaderState::End;
-                    }
-                
-                    return Ok(self);
-                },
-                RequestReaderState::End => {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "read finish").into());
+                    },
+                    c => self.out.push(c),
                 }
-            } 
+            }
+            self.out.push('"');
+        }
+    }
+
+    pub use route_macro_attribute::{ToJson, FromJson, route, route_module};
+
+    pub trait ToJson {
+        fn to_json(&self) -> Json;
+    }
 
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, "should not in here").into());
+    //可以用 #[json(flatten)] 展开的类型, 返回 None 时不展开任何字段
+    pub trait ToJsonObject: ToJson {
+        fn to_json_object(&self) -> Option<std::collections::HashMap<String, Json>>;
+    }
+
+    pub trait FromJson: Sized {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError>;
+
+        //对象中缺少该字段时调用, Option 之类的类型可以覆盖
+        fn from_missing() -> Result<Self, JsonDecodeError> {
+            Err(JsonDecodeError::new("missing field"))
         }
 
-        pub fn is_finished(&self) -> bool {
-            return self.cur_state == RequestReaderState::End;
+        //路径参数、query 与表单里的值都是字符串, 数字与布尔类型需要从文本转换
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            Self::from_json(json)
         }
 
-        pub fn get_request(self) -> Result<HttpRequest, BacktraceError> {
-            if !self.is_finished() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "read not finish").into());}
+        //#[json(flatten)] 的字段从外层的整个对象解码, form 表示按表单的规则
+        fn from_flattened(json: &Json, form: bool) -> Result<Self, JsonDecodeError> {
+            if form { Self::from_form(json) } else { Self::from_json(json) }
+        }
 
-            return Ok(self.http_request);
+        //derive 的对象结构体会读取的字段名, 其余类型返回 None
+        fn field_names() -> Option<Vec<&'static str>> {
+            None
         }
     }
 
-    #[derive(Copy, Clone, Debug, TryFromPrimitive)]
-    #[repr(u16)]
-    pub enum HttpResponseStatusCode {
-        OK = 200,
-        NotFound = 404,
-        BadRequest = 400,
-        Unauthorized = 401,
-        Found = 302,
-        UnsupportedMediaType = 415,
-        InternalServerError = 500,
+    //path 为出错位置, 例如 $.users[2].name
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct JsonDecodeError {
+        path: String,
+        message: String,
     }
 
-    #[derive(Debug)]
-    pub struct HttpResponse {
-        version: String, 
-        status_code: HttpResponseStatusCode,
-        status_desc: String,
-        header: std::collections::HashMap<String, String>,  
-        body: std::vec::Vec::<u8>,
+    impl JsonDecodeError {
+        pub fn new<T: Into<String>>(message: T) -> Self {
+            Self {
+                path: String::new(),
+                message: message.into(),
+            }
+        }
+
+        pub fn invalid_type(expected: &str, found: &JsonType) -> Self {
+            let found = match found {
+                JsonType::i64(_) | JsonType::f64(_) => "number",
+                JsonType::Bool(_) => "boolean",
+                JsonType::String(_) => "string",
+                JsonType::Vec(_) => "array",
+                JsonType::Object(_) => "object",
+                JsonType::Null => "null",
+            };
+
+            Self::new(format!("invalid type: expected {}, found {}", expected, found))
+        }
+
+        pub fn with_key(mut self, key: &str) -> Self {
+            if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                self.path = format!(".{}{}", key, self.path);
+            }
+            else {
+                self.path = format!("[{}]{}", JsonType::String(key.to_string()), self.path);
+            }
+            self
+        }
+
+        pub fn with_index(mut self, index: usize) -> Self {
+            self.path = format!("[{}]{}", index, self.path);
+            self
+        }
+
+        pub fn get_path(&self) -> String {
+            format!("${}", self.path)
+        }
+
+        pub fn get_message(&self) -> &str {
+            &self.message
+        }
     }
 
-    impl HttpResponse {
-        pub fn new(code: HttpResponseStatusCode) -> Self {
-            Self { 
-                version: String::from("HTTP/1.1"), 
-                status_code: code,
-                status_desc: format!("{:?}", code),
-                header: Default::default(),
-                body: Default::default(),
+    impl std::fmt::Display for JsonDecodeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{} at {}", self.message, self.get_path())
+        }
+    }
+
+    impl std::error::Error for JsonDecodeError {}
+
+    impl ToJson for Json {
+        fn to_json(&self) -> Json {
+            self.clone()
+        }
+    }
+
+    impl FromJson for Json {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            Ok(json.clone())
+        }
+    }
+
+    impl ToJson for bool {
+        fn to_json(&self) -> Json {
+            Json::new(JsonType::Bool(*self))
+        }
+    }
+
+    impl FromJson for bool {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::Bool(val) => Ok(*val),
+                other => Err(JsonDecodeError::invalid_type("boolean", other)),
             }
         }
 
-        fn get_file(path: &str) -> Result<HttpResponse, BacktraceError> {
-            println!("path:{}", path);
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::String(val) if val == "true" || val == "1" || val == "on" => Ok(true),
+                JsonType::String(val) if val == "false" || val == "0" || val == "off" => Ok(false),
+                JsonType::String(val) => Err(JsonDecodeError::new(format!("invalid value: expected boolean, found {:?}", val))),
+                _ => Self::from_json(json),
+            }
+        }
+    }
 
-            let file_res = std::fs::OpenOptions::new().read(true).open(path);
-            match file_res {
-                Ok(mut file) => {
-                    let mut buffer = vec![0u8; file.metadata()?.len() as usize];
+    macro_rules! json_integer {
+        ($($ty:ty),*) => {$(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Json {
+                    //超出 i64 范围的按 f64 输出, 与解析时的处理一致
+                    match i64::try_from(*self) {
+                        Ok(val) => Json::new(JsonType::i64(val)),
+                        Err(_) => Json::new(JsonType::f64(*self as f64)),
+                    }
+                }
+            }
 
-                    let len = file.read(buffer.as_mut_slice())?;
-                    assert_eq!(len, buffer.len());
+            impl FromJson for $ty {
+                fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+                    let out_of_range = || JsonDecodeError::new(concat!("number out of range for ", stringify!($ty)));
 
-                    let mut response = HttpResponse::new(HttpResponseStatusCode::OK);
-                    
-                    let file_type = *path.split('.').collect::<Vec<&str>>().last().unwrap();
-                    let content_type = match file_type {
-                        "html" => "text/html; charset=utf-8",
-                        "js" => "text/javascript",
-                        "css" => "text/css",
-                        "ico" => "image/x-icon",
-                        _ => "charset=utf-8",
-                    };
-                    response.insert_header("content-type", content_type);
+                    match json.get() {
+                        JsonType::i64(val) => <$ty>::try_from(*val).map_err(|_| out_of_range()),
+                        JsonType::f64(val) if val.fract() == 0.0 => {
+                            //MAX as f64 会向上取整 (例如 i64 得到 2^63), 上界必须用严格小于
+                            if *val >= <$ty>::MIN as f64 && *val < <$ty>::MAX as f64 + 1.0 { Ok(*val as $ty) } else { Err(out_of_range()) }
+                        },
+                        JsonType::f64(_) => Err(JsonDecodeError::new(concat!("invalid value: expected ", stringify!($ty), ", found fractional number"))),
+                        other => Err(JsonDecodeError::invalid_type(stringify!($ty), other)),
+                    }
+                }
 
-                    response.set_body(buffer);
-                    Ok(response)
-                },
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::NotFound {
-                        let mut response = HttpResponse::new(HttpResponseStatusCode::NotFound);
+                fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+                    match json.get() {
+                        JsonType::String(val) => val.parse::<$ty>().map_err(|err| JsonDecodeError::new(format!("invalid value {:?} for {}: {}", val, stringify!($ty), err))),
+                        _ => Self::from_json(json),
+                    }
+                }
+            }
+        )*};
+    }
 
-                        response.set_body("404 not found".into());
+    json_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
 
-                        Ok(response)
+    macro_rules! json_float {
+        ($($ty:ty),*) => {$(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Json {
+                    Json::new(JsonType::f64(*self as f64))
+                }
+            }
+
+            impl FromJson for $ty {
+                fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+                    match json.get() {
+                        JsonType::i64(val) => Ok(*val as $ty),
+                        JsonType::f64(val) => Ok(*val as $ty),
+                        other => Err(JsonDecodeError::invalid_type(stringify!($ty), other)),
                     }
-                    else {
-                        Ok(HttpResponse::new(HttpResponseStatusCode::InternalServerError))
+                }
+
+                fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+                    match json.get() {
+                        JsonType::String(val) => val.parse::<$ty>().map_err(|err| JsonDecodeError::new(format!("invalid value {:?} for {}: {}", val, stringify!($ty), err))),
+                        _ => Self::from_json(json),
                     }
                 }
             }
-        }
+        )*};
+    }
 
-        pub fn view(path: &str) -> Result<HttpResponse, BacktraceError> {
-            let mut view: String = String::from("views/");
-            view += path;
+    json_float!(f32, f64);
 
-            Self::get_file(view.as_str())
+    impl ToJson for str {
+        fn to_json(&self) -> Json {
+            Json::new(JsonType::String(self.to_string()))
         }
+    }
 
-        pub fn get_root_file(uri: &str) -> Result<HttpResponse, BacktraceError> {
-            let mut root: String = String::from("wwwroot");
-            root += uri;
+    impl ToJson for String {
+        fn to_json(&self) -> Json {
+            self.as_str().to_json()
+        }
+    }
 
-            Self::get_file(root.as_str())
+    impl FromJson for String {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::String(val) => Ok(val.clone()),
+                other => Err(JsonDecodeError::invalid_type("string", other)),
+            }
         }
+    }
 
-        pub fn json(json_val: Json) -> Self { 
-            let data = json_val.to_string().as_bytes().to_vec();
+    impl ToJson for () {
+        fn to_json(&self) -> Json {
+            Json::new(JsonType::Null)
+        }
+    }
 
-            let mut response = Self {
-                version: String::from("HTTP/1.1"), 
-                status_code: HttpResponseStatusCode::OK,
-                status_desc: format!("{:?}", HttpResponseStatusCode::OK),
-                header: Default::default(),
-                body: Default::default(), 
-            };
+    impl FromJson for () {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::Null => Ok(()),
+                other => Err(JsonDecodeError::invalid_type("null", other)),
+            }
+        }
+    }
 
-            response.set_body(data);
-            response.insert_header("content-type", "application/json; charset=utf-8");
+    impl<T: ToJson + ?Sized> ToJson for &T {
+        fn to_json(&self) -> Json {
+            (**self).to_json()
+        }
+    }
 
-            return response;
+    impl<T: ToJson + ?Sized> ToJson for Box<T> {
+        fn to_json(&self) -> Json {
+            (**self).to_json()
         }
+    }
 
-        pub fn set_status_code(&mut self, code: HttpResponseStatusCode) {
-            self.status_code = code;
+    impl<T: FromJson> FromJson for Box<T> {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            T::from_json(json).map(Box::new)
         }
 
-        pub fn get_status_code(&self) -> HttpResponseStatusCode {
-            self.status_code
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            T::from_form(json).map(Box::new)
         }
+    }
 
-        pub fn set_status_desc<T: Into<String>>(&mut self, desc: T) {
-            self.status_desc = desc.into();
+    impl<T: ToJson> ToJson for Option<T> {
+        fn to_json(&self) -> Json {
+            match self {
+                Some(val) => val.to_json(),
+                None => Json::new(JsonType::Null),
+            }
         }
+    }
 
-        pub fn insert_header<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
-            self.header.insert(key.into().to_lowercase(), val.into());
+    impl<T: ToJsonObject> ToJsonObject for Option<T> {
+        fn to_json_object(&self) -> Option<std::collections::HashMap<String, Json>> {
+            self.as_ref().and_then(|val| val.to_json_object())
         }
+    }
 
-        pub fn get_headers(&self) -> &std::collections::hash_map::HashMap<String, String> {
-            &self.header
+    impl<T: FromJson> FromJson for Option<T> {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::Null => Ok(None),
+                _ => T::from_json(json).map(Some),
+            }
         }
 
-        pub fn set_body(&mut self, body_byte: std::vec::Vec::<u8>) {
-            self.body = body_byte;
+        fn from_missing() -> Result<Self, JsonDecodeError> {
+            Ok(None)
+        }
 
-            if !self.header.contains_key("transfer-encoding") {
-                self.insert_header("content-length", self.body.len().to_string());
+        //展开的 Option 在内层的字段一个都没有时为 None
+        fn from_flattened(json: &Json, form: bool) -> Result<Self, JsonDecodeError> {
+            if let (Some(names), JsonType::Object(attr)) = (T::field_names(), json.get()) {
+                if !names.iter().any(|name| attr.contains_key(*name)) { return Ok(None); }
             }
+            T::from_flattened(json, form).map(Some)
         }
 
-        pub fn get_body(&self) -> &std::vec::Vec::<u8> {
-            &self.body
+        fn field_names() -> Option<Vec<&'static str>> {
+            T::field_names()
         }
 
-        pub fn set_version<T: Into<String>>(&mut self, version: T) {
-            self.version = version.into()
+        //表单里的空值也当作没有
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::Null => Ok(None),
+                JsonType::String(val) if val.is_empty() => Ok(None),
+                _ => T::from_form(json).map(Some),
+            }
         }
+    }
 
-        pub fn get_version(&self) -> &str {
-            self.version.as_str()
-        } 
+    impl<T: ToJson> ToJson for [T] {
+        fn to_json(&self) -> Json {
+            Json::new(JsonType::Vec(self.iter().map(ToJson::to_json).collect()))
+        }
     }
 
-    #[derive(Debug, PartialEq)]
-    enum ResponseReaderState {
-        Version,
-        StatusCode,
-        StatusDesc,
-        Header,
-        Body,
-        End,
+    impl<T: ToJson> ToJson for Vec<T> {
+        fn to_json(&self) -> Json {
+            self.as_slice().to_json()
+        }
     }
 
-    #[derive(Debug)]
-    pub struct HttpResponseReader {
-        cur_state: ResponseReaderState,
-        response: HttpResponse,
-        cache: Vec<u8>,
+    fn decode_vec<T>(json: &Json, decode: fn(&Json) -> Result<T, JsonDecodeError>) -> Result<Vec<T>, JsonDecodeError> {
+        match json.get() {
+            JsonType::Vec(arr) => arr.iter().enumerate().map(|(index, item)| {
+                decode(item).map_err(|err| err.with_index(index))
+            }).collect(),
+            other => Err(JsonDecodeError::invalid_type("array", other)),
+        }
     }
 
-    impl Default for HttpResponseReader {
-        fn default() -> Self {
-            Self {
-                cur_state: ResponseReaderState::Version,
-                response: HttpResponse::new(HttpResponseStatusCode::OK), 
-                cache: Vec::<u8>::new(),    
+    impl<T: FromJson> FromJson for Vec<T> {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            decode_vec(json, T::from_json)
+        }
+
+        //?id=1 这种单个值也当作只有一项的数组
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            match json.get() {
+                JsonType::Vec(_) => decode_vec(json, T::from_form),
+                _ => T::from_form(json).map(|val| vec![val]),
             }
         }
     }
 
-    impl HttpResponseReader {
-        pub fn read(&mut self, mut buf: Vec<u8>) -> Result<&mut Self, BacktraceError> {
-            self.cache.append(&mut buf);
+    impl<T: ToJson, S> ToJson for std::collections::HashMap<String, T, S> {
+        fn to_json(&self) -> Json {
+            Json::new(JsonType::Object(self.iter().map(|(key, val)| (key.clone(), val.to_json())).collect()))
+        }
+    }
 
-            match self.cur_state {
-                ResponseReaderState::Version => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
-                        self.response.set_version(std::str::from_utf8(&self.cache[..pos])?);
-                        self.cache = self.cache[pos + 1..].to_vec();
-                        self.cur_state = ResponseReaderState::StatusCode;
-                        
-                        return self.read(vec![]);
-                    }
-                },
-                ResponseReaderState::StatusCode => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
-                        let code_str = std::str::from_utf8(&self.cache[..pos])?;
-                        let code_u16 = code_str.parse::<u16>()?;
-                        let enum_code = HttpResponseStatusCode::try_from(code_u16)?;
-                        self.response.set_status_code(enum_code);
-                        self.cache = self.cache[pos + 1..].to_vec();
-                        self.cur_state = ResponseReaderState::StatusDesc;
-                        
-                        return self.read(vec![]);
-                    }
-                },
-                ResponseReaderState::StatusDesc => {
-                    if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
-                        self.response.set_status_desc(std::str::from_utf8(&self.cache[..pos - 1])?);
-                        self.cache = self.cache[pos + 1..].to_vec();
-                        self.cur_state = ResponseReaderState::Header;
-                        
-                        return self.read(vec![]);
-                    }
-                },
-                ResponseReaderState::Header => {
-                    if let Some(return_pos) = self.cache.iter().position(|&item| item == b'\n') {
-                        let attr = &self.cache[..return_pos];
+    impl<T: ToJson, S> ToJsonObject for std::collections::HashMap<String, T, S> {
+        fn to_json_object(&self) -> Option<std::collections::HashMap<String, Json>> {
+            Some(self.iter().map(|(key, val)| (key.clone(), val.to_json())).collect())
+        }
+    }
 
-                        if attr[0] == b'\r' { 
-                            self.cur_state = ResponseReaderState::Body;
-                            self.cache = self.cache[return_pos + 1..].to_vec();
-                        }
-                        else if let Some(split_pos) = attr.iter().position(|&item| item == b':') {
-                            let key = &attr[..split_pos];
-                            let value = &attr[split_pos + 1..return_pos - 1];
+    fn decode_map<T, S>(json: &Json, decode: fn(&Json) -> Result<T, JsonDecodeError>) -> Result<std::collections::HashMap<String, T, S>, JsonDecodeError>
+    where
+        S: std::hash::BuildHasher + Default,
+    {
+        match json.get() {
+            JsonType::Object(attr) => attr.iter().map(|(key, val)| {
+                decode(val).map(|val| (key.clone(), val)).map_err(|err| err.with_key(key))
+            }).collect(),
+            other => Err(JsonDecodeError::invalid_type("object", other)),
+        }
+    }
 
-                            self.response.insert_header(std::str::from_utf8(&key)?.trim().to_lowercase(), std::str::from_utf8(&value)?.trim());
-                            self.cache = self.cache[return_pos + 1..].to_vec();
-                        }
+    impl<T: FromJson, S: std::hash::BuildHasher + Default> FromJson for std::collections::HashMap<String, T, S> {
+        fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+            decode_map(json, T::from_json)
+        }
 
-                        return self.read(vec![]);
-                    } 
+        fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+            decode_map(json, T::from_form)
+        }
+    }
+
+    //元组按定长数组处理
+    macro_rules! json_tuple {
+        (@decode $json:ident, $decode:ident, $len:expr => $($name:ident $index:tt),+) => {
+            match $json.get() {
+                JsonType::Vec(arr) if arr.len() == $len => {
+                    Ok(($($name::$decode(&arr[$index]).map_err(|err| err.with_index($index))?,)+))
                 },
-                ResponseReaderState::Body => {
-                    if let Some(content_length) = self.response.get_headers().get("content-length") {
-                        if self.cache.len() == content_length.parse::<usize>()? {
-                            self.response.set_body(std::mem::take(&mut self.cache));
-                            self.cur_state = ResponseReaderState::End;
-                        }
-                    }
-                    else if let Some(transfer_encoding) = self.response.get_headers().get("transfer-encoding") {
-                        match transfer_encoding.as_str() {
-                            "chunked" => {
-                                if let Some(len_return_pos) = self.cache.iter().position(|&item| item == b'\r') {
-                                    let chunk_len_hex = &self.cache[..len_return_pos];
-                                    let mut chunk_len: usize = 0;
-                                    for i in 0..chunk_len_hex.len() {
-                                        chunk_len += usize::from_str_radix(&(chunk_len_hex[i] as char).to_string(), 16)? * (16usize.pow((chunk_len_hex.len() - (i + 1)).try_into()?));
-                                    }
+                JsonType::Vec(arr) => Err(JsonDecodeError::new(format!("invalid length: expected array of {}, found {}", $len, arr.len()))),
+                other => Err(JsonDecodeError::invalid_type("array", other)),
+            }
+        };
+        ($len:expr => $($name:ident $index:tt),+) => {
+            impl<$($name: ToJson),+> ToJson for ($($name,)+) {
+                fn to_json(&self) -> Json {
+                    Json::new(JsonType::Vec(vec![$(self.$index.to_json()),+]))
+                }
+            }
 
+            impl<$($name: FromJson),+> FromJson for ($($name,)+) {
+                fn from_json(json: &Json) -> Result<Self, JsonDecodeError> {
+                    json_tuple!(@decode json, from_json, $len => $($name $index),+)
+                }
 
-                                    if chunk_len == 0 {
-                                        self.cur_state = ResponseReaderState::End;
-                                    }
-                                    else {
-                                        if self.cache.len() >= len_return_pos + 2 + chunk_len + 2 {
-                                            self.cache = self.cache[len_return_pos..].to_vec();
-                                            assert_eq!('\r', self.cache[0] as char);
-                                            assert_eq!('\n', self.cache[1] as char);
-                                            self.cache = self.cache[2..].to_vec();
+                fn from_form(json: &Json) -> Result<Self, JsonDecodeError> {
+                    json_tuple!(@decode json, from_form, $len => $($name $index),+)
+                }
+            }
+        };
+    }
 
-                                            let mut temp = self.response.get_body().clone();
-                                            temp.append(&mut self.cache[..chunk_len].to_vec());
-                                            self.response.set_body(std::mem::take(&mut temp));
-                                            self.cache = self.cache[chunk_len..].to_vec();
-                                            assert_eq!('\r', self.cache[0] as char);
-                                            assert_eq!('\n', self.cache[1] as char);
-                                            self.cache = self.cache[2..].to_vec();
-                                        }
-                                    }
-                                }
-                                else { 
-                                    //return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("chunk not vaild")).into());
-                                }
-                            },
-                            _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("transfer_encoding:{transfer_encoding}")).into()),
-                        }   
+    json_tuple!(1 => A 0);
+    json_tuple!(2 => A 0, B 1);
+    json_tuple!(3 => A 0, B 1, C 2);
+    json_tuple!(4 => A 0, B 1, C 2, D 3);
+    json_tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+    json_tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+
+    //双向 TLS 中已通过校验的客户端证书
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct PeerCertificate {
+        subject: String,
+        subject_alt_names: Vec<String>,
+        fingerprint: String,
+    }
+
+    impl PeerCertificate {
+        fn from_x509(cert: &openssl::x509::X509Ref) -> Result<Self, BacktraceError> {
+            let subject = cert.subject_name().entries().map(|entry| {
+                let key = entry.object().nid().short_name().unwrap_or("?");
+                let val = entry.data().as_utf8().map(|val| val.to_string()).unwrap_or_default();
+                format!("{key}={val}")
+            }).collect::<Vec<String>>().join(",");
+
+            let subject_alt_names = cert.subject_alt_names().map(|names| names.iter().filter_map(|name| {
+                name.dnsname().or(name.email()).or(name.uri()).map(|name| name.to_string()).or_else(|| {
+                    match name.ipaddress()? {
+                        &[a, b, c, d] => Some(std::net::Ipv4Addr::new(a, b, c, d).to_string()),
+                        ip => <[u8; 16]>::try_from(ip).ok().map(|ip| std::net::Ipv6Addr::from(ip).to_string()),
                     }
-                    else { return Err(std::io::Error::new(std::io::ErrorKind::Other, "not have content-length").into()); };
-                },
-                ResponseReaderState::End => {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "is finish").into());
-                },
-            }
+                })
+            }).collect()).unwrap_or_default();
 
-            Ok(self)
+            let fingerprint = cert.digest(openssl::hash::MessageDigest::sha256())?.iter().map(|byte| format!("{byte:02x}")).collect();
+
+            Ok(Self { subject, subject_alt_names, fingerprint })
         }
 
-        pub fn is_finished(&self) -> bool {
-            return self.cur_state == ResponseReaderState::End;
+        //例如 "CN=client,O=example"
+        pub fn get_subject(&self) -> &str {
+            self.subject.as_str()
         }
 
-        pub fn get_response(self) -> Result<HttpResponse, BacktraceError> {
-            if !self.is_finished() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "read not finish").into());}
+        pub fn get_subject_alt_names(&self) -> &Vec<String> {
+            &self.subject_alt_names
+        }
 
-            return Ok(self.response);
+        //SHA-256, 小写十六进制
+        pub fn get_fingerprint(&self) -> &str {
+            self.fingerprint.as_str()
         }
     }
 
-    type WebFunc = dyn Fn(Json) -> HttpResponse + Send + Sync;
-    type RouterLink = std::collections::HashMap<String, Box<WebFunc>>;
-    #[derive(Default)]
-    pub struct Router {
-        routes: std::collections::HashMap<String, RouterLink>,
+    #[derive(Debug, Default, Clone)]
+    pub struct HttpRequest {
+        method: String,
+        uri: String,
+        query_string: String,
+        version: String,
+        header: std::collections::HashMap<String, String>,
+        body: std::vec::Vec<u8>,
+        trailers: std::collections::HashMap<String, String>,
+        peer_certificate: Option<PeerCertificate>,
     }
 
-    impl Router {
-        pub fn new() -> Self {
-            Self {
-                ..Default::default()
-            }
+    impl HttpRequest {
+        pub fn set_method<T: Into<String>>(&mut self, method: T) {
+            self.method = method.into(); 
         }
 
-        pub fn register_url<F: Fn(Json) -> HttpResponse + Send + Sync, T: Into<String>>(&mut self, method: T, url: T, func: &'static F) {
-            let method: String = method.into();
-
-            if let None = self.routes.get_mut(method.as_str()) {
-                self.routes.insert(method.to_string(), RouterLink::new());
-            }
-
-            let link = self.routes.get_mut(method.as_str()).unwrap();
-            link.insert(url.into(), Box::new(func));
+        pub fn get_method(&self) -> &String {
+            &self.method 
         }
 
-        pub fn contains_url(&self, method: &str, url: &str) -> bool {
-            if let Some(link) = self.routes.get(method) {
-                return link.contains_key(url);
+        pub fn set_uri<T: Into<String>>(&mut self, uri: T) {
+            let uri: String = uri.into();
+            if let Some(pos) = uri.find("?") {
+                let (uri, query_string) = uri.split_at(pos);
+                self.uri = uri.to_string();
+                self.query_string = query_string[1..].to_string();
             }
             else {
-                return false;
+                self.uri = uri;
             }
         }
 
-        pub fn call(&self, method: &str, url: &str, request: &HttpRequest) -> Result<HttpResponse, BacktraceError> {
-            let link = self.routes.get(method).ok_or(std::io::Error::new(std::io::ErrorKind::Other, "route have not register method"))?;
-            let func = link.get(url).ok_or(std::io::Error::new(std::io::ErrorKind::Other, "route have not register url"))?;
+        pub fn get_uri(&self) -> &String {
+            &self.uri
+        }
 
-            let def = String::new();
-            let content_type = request.get_headers().get("content-type").unwrap_or(&def);
+        pub fn get_query_string(&self) -> &String {
+            &self.query_string 
+        }
+
+        pub fn get_version(&self) -> &String {
+            &self.version
+        }
+
+        pub fn set_version<T: Into<String>>(&mut self, version: T) {
+            self.version = version.into();
+        }
+
+        pub fn insert_header<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
+            self.header.insert(key.into().to_lowercase(), val.into());
+        }
+
+        pub fn get_headers(&self) -> &std::collections::hash_map::HashMap<String, String> {
+            &self.header
+        }
+
+        pub fn set_body(&mut self, buffer: std::vec::Vec<u8>) {
+            self.body = buffer;
 
-            let json = if content_type.find("application/json").is_some() { 
-                Json::parse(std::str::from_utf8(request.get_body())?)?
+            if !self.header.contains_key("transfer-encoding") {
+                self.insert_header("content-length", self.body.len().to_string());
             }
-            else if content_type.find("application/x-www-form-urlencoded").is_some() {
-                Json::parse_form_data(std::str::from_utf8(request.get_body())?)?
+        }
+
+        pub fn get_body(&self) -> &std::vec::Vec<u8> {
+           &self.body
+        }
+
+        //chunked 请求体之后的 trailer 字段
+        pub fn insert_trailer<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
+            self.trailers.insert(key.into().to_lowercase(), val.into());
+        }
+
+        pub fn get_trailers(&self) -> &std::collections::HashMap<String, String> {
+            &self.trailers
+        }
+
+        pub fn set_peer_certificate(&mut self, cert: Option<PeerCertificate>) {
+            self.peer_certificate = cert;
+        }
+
+        pub fn get_peer_certificate(&self) -> Option<&PeerCertificate> {
+            self.peer_certificate.as_ref()
+        }
+
+        pub fn get_body_len(&self) -> usize {
+            if let Some(len_str) = self.get_headers().get("content-length") {
+                return str::parse::<usize>(len_str).unwrap()
             }
             else {
-                Json::parse_form_data(request.get_query_string())?
-            };
-
-            Ok(func(json))
+                return 0;
+            }
         }
     }
-    
-    pub struct HttpServer {
-        socket: std::net::TcpListener,
-        router: std::sync::Arc<Router>,
-        use_ssl: bool,
+
+    #[derive(Debug, PartialEq)]
+    enum RequestReaderState {
+        Head,
+        Body(usize),
+        ChunkSize,
+        ChunkData(usize),
+        Trailer,
+        End,
     }
 
-    impl HttpServer {
-        async fn send_response(stream: &mut impl Write, response: &HttpResponse) -> Result<(), BacktraceError> {
-            let head_content = format!
-                                (
-                                    "{version} {status_code} {status_desc}\r\n{header}\r\n", 
-                                    version = response.get_version(), 
-                                    status_code = response.get_status_code() as u16,
-                                    status_desc = format!("{:?}", response.get_status_code()),
-                                    header = (|| {
-                                        let mut result = String::new();
-                                        for (key, val) in response.get_headers().iter() {
-                                            result += format!("{}: {}\r\n", key, val).as_str();
-                                        }
+    //请求体的分割方式
+    enum BodyFraming {
+        Empty,
+        Length(usize),
+        Chunked,
+    }
 
-                                        return result;
-                                    })()
-                                );
+    //请求头的借用视图, 所有字段都指向原始数据, 不做拷贝
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RequestHead<'a> {
+        method: &'a str,
+        uri: &'a str,
+        version: &'a str,
+        headers: Vec<(&'a str, &'a str)>,
+    }
 
-            let wait_write = vec![head_content.as_bytes(), response.get_body().as_slice()];
-            for cur_buf in wait_write {
-                let mut had_write = 0usize;
-                while had_write < cur_buf.len() {
-                    had_write += stream.write(&cur_buf[had_write..cur_buf.len()])?;
+    impl<'a> RequestHead<'a> {
+        //buf 为请求行加 header 行, 可以带或不带结尾的空行
+        pub fn parse(buf: &'a [u8]) -> Result<Self, HttpError> {
+            let mut lines = buf.split(|&item| item == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+            let request_line = std::str::from_utf8(lines.next().unwrap_or_default()).map_err(bad_request)?;
+            let mut parts = request_line.splitn(3, ' ');
+            let (Some(method), Some(uri), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(bad_request("invalid request line"));
+            };
+            if method.is_empty() || uri.is_empty() || !version.starts_with("HTTP/") {
+                return Err(bad_request("invalid request line"));
+            }
+
+            let mut headers = Vec::new();
+            for line in lines.take_while(|line| !line.is_empty()) {
+                let line = std::str::from_utf8(line).map_err(bad_request)?;
+                let Some((key, val)) = line.split_once(':') else {
+                    return Err(bad_request(format!("invalid header: {line}")));
+                };
+                //字段名和冒号之间不允许有空白, 否则不同实现会解析出不同的字段
+                if key.is_empty() || key.contains([' ', '\t']) {
+                    return Err(bad_request(format!("invalid header: {line}")));
                 }
+                headers.push((key, val.trim()));
             }
-            //stream.flush().await.unwrap();
 
-            Ok(())
+            Ok(Self { method, uri, version, headers })
+        }
+
+        pub fn get_method(&self) -> &'a str {
+            self.method
+        }
+
+        pub fn get_uri(&self) -> &'a str {
+            self.uri
+        }
+
+        pub fn get_version(&self) -> &'a str {
+            self.version
+        }
+
+        pub fn get_headers(&self) -> &[(&'a str, &'a str)] {
+            &self.headers
+        }
+
+        //字段名不区分大小写, 重复的字段返回第一个
+        pub fn get_header(&self, name: &str) -> Option<&'a str> {
+            self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, val)| *val)
         }
 
-        fn handle_request(router: &Router, request: &HttpRequest) -> Result<HttpResponse, BacktraceError> {
-            let method = request.get_method();
-            let uri = request.get_uri();
+        //按原始顺序检查所有 Content-Length 与 Transfer-Encoding 行, 转成 HashMap 后重复的字段只剩最后一个
+        //前后端对请求边界理解可能不一致的写法 (请求走私) 都直接拒绝
+        fn framing(&self) -> Result<BodyFraming, HttpError> {
+            let values = |name: &str| self.headers.iter().filter(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, val)| *val).collect::<Vec<&str>>();
+            let transfer_encodings = values("transfer-encoding");
+            let content_lengths = values("content-length");
+
+            if !transfer_encodings.is_empty() {
+                if !content_lengths.is_empty() { return Err(bad_request("both transfer-encoding and content-length")); }
 
-            println!("handle_request:{}, {}", method, uri);
-            if router.contains_url(method, uri) {
-                router.call(method, uri, request)
+                let codings = transfer_encodings.iter().flat_map(|val| val.split(',')).map(|coding| coding.trim()).collect::<Vec<&str>>();
+                if codings.len() != 1 || !codings[0].eq_ignore_ascii_case("chunked") {
+                    return Err(HttpError::new(HttpResponseStatusCode::NotImplemented, format!("transfer-encoding not supported: {}", transfer_encodings.join(", "))));
+                }
+                return Ok(BodyFraming::Chunked);
             }
-            else {
-                let response = HttpResponse::get_root_file(uri)?;
 
-                Ok(response)
+            let Some(content_length) = content_lengths.first() else { return Ok(BodyFraming::Empty); };
+            if content_lengths.iter().any(|val| val != content_length) {
+                return Err(bad_request(format!("conflicting content-length: {}", content_lengths.join(", "))));
+            }
+            //parse 允许 "+5", 这里只接受数字
+            match content_length.parse::<usize>() {
+                Ok(len) if content_length.bytes().all(|item| item.is_ascii_digit()) => Ok(BodyFraming::Length(len)),
+                _ => Err(bad_request(format!("invalid content-length: {content_length}"))),
             }
         }
 
-        async fn handle_accept(stream: &mut impl Read) -> Result<HttpRequest, BacktraceError> {
-            let mut reader = HttpRequestReader::new();
+        fn to_request(&self) -> HttpRequest {
+            let mut request = HttpRequest::default();
+            request.set_method(self.method);
+            request.set_uri(self.uri);
+            request.set_version(self.version);
+            request.header.reserve(self.headers.len());
+            for (key, val) in self.headers.iter() {
+                request.insert_header(*key, *val);
+            }
+            request
+        }
+    }
 
-            while !reader.is_finished() {
-                let mut buf = [0u8; 1024];
-                let buf_size = stream.read(&mut buf)?;
-                //println!("{:?}", std::str::from_utf8(&buf[0..buf_size])?);
+    //chunk 大小行 (含扩展) 的最大长度
+    const MAX_CHUNK_LINE: usize = 4096;
 
-                if buf_size == 0 { return Err(std::io::Error::new(std::io::ErrorKind::Other, "socket close").into()); }
+    //超过限制时分别返回 414, 431, 413
+    #[derive(Debug, Clone, Copy)]
+    pub struct RequestLimits {
+        max_request_line: usize,
+        max_header_count: usize,
+        max_header_size: usize,
+        max_body_size: usize,
+    }
 
-                reader.read(buf[..buf_size].to_vec())?;
+    impl Default for RequestLimits {
+        fn default() -> Self {
+            Self {
+                max_request_line: 8 * 1024,
+                max_header_count: 100,
+                max_header_size: 64 * 1024,
+                max_body_size: 10 * 1024 * 1024,
             }
+        }
+    }
 
-            //println!("{:#?}", http_request);
+    impl RequestLimits {
+        pub fn new() -> Self {
+            Default::default()
+        }
 
-            //println!("finish!!!!!!!!!!!!");
+        pub fn max_request_line(&mut self, size: usize) -> &mut Self {
+            self.max_request_line = size;
+            self
+        }
+
+        pub fn max_header_count(&mut self, count: usize) -> &mut Self {
+            self.max_header_count = count;
+            self
+        }
+
+        //所有 header 行加起来的字节数
+        pub fn max_header_size(&mut self, size: usize) -> &mut Self {
+            self.max_header_size = size;
+            self
+        }
+
+        pub fn max_body_size(&mut self, size: usize) -> &mut Self {
+            self.max_body_size = size;
+            self
+        }
+    }
+
+    //所有数据放在一个缓冲区里, cursor 之前是已经处理完的部分
+    #[derive(Debug)]
+    pub struct HttpRequestReader {
+        //当前等待读取的内容
+        cur_state: RequestReaderState,
+        http_request: HttpRequest,
+        cache: Vec<u8>,
+        cursor: usize,
+        //已经找过换行符的位置, 数据分多次到达时不重复扫描
+        scanned: usize,
+        //请求头中当前行的起始位置, 请求头读完之前 cursor 不动
+        line_start: usize,
+        //请求格式不对时要返回给客户端的错误
+        rejection: Option<HttpError>,
+        limits: RequestLimits,
+        header_size: usize,
+        header_count: usize,
+        //chunked 请求体已经解码的总长度
+        body_size: usize,
+        //请求头已经交出, 请求体收到多少就解码多少, 由 take_body_data 取走
+        streaming: bool,
+    }
 
-            return Ok(reader.get_request()?)
+    impl HttpRequestReader {
+        pub fn new() -> Self {
+            Self::with_limits(Default::default())
         }
 
-        async fn accept_process(use_ssl: bool, router: std::sync::Arc::<Router>, stream: std::net::TcpStream) -> Result<(), BacktraceError> {
-            enum HttpStream<'a> {
-                TCP(&'a std::net::TcpStream),
-                SSL(openssl::ssl::SslStream::<&'a std::net::TcpStream>),
+        pub fn with_limits(limits: RequestLimits) -> Self {
+            Self {
+                cur_state: RequestReaderState::Head,
+                http_request: Default::default(),
+                cache: Default::default(),
+                cursor: 0,
+                scanned: 0,
+                line_start: 0,
+                rejection: None,
+                limits,
+                header_size: 0,
+                header_count: 0,
+                body_size: 0,
+                streaming: false,
             }
+        }
 
-            impl<'a> Read for HttpStream<'a> {
-                fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
-                    match self {
-                        HttpStream::TCP(stream) => stream.read(buf),
-                        HttpStream::SSL(stream) => stream.read(buf),
-                    }
+        fn reject<T: Into<String>>(&mut self, code: HttpResponseStatusCode, desc: T) -> BacktraceError {
+            let desc = desc.into();
+            self.rejection = Some(HttpError::new(code, desc.clone()));
+            std::io::Error::other(desc).into()
+        }
+
+        //read 出错后取出应该回复的错误, 没有时说明是连接本身的问题
+        pub fn take_rejection(&mut self) -> Option<HttpError> {
+            self.rejection.take()
+        }
+
+        //chunk 大小行: 十六进制长度, 后面可以跟 ;name=value 扩展
+        fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+            let line = std::str::from_utf8(line).ok()?;
+            let size = line.split(';').next()?.trim_end_matches([' ', '\t']);
+            if size.is_empty() || !size.bytes().all(|item| item.is_ascii_hexdigit()) { return None; }
+
+            //还要加上结尾的 CRLF, 加不上的长度当作非法
+            usize::from_str_radix(size, 16).ok().filter(|len| len.checked_add(2).is_some())
+        }
+
+        //从 from 开始找下一个换行符, 上次已经扫描过的部分直接跳过
+        fn find_line(&mut self, from: usize) -> Option<usize> {
+            let start = self.scanned.max(from);
+            match self.cache[start..].iter().position(|&item| item == b'\n') {
+                Some(pos) => {
+                    self.scanned = start + pos + 1;
+                    Some(start + pos)
+                },
+                None => {
+                    self.scanned = self.cache.len();
+                    None
                 }
             }
+        }
 
-            impl<'a> Write for HttpStream<'a> {
-                fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-                    match self {
-                        HttpStream::TCP(stream) => stream.write(buf),
-                        HttpStream::SSL(stream) => stream.write(buf),
-                    }
-                }
+        fn line(&self, start: usize, end: usize) -> &[u8] {
+            let line = &self.cache[start..end];
+            line.strip_suffix(b"\r").unwrap_or(line)
+        }
 
-                fn flush(&mut self) -> Result<(), std::io::Error> {
-                    match self {
-                        HttpStream::TCP(stream) => stream.flush(),
-                        HttpStream::SSL(stream) => stream.flush(),
-                    }
-                }
+        fn check_body(&mut self, body_len: usize) -> Result<(), BacktraceError> {
+            if body_len > self.limits.max_body_size {
+                return Err(self.reject(HttpResponseStatusCode::ContentTooLarge, "request body too large"));
             }
+            Ok(())
+        }
 
-            println!("accept_process...");
-            
-            let mut wrap_stream: HttpStream = if use_ssl { 
-                let mut acceptor = openssl::ssl::SslAcceptor::mozilla_intermediate(openssl::ssl::SslMethod::tls())?;
-                acceptor.set_private_key_file("key.pem", openssl::ssl::SslFiletype::PEM)?;
-                acceptor.set_certificate_chain_file("cert.pem")?;
-                acceptor.check_private_key()?; 
-                let acceptor = acceptor.build();
-                    HttpStream::SSL(acceptor.accept(&stream)?)
-                }
-                else {
-                    HttpStream::TCP(&stream)
-                };
+        //请求头 (包括请求行) 已经读完, 正在读请求体
+        pub fn is_header_finished(&self) -> bool {
+            self.cur_state != RequestReaderState::Head
+        }
+
+        pub fn read(&mut self, buf: Vec<u8>) -> Result<&mut Self, BacktraceError> {
+            if self.cur_state == RequestReaderState::End && !buf.is_empty() {
+                return Err(std::io::Error::other("read finish").into());
+            }
+
+            //处理完的数据在追加前移走, 只需要移动还没处理的部分
+            if self.cursor > 0 {
+                self.cache.drain(..self.cursor);
+                self.scanned = self.scanned.saturating_sub(self.cursor);
+                self.line_start = self.line_start.saturating_sub(self.cursor);
+                self.cursor = 0;
+            }
+
+            if self.cache.is_empty() {
+                self.cache = buf;
+            }
+            else {
+                self.cache.extend_from_slice(&buf);
+            }
+
+            while self.cur_state != RequestReaderState::End && self.advance()? {}
+
+            Ok(self)
+        }
+
+        //处理一步, 数据不够时返回 false 等待下一次 read
+        fn advance(&mut self) -> Result<bool, BacktraceError> {
+            match self.cur_state {
+                RequestReaderState::Head => {
+                    let is_request_line = self.line_start == self.cursor;
+                    let Some(pos) = self.find_line(self.line_start) else {
+                        let pending = self.cache.len() - self.line_start;
+                        if is_request_line && pending > self.limits.max_request_line {
+                            return Err(self.reject(HttpResponseStatusCode::URITooLong, "request line too long"));
+                        }
+                        if !is_request_line && self.header_size + pending > self.limits.max_header_size {
+                            return Err(self.reject(HttpResponseStatusCode::RequestHeaderFieldsTooLarge, "request header too large"));
+                        }
+                        return Ok(false);
+                    };
+
+                    let line_len = self.line(self.line_start, pos).len();
+                    if is_request_line {
+                        //请求行之前的空行直接忽略
+                        if line_len == 0 {
+                            self.cursor = pos + 1;
+                            self.line_start = self.cursor;
+                            return Ok(true);
+                        }
+                        if line_len > self.limits.max_request_line {
+                            return Err(self.reject(HttpResponseStatusCode::URITooLong, "request line too long"));
+                        }
+                    }
+                    else if line_len > 0 {
+                        self.header_size += pos + 1 - self.line_start;
+                        self.header_count += 1;
+                        if self.header_size > self.limits.max_header_size || self.header_count > self.limits.max_header_count {
+                            return Err(self.reject(HttpResponseStatusCode::RequestHeaderFieldsTooLarge, "request header too large"));
+                        }
+                    }
+
+                    self.line_start = pos + 1;
+                    if is_request_line || line_len > 0 { return Ok(true); }
+
+                    //空行, 整个请求头都已经收到
+                    let parsed = RequestHead::parse(&self.cache[self.cursor..pos]).and_then(|head| Ok((head.to_request(), head.framing()?)));
+                    let (request, framing) = match parsed {
+                        Ok(parsed) => parsed,
+                        Err(err) => return Err(self.reject(err.get_status_code(), err.get_message())),
+                    };
+                    self.http_request = request;
+                    self.cursor = pos + 1;
+                    self.start_body(framing)?;
+                },
+                RequestReaderState::Body(body_len) => {
+                    let pending = self.cache.len() - self.cursor;
+                    if self.streaming && pending < body_len {
+                        if pending == 0 { return Ok(false); }
+
+                        self.http_request.body.extend_from_slice(&self.cache[self.cursor..]);
+                        self.cursor += pending;
+                        self.cur_state = RequestReaderState::Body(body_len - pending);
+                        return Ok(true);
+                    }
+                    if pending < body_len { return Ok(false); }
+
+                    //缓冲区里正好只有请求体时直接拿走, 不用拷贝
+                    let body = if self.cursor == 0 && self.cache.len() == body_len {
+                        self.scanned = 0;
+                        self.line_start = 0;
+                        std::mem::take(&mut self.cache)
+                    }
+                    else {
+                        self.cursor += body_len;
+                        self.cache[self.cursor - body_len..self.cursor].to_vec()
+                    };
+                    self.http_request.set_body(body);
+                    self.cur_state = RequestReaderState::End;
+                },
+                RequestReaderState::ChunkSize => {
+                    let pos = self.find_line(self.cursor);
+                    if pos.unwrap_or(self.cache.len()) - self.cursor > MAX_CHUNK_LINE {
+                        return Err(self.reject(HttpResponseStatusCode::BadRequest, "chunk size line too long"));
+                    }
+                    let Some(pos) = pos else { return Ok(false); };
+
+                    let Some(chunk_len) = Self::parse_chunk_size(self.line(self.cursor, pos)) else {
+                        return Err(self.reject(HttpResponseStatusCode::BadRequest, "invalid chunk size"));
+                    };
+                    self.body_size = self.body_size.saturating_add(chunk_len);
+                    self.check_body(self.body_size)?;
+
+                    self.cursor = pos + 1;
+                    self.cur_state = if chunk_len == 0 { RequestReaderState::Trailer } else { RequestReaderState::ChunkData(chunk_len) };
+                },
+                RequestReaderState::ChunkData(chunk_len) => {
+                    let pending = (self.cache.len() - self.cursor).min(chunk_len);
+                    if self.streaming && pending > 0 && self.cache.len() - self.cursor < chunk_len + 2 {
+                        self.http_request.body.extend_from_slice(&self.cache[self.cursor..self.cursor + pending]);
+                        self.cursor += pending;
+                        self.cur_state = RequestReaderState::ChunkData(chunk_len - pending);
+                        return Ok(true);
+                    }
+                    if self.cache.len() - self.cursor < chunk_len + 2 { return Ok(false); }
+
+                    let data_end = self.cursor + chunk_len;
+                    if &self.cache[data_end..data_end + 2] != b"\r\n" {
+                        return Err(self.reject(HttpResponseStatusCode::BadRequest, "chunk data not end with CRLF"));
+                    }
+
+                    self.http_request.body.extend_from_slice(&self.cache[self.cursor..data_end]);
+                    self.cursor = data_end + 2;
+                    self.cur_state = RequestReaderState::ChunkSize;
+                },
+                RequestReaderState::Trailer => {
+                    let pos = self.find_line(self.cursor);
+                    if self.header_size + pos.unwrap_or(self.cache.len()) - self.cursor > self.limits.max_header_size {
+                        return Err(self.reject(HttpResponseStatusCode::RequestHeaderFieldsTooLarge, "request header too large"));
+                    }
+                    let Some(pos) = pos else { return Ok(false); };
+
+                    let line = String::from_utf8_lossy(self.line(self.cursor, pos)).into_owned();
+                    self.header_size += pos + 1 - self.cursor;
+                    self.cursor = pos + 1;
+
+                    if line.is_empty() {
+                        self.cur_state = RequestReaderState::End;
+                        return Ok(false);
+                    }
+
+                    let Some((key, val)) = line.split_once(':') else {
+                        return Err(self.reject(HttpResponseStatusCode::BadRequest, "invalid trailer"));
+                    };
+                    self.header_count += 1;
+                    if self.header_count > self.limits.max_header_count {
+                        return Err(self.reject(HttpResponseStatusCode::RequestHeaderFieldsTooLarge, "too many request headers"));
+                    }
+                    self.http_request.insert_trailer(key.trim(), val.trim());
+                },
+                RequestReaderState::End => return Ok(false),
+            }
+
+            Ok(true)
+        }
+
+        //根据 Transfer-Encoding 与 Content-Length 决定请求体的读法
+        fn start_body(&mut self, framing: BodyFraming) -> Result<(), BacktraceError> {
+            self.cur_state = match framing {
+                BodyFraming::Chunked => RequestReaderState::ChunkSize,
+                BodyFraming::Length(body_len) => {
+                    self.check_body(body_len)?;
+                    if body_len > 0 { RequestReaderState::Body(body_len) } else { RequestReaderState::End }
+                },
+                BodyFraming::Empty => RequestReaderState::End,
+            };
+
+            Ok(())
+        }
+
+        pub fn is_finished(&self) -> bool {
+            return self.cur_state == RequestReaderState::End;
+        }
+
+        pub fn get_request(self) -> Result<HttpRequest, BacktraceError> {
+            if !self.is_finished() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "read not finish").into());}
+
+            return Ok(self.http_request);
+        }
+
+        //取出已读完的请求, 缓存中剩余的字节留给下一个请求
+        pub fn take_request(&mut self) -> Result<HttpRequest, BacktraceError> {
+            if !self.is_finished() { return Err(std::io::Error::other("read not finish").into()); }
+
+            self.cur_state = RequestReaderState::Head;
+            self.line_start = self.cursor;
+            self.header_size = 0;
+            self.header_count = 0;
+            self.body_size = 0;
+            self.streaming = false;
+            Ok(std::mem::take(&mut self.http_request))
+        }
+
+        //请求头读完后可以查看, 用来决定请求体的读法
+        pub fn get_head(&self) -> Option<&HttpRequest> {
+            if !self.is_header_finished() || self.streaming { return None; }
+            Some(&self.http_request)
+        }
+
+        //只取出请求头, 之后的请求体通过 take_body_data 分段取出, 读完后仍然要调用 take_request
+        pub fn take_head(&mut self) -> Result<HttpRequest, BacktraceError> {
+            if !self.is_header_finished() || self.streaming { return Err(std::io::Error::other("header not finish").into()); }
+
+            let mut request = std::mem::take(&mut self.http_request);
+            self.http_request.body = std::mem::take(&mut request.body);
+            self.streaming = true;
+
+            //已经收到但还没收全的请求体现在就可以交出去
+            while self.cur_state != RequestReaderState::End && self.advance()? {}
+            Ok(request)
+        }
+
+        pub fn is_streaming(&self) -> bool {
+            self.streaming
+        }
+
+        //取出 take_head 之后已经解码的请求体
+        pub fn take_body_data(&mut self) -> Vec<u8> {
+            std::mem::take(&mut self.http_request.body)
+        }
+
+        pub fn has_buffered(&self) -> bool {
+            self.cursor < self.cache.len() || self.cur_state != RequestReaderState::Head
+        }
+    }
+
+    macro_rules! status_codes {
+        ($($name:ident = $code:literal, $reason:literal;)*) => {
+            #[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, IntoPrimitive)]
+            #[repr(u16)]
+            pub enum HttpResponseStatusCode {
+                $($name = $code,)*
+                //没有登记的状态码原样保留
+                #[num_enum(catch_all)]
+                Unknown(u16),
+            }
+
+            impl HttpResponseStatusCode {
+                //未登记的状态码没有原因短语
+                pub fn get_reason_phrase(&self) -> &'static str {
+                    match self {
+                        $(Self::$name => $reason,)*
+                        Self::Unknown(_) => "",
+                    }
+                }
+            }
+        };
+    }
+
+    //IANA HTTP Status Code Registry
+    status_codes! {
+        Continue = 100, "Continue";
+        SwitchingProtocols = 101, "Switching Protocols";
+        Processing = 102, "Processing";
+        EarlyHints = 103, "Early Hints";
+        OK = 200, "OK";
+        Created = 201, "Created";
+        Accepted = 202, "Accepted";
+        NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+        NoContent = 204, "No Content";
+        ResetContent = 205, "Reset Content";
+        PartialContent = 206, "Partial Content";
+        MultiStatus = 207, "Multi-Status";
+        AlreadyReported = 208, "Already Reported";
+        IMUsed = 226, "IM Used";
+        MultipleChoices = 300, "Multiple Choices";
+        MovedPermanently = 301, "Moved Permanently";
+        Found = 302, "Found";
+        SeeOther = 303, "See Other";
+        NotModified = 304, "Not Modified";
+        UseProxy = 305, "Use Proxy";
+        TemporaryRedirect = 307, "Temporary Redirect";
+        PermanentRedirect = 308, "Permanent Redirect";
+        BadRequest = 400, "Bad Request";
+        Unauthorized = 401, "Unauthorized";
+        PaymentRequired = 402, "Payment Required";
+        Forbidden = 403, "Forbidden";
+        NotFound = 404, "Not Found";
+        MethodNotAllowed = 405, "Method Not Allowed";
+        NotAcceptable = 406, "Not Acceptable";
+        ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+        RequestTimeout = 408, "Request Timeout";
+        Conflict = 409, "Conflict";
+        Gone = 410, "Gone";
+        LengthRequired = 411, "Length Required";
+        PreconditionFailed = 412, "Precondition Failed";
+        ContentTooLarge = 413, "Content Too Large";
+        URITooLong = 414, "URI Too Long";
+        UnsupportedMediaType = 415, "Unsupported Media Type";
+        RangeNotSatisfiable = 416, "Range Not Satisfiable";
+        ExpectationFailed = 417, "Expectation Failed";
+        MisdirectedRequest = 421, "Misdirected Request";
+        UnprocessableContent = 422, "Unprocessable Content";
+        Locked = 423, "Locked";
+        FailedDependency = 424, "Failed Dependency";
+        TooEarly = 425, "Too Early";
+        UpgradeRequired = 426, "Upgrade Required";
+        PreconditionRequired = 428, "Precondition Required";
+        TooManyRequests = 429, "Too Many Requests";
+        RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+        UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+        InternalServerError = 500, "Internal Server Error";
+        NotImplemented = 501, "Not Implemented";
+        BadGateway = 502, "Bad Gateway";
+        ServiceUnavailable = 503, "Service Unavailable";
+        GatewayTimeout = 504, "Gateway Timeout";
+        HTTPVersionNotSupported = 505, "HTTP Version Not Supported";
+        VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+        InsufficientStorage = 507, "Insufficient Storage";
+        LoopDetected = 508, "Loop Detected";
+        NotExtended = 510, "Not Extended";
+        NetworkAuthenticationRequired = 511, "Network Authentication Required";
+    }
+
+    impl HttpResponseStatusCode {
+        pub fn as_u16(self) -> u16 {
+            self.into()
+        }
+
+        //1xx, 204 与 304 的响应没有响应体
+        fn has_body(self) -> bool {
+            let code = self.as_u16();
+            code >= 200 && code != 204 && code != 304
+        }
+    }
+
+    type TrailerFunc = dyn FnOnce() -> std::collections::HashMap<String, String> + Send;
+
+    //不放进内存的响应体, 发送时边读边写
+    enum ResponseStream {
+        //文件与 content-length 中的长度
+        File(async_std::fs::File, u64),
+        Stream(futures::stream::BoxStream<'static, Result<Vec<u8>, BacktraceError>>),
+    }
+
+    impl std::fmt::Debug for ResponseStream {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                ResponseStream::File(file, len) => f.debug_tuple("File").field(file).field(len).finish(),
+                ResponseStream::Stream(_) => f.write_str("Stream"),
+            }
+        }
+    }
+
+    struct Trailers(Box<TrailerFunc>);
+
+    impl std::fmt::Debug for Trailers {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("Trailers")
+        }
+    }
+
+    //超过这个大小的文件不再整个读进内存
+    const STREAM_FILE_SIZE: u64 = 256 * 1024;
+
+    #[derive(Debug)]
+    pub struct HttpResponse {
+        version: String, 
+        status_code: HttpResponseStatusCode,
+        status_desc: String,
+        header: std::collections::HashMap<String, String>,  
+        body: std::vec::Vec::<u8>,
+        stream: Option<ResponseStream>,
+        trailers: Option<Trailers>,
+    }
+
+    impl HttpResponse {
+        pub fn new(code: HttpResponseStatusCode) -> Self {
+            Self { 
+                version: String::from("HTTP/1.1"), 
+                status_code: code,
+                status_desc: code.get_reason_phrase().to_string(),
+                header: Default::default(),
+                body: Default::default(),
+                stream: None,
+                trailers: None,
+            }
+        }
+
+        //大文件边读边发, 小文件读进内存以便压缩; ETag 与 Last-Modified 由文件大小和修改时间得出
+        fn file_response(mut file: std::fs::File, path: &std::path::Path) -> Result<HttpResponse, BacktraceError> {
+            let mut response = HttpResponse::new(HttpResponseStatusCode::OK);
+            response.insert_header("content-type", mime_type(path));
+
+            let metadata = file.metadata()?;
+            response.insert_validators(&metadata);
+
+            if metadata.len() > STREAM_FILE_SIZE {
+                response.set_file(file)?;
+            }
+            else {
+                let mut buffer = Vec::new();
+                file.read_to_end(&mut buffer)?;
+                response.set_body(buffer);
+            }
+            Ok(response)
+        }
+
+        //ETag 与 Last-Modified 只依赖文件的长度与修改时间
+        fn insert_validators(&mut self, metadata: &std::fs::Metadata) {
+            if let Ok(modified) = metadata.modified() {
+                let mtime = modified.duration_since(std::time::UNIX_EPOCH).map(|time| time.as_nanos()).unwrap_or_default();
+                //压缩后内容会变, 所以用弱 ETag
+                self.insert_header("etag", format!("W/\"{:x}-{:x}\"", metadata.len(), mtime));
+                self.insert_header("last-modified", http_date(modified));
+            }
+        }
+
+        fn get_file(path: &str) -> Result<HttpResponse, BacktraceError> {
+            let file_res = std::fs::OpenOptions::new().read(true).open(path);
+            match file_res {
+                Ok(file) => Self::file_response(file, std::path::Path::new(path)),
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound {
+                        let mut response = HttpResponse::new(HttpResponseStatusCode::NotFound);
+
+                        response.set_body("404 not found".into());
+
+                        Ok(response)
+                    }
+                    else {
+                        Ok(HttpResponse::new(HttpResponseStatusCode::InternalServerError))
+                    }
+                }
+            }
+        }
+
+        pub fn view(path: &str) -> Result<HttpResponse, BacktraceError> {
+            let mut view: String = String::from("views/");
+            view += path;
+
+            Self::get_file(view.as_str())
+        }
+
+        //uri 是请求中未解码的路径, 按 wwwroot 下的静态文件处理
+        pub fn get_root_file(uri: &str) -> Result<HttpResponse, BacktraceError> {
+            StaticFiles::default().serve(uri)
+        }
+
+        pub fn json(json_val: Json) -> Self { 
+            let data = json_val.to_string().as_bytes().to_vec();
+
+            let mut response = Self {
+                version: String::from("HTTP/1.1"), 
+                status_code: HttpResponseStatusCode::OK,
+                status_desc: HttpResponseStatusCode::OK.get_reason_phrase().to_string(),
+                header: Default::default(),
+                body: Default::default(), 
+                stream: None,
+                trailers: None,
+            };
+
+            response.set_body(data);
+            response.insert_header("content-type", "application/json; charset=utf-8");
+
+            return response;
+        }
+
+        //原因短语同时换成新状态码的标准写法, 需要自定义时之后再调用 set_status_desc
+        pub fn set_status_code(&mut self, code: HttpResponseStatusCode) {
+            self.status_code = code;
+            self.status_desc = code.get_reason_phrase().to_string();
+        }
+
+        pub fn get_status_code(&self) -> HttpResponseStatusCode {
+            self.status_code
+        }
+
+        pub fn set_status_desc<T: Into<String>>(&mut self, desc: T) {
+            self.status_desc = desc.into();
+        }
+
+        pub fn get_status_desc(&self) -> &str {
+            &self.status_desc
+        }
+
+        pub fn insert_header<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
+            self.header.insert(key.into().to_lowercase(), val.into());
+        }
+
+        pub fn get_headers(&self) -> &std::collections::hash_map::HashMap<String, String> {
+            &self.header
+        }
+
+        pub fn remove_header(&mut self, key: &str) -> Option<String> {
+            self.header.remove(&key.to_lowercase())
+        }
+
+        pub fn set_body(&mut self, body_byte: std::vec::Vec::<u8>) {
+            self.body = body_byte;
+            self.stream = None;
+
+            if !self.header.contains_key("transfer-encoding") {
+                self.insert_header("content-length", self.body.len().to_string());
+            }
+        }
+
+        pub fn get_body(&self) -> &std::vec::Vec::<u8> {
+            &self.body
+        }
+
+        //长度未知的响应体, 用 chunked 编码发送, HTTP/1.0 客户端则发送完后关闭连接
+        pub fn set_stream<S>(&mut self, stream: S)
+        where
+            S: futures::Stream<Item = Result<Vec<u8>, BacktraceError>> + Send + 'static,
+        {
+            self.body.clear();
+            self.header.remove("content-length");
+            self.stream = Some(ResponseStream::Stream(Box::pin(stream)));
+        }
+
+        //文件长度已知, 仍然使用 content-length
+        pub fn set_file(&mut self, file: std::fs::File) -> Result<(), BacktraceError> {
+            let len = file.metadata()?.len();
+            self.body.clear();
+            self.insert_header("content-length", len.to_string());
+            self.stream = Some(ResponseStream::File(async_std::fs::File::from(file), len));
+            Ok(())
+        }
+
+        //流式响应体发送完后调用, 返回的字段作为 chunked 的 trailer 发送
+        pub fn set_trailers<F: FnOnce() -> std::collections::HashMap<String, String> + Send + 'static>(&mut self, func: F) {
+            self.trailers = Some(Trailers(Box::new(func)));
+        }
+
+        pub fn is_streaming(&self) -> bool {
+            self.stream.is_some()
+        }
+
+        //没有 content-length 的流式响应体需要 chunked 编码
+        fn is_chunked(&self) -> bool {
+            matches!(self.stream, Some(ResponseStream::Stream(_))) && !self.header.contains_key("content-length")
+        }
+
+        pub fn set_version<T: Into<String>>(&mut self, version: T) {
+            self.version = version.into()
+        }
+
+        pub fn get_version(&self) -> &str {
+            self.version.as_str()
+        } 
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum ResponseReaderState {
+        Version,
+        StatusCode,
+        StatusDesc,
+        Header,
+        Body,
+        End,
+    }
+
+    #[derive(Debug)]
+    pub struct HttpResponseReader {
+        cur_state: ResponseReaderState,
+        response: HttpResponse,
+        cache: Vec<u8>,
+    }
+
+    impl Default for HttpResponseReader {
+        fn default() -> Self {
+            Self {
+                cur_state: ResponseReaderState::Version,
+                response: HttpResponse::new(HttpResponseStatusCode::OK), 
+                cache: Vec::<u8>::new(),    
+            }
+        }
+    }
+
+    impl HttpResponseReader {
+        pub fn read(&mut self, mut buf: Vec<u8>) -> Result<&mut Self, BacktraceError> {
+            self.cache.append(&mut buf);
+
+            match self.cur_state {
+                ResponseReaderState::Version => {
+                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
+                        self.response.set_version(std::str::from_utf8(&self.cache[..pos])?);
+                        self.cache = self.cache[pos + 1..].to_vec();
+                        self.cur_state = ResponseReaderState::StatusCode;
+                        
+                        return self.read(vec![]);
+                    }
+                },
+                ResponseReaderState::StatusCode => {
+                    if let Some(pos) = self.cache.iter().position(|&item| item == b' ') {
+                        let code_str = std::str::from_utf8(&self.cache[..pos])?;
+                        if code_str.len() != 3 || !code_str.bytes().all(|item| item.is_ascii_digit()) {
+                            return Err(std::io::Error::other(format!("invalid status code: {code_str}")).into());
+                        }
+                        //未登记的状态码保留为 Unknown
+                        let enum_code = HttpResponseStatusCode::from(code_str.parse::<u16>()?);
+                        self.response.set_status_code(enum_code);
+                        self.cache = self.cache[pos + 1..].to_vec();
+                        self.cur_state = ResponseReaderState::StatusDesc;
+                        
+                        return self.read(vec![]);
+                    }
+                },
+                ResponseReaderState::StatusDesc => {
+                    if let Some(pos) = self.cache.iter().position(|&item| item == b'\n') {
+                        self.response.set_status_desc(std::str::from_utf8(&self.cache[..pos - 1])?);
+                        self.cache = self.cache[pos + 1..].to_vec();
+                        self.cur_state = ResponseReaderState::Header;
+                        
+                        return self.read(vec![]);
+                    }
+                },
+                ResponseReaderState::Header => {
+                    if let Some(return_pos) = self.cache.iter().position(|&item| item == b'\n') {
+                        let attr = &self.cache[..return_pos];
+
+                        if attr[0] == b'\r' { 
+                            self.cur_state = ResponseReaderState::Body;
+                            self.cache = self.cache[return_pos + 1..].to_vec();
+                        }
+                        else if let Some(split_pos) = attr.iter().position(|&item| item == b':') {
+                            let key = &attr[..split_pos];
+                            let value = &attr[split_pos + 1..return_pos - 1];
+
+                            self.response.insert_header(std::str::from_utf8(&key)?.trim().to_lowercase(), std::str::from_utf8(&value)?.trim());
+                            self.cache = self.cache[return_pos + 1..].to_vec();
+                        }
+
+                        return self.read(vec![]);
+                    } 
+                },
+                ResponseReaderState::Body => {
+                    //1xx, 204 与 304 没有响应体, 不管响应头怎么写
+                    if !self.response.get_status_code().has_body() {
+                        self.cur_state = ResponseReaderState::End;
+                    }
+                    else if let Some(content_length) = self.response.get_headers().get("content-length") {
+                        if self.cache.len() == content_length.parse::<usize>()? {
+                            self.response.set_body(std::mem::take(&mut self.cache));
+                            self.cur_state = ResponseReaderState::End;
+                        }
+                    }
+                    else if let Some(transfer_encoding) = self.response.get_headers().get("transfer-encoding") {
+                        match transfer_encoding.as_str() {
+                            "chunked" => {
+                                if let Some(len_return_pos) = self.cache.iter().position(|&item| item == b'\r') {
+                                    let chunk_len_hex = &self.cache[..len_return_pos];
+                                    let mut chunk_len: usize = 0;
+                                    for i in 0..chunk_len_hex.len() {
+                                        chunk_len += usize::from_str_radix(&(chunk_len_hex[i] as char).to_string(), 16)? * (16usize.pow((chunk_len_hex.len() - (i + 1)).try_into()?));
+                                    }
+
+
+                                    if chunk_len == 0 {
+                                        self.cur_state = ResponseReaderState::End;
+                                    }
+                                    else {
+                                        if self.cache.len() >= len_return_pos + 2 + chunk_len + 2 {
+                                            self.cache = self.cache[len_return_pos..].to_vec();
+                                            assert_eq!('\r', self.cache[0] as char);
+                                            assert_eq!('\n', self.cache[1] as char);
+                                            self.cache = self.cache[2..].to_vec();
+
+                                            let mut temp = self.response.get_body().clone();
+                                            temp.append(&mut self.cache[..chunk_len].to_vec());
+                                            self.response.set_body(std::mem::take(&mut temp));
+                                            self.cache = self.cache[chunk_len..].to_vec();
+                                            assert_eq!('\r', self.cache[0] as char);
+                                            assert_eq!('\n', self.cache[1] as char);
+                                            self.cache = self.cache[2..].to_vec();
+                                        }
+                                    }
+                                }
+                                else { 
+                                    //return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("chunk not vaild")).into());
+                                }
+                            },
+                            _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("transfer_encoding:{transfer_encoding}")).into()),
+                        }   
+                    }
+                    else { return Err(std::io::Error::new(std::io::ErrorKind::Other, "not have content-length").into()); };
+                },
+                ResponseReaderState::End => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "is finish").into());
+                },
+            }
+
+            Ok(self)
+        }
+
+        pub fn is_finished(&self) -> bool {
+            return self.cur_state == ResponseReaderState::End;
+        }
+
+        pub fn get_response(self) -> Result<HttpResponse, BacktraceError> {
+            if !self.is_finished() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "read not finish").into());}
+
+            return Ok(self.response);
+        }
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct PathParams {
+        params: Vec<(String, String)>,
+    }
+
+    impl PathParams {
+        pub fn get(&self, name: &str) -> Option<&str> {
+            self.params.iter().find(|(key, _)| key == name).map(|(_, val)| val.as_str())
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+            self.params.iter().map(|(key, val)| (key.as_str(), val.as_str()))
+        }
+
+        pub fn len(&self) -> usize {
+            self.params.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.params.is_empty()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct HttpError {
+        status_code: HttpResponseStatusCode,
+        message: String,
+    }
+
+    impl HttpError {
+        pub fn new<T: Into<String>>(code: HttpResponseStatusCode, message: T) -> Self {
+            Self {
+                status_code: code,
+                message: message.into(),
+            }
+        }
+
+        pub fn get_status_code(&self) -> HttpResponseStatusCode {
+            self.status_code
+        }
+
+        pub fn get_message(&self) -> &str {
+            self.message.as_str()
+        }
+    }
+
+    impl std::fmt::Display for HttpError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{} {}: {}", self.status_code.as_u16(), self.status_code.get_reason_phrase(), self.message)
+        }
+    }
+
+    impl<E: std::error::Error> From<E> for HttpError {
+        fn from(err: E) -> HttpError {
+            Self::new(HttpResponseStatusCode::InternalServerError, err.to_string())
+        }
+    }
+
+    impl From<BacktraceError> for HttpError {
+        fn from(err: BacktraceError) -> HttpError {
+            Self::new(HttpResponseStatusCode::InternalServerError, err.err_desc)
+        }
+    }
+
+    type BodySender = futures::channel::mpsc::Sender<Result<Vec<u8>, std::io::Error>>;
+
+    //RouteHandle::stream_body 的路由收到的请求体, 边从连接读取边交给 handler, 读到 0 表示结束
+    #[derive(Debug)]
+    pub struct RequestBody {
+        receiver: futures::channel::mpsc::Receiver<Result<Vec<u8>, std::io::Error>>,
+        chunk: Vec<u8>,
+        pos: usize,
+    }
+
+    impl RequestBody {
+        fn channel() -> (BodySender, Self) {
+            let (sender, receiver) = futures::channel::mpsc::channel(1);
+            (sender, Self { receiver, chunk: Vec::new(), pos: 0 })
+        }
+    }
+
+    impl async_std::io::Read for RequestBody {
+        fn poll_read(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut [u8]) -> std::task::Poll<std::io::Result<usize>> {
+            while self.pos == self.chunk.len() {
+                match futures::ready!(futures::StreamExt::poll_next_unpin(&mut self.receiver, cx)) {
+                    Some(Ok(chunk)) => {
+                        self.chunk = chunk;
+                        self.pos = 0;
+                    },
+                    Some(Err(e)) => return std::task::Poll::Ready(Err(e)),
+                    None => return std::task::Poll::Ready(Ok(0)),
+                }
+            }
+
+            let len = buf.len().min(self.chunk.len() - self.pos);
+            buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+            self.pos += len;
+            std::task::Poll::Ready(Ok(len))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct RequestContext {
+        request: HttpRequest,
+        params: PathParams,
+        json: Json,
+        peer_addr: Option<std::net::SocketAddr>,
+        states: std::sync::Arc<StateMap>,
+        body: std::sync::Mutex<Option<RequestBody>>,
+        multipart_limits: MultipartLimits,
+        files: Vec<UploadedFile>,
+    }
+
+    impl RequestContext {
+        pub fn get_request(&self) -> &HttpRequest {
+            &self.request
+        }
+
+        pub fn get_params(&self) -> &PathParams {
+            &self.params
+        }
+
+        pub fn get_param(&self, name: &str) -> Option<&str> {
+            self.params.get(name)
+        }
+
+        pub fn get_json(&self) -> &Json {
+            &self.json
+        }
+
+        pub fn take_json(&mut self) -> Json {
+            std::mem::replace(&mut self.json, Json::new(JsonType::Null))
+        }
+
+        pub fn get_peer_addr(&self) -> Option<std::net::SocketAddr> {
+            self.peer_addr
+        }
+
+        //multipart/form-data 请求中的文件, 普通字段在 get_json 中
+        pub fn get_files(&self) -> &Vec<UploadedFile> {
+            &self.files
+        }
+
+        pub fn take_files(&mut self) -> Vec<UploadedFile> {
+            std::mem::take(&mut self.files)
+        }
+
+        //只有 stream_body 的路由有, 只能取一次
+        pub fn take_body(&self) -> Option<RequestBody> {
+            self.body.lock().ok()?.take()
+        }
+
+        pub fn get_state<T: Send + Sync + 'static>(&self) -> Option<std::sync::Arc<T>> {
+            let state = self.states.get(&std::any::TypeId::of::<T>())?.clone();
+            state.downcast::<T>().ok()
+        }
+    }
+
+    //同步与异步 handler 都可以返回 HttpResponse 或 Result<HttpResponse, E>
+    pub trait IntoHandlerResult {
+        fn into_handler_result(self) -> Result<HttpResponse, HttpError>;
+    }
+
+    impl IntoHandlerResult for HttpResponse {
+        fn into_handler_result(self) -> Result<HttpResponse, HttpError> {
+            Ok(self)
+        }
+    }
+
+    impl<E: Into<HttpError>> IntoHandlerResult for Result<HttpResponse, E> {
+        fn into_handler_result(self) -> Result<HttpResponse, HttpError> {
+            self.map_err(|e| e.into())
+        }
+    }
+
+    //handler 参数在调用前从请求中解析, 失败时直接作为错误响应返回
+    pub trait FromRequest: Sized {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError>;
+    }
+
+    impl<T: FromRequest> FromRequest for Option<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            Ok(T::from_request(ctx).ok())
+        }
+    }
+
+    impl FromRequest for HttpRequest {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            Ok(ctx.request.clone())
+        }
+    }
+
+    impl FromRequest for PathParams {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            Ok(ctx.params.clone())
+        }
+    }
+
+    //Router::add_state 注册的共享状态, 没有注册时返回 500
+    #[derive(Debug)]
+    pub struct State<T>(pub std::sync::Arc<T>);
+
+    impl<T> Clone for State<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> std::ops::Deref for State<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T: Send + Sync + 'static> FromRequest for State<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            match ctx.get_state::<T>() {
+                Some(state) => Ok(State(state)),
+                None => Err(HttpError::new(HttpResponseStatusCode::InternalServerError, format!("state not registered: {}", std::any::type_name::<T>()))),
+            }
+        }
+    }
+
+    //content-type 去掉参数后与 mime 比较, 不区分大小写
+    fn has_content_type(request: &HttpRequest, mime: &str) -> bool {
+        let Some(content_type) = request.get_headers().get("content-type") else { return false; };
+        let content_type = content_type.split(';').next().unwrap_or_default().trim();
+
+        content_type.eq_ignore_ascii_case(mime)
+    }
+
+    fn bad_request<T: std::fmt::Display>(desc: T) -> HttpError {
+        HttpError::new(HttpResponseStatusCode::BadRequest, desc.to_string())
+    }
+
+    //路径参数, 可以是单个值、元组或按参数名解析的结构体
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Path<T>(pub T);
+
+    impl<T: FromJson> FromRequest for Path<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            let object = ctx.params.iter().map(|(key, val)| (key.to_string(), Json::from(val))).collect();
+
+            let result = match T::from_form(&Json::new(JsonType::Object(object))) {
+                Err(err) if err.get_path() == "$" => {
+                    let mut values = ctx.params.iter().map(|(_, val)| Json::from(val)).collect::<Vec<Json>>();
+                    let json = if values.len() == 1 { values.remove(0) } else { Json::new(JsonType::Vec(values)) };
+                    T::from_form(&json)
+                },
+                result => result,
+            };
+
+            Ok(Path(result.map_err(bad_request)?))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Query<T>(pub T);
+
+    impl<T: FromJson> FromRequest for Query<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            let json = Json::parse_form_data(ctx.request.get_query_string()).map_err(|e| bad_request(e.err_desc))?;
+            Ok(Query(T::from_form(&json).map_err(bad_request)?))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct JsonBody<T>(pub T);
+
+    impl<T: FromJson> FromRequest for JsonBody<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            if !has_content_type(&ctx.request, "application/json") {
+                return Err(HttpError::new(HttpResponseStatusCode::UnsupportedMediaType, "expected content-type application/json"));
+            }
+
+            let body = std::str::from_utf8(ctx.request.get_body()).map_err(bad_request)?;
+            let json = Json::parse(body).map_err(|e| bad_request(e.err_desc))?;
+            Ok(JsonBody(T::from_json(&json).map_err(bad_request)?))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Form<T>(pub T);
+
+    impl<T: FromJson> FromRequest for Form<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            if !has_content_type(&ctx.request, "application/x-www-form-urlencoded") {
+                return Err(HttpError::new(HttpResponseStatusCode::UnsupportedMediaType, "expected content-type application/x-www-form-urlencoded"));
+            }
+
+            let body = std::str::from_utf8(ctx.request.get_body()).map_err(bad_request)?;
+            let json = Json::parse_form_data(body).map_err(|e| bad_request(e.err_desc))?;
+            Ok(Form(T::from_form(&json).map_err(bad_request)?))
+        }
+    }
+
+    //每个部分头部的最大长度
+    const MAX_PART_HEADER: usize = 8 * 1024;
+
+    //超过限制时返回 413, 文件大于 memory_limit 时写到临时文件
+    #[derive(Debug, Clone, Copy)]
+    pub struct MultipartLimits {
+        max_part_size: usize,
+        max_total_size: usize,
+        max_parts: usize,
+        memory_limit: usize,
+    }
+
+    impl Default for MultipartLimits {
+        fn default() -> Self {
+            Self {
+                max_part_size: 10 * 1024 * 1024,
+                max_total_size: 10 * 1024 * 1024,
+                max_parts: 100,
+                memory_limit: 256 * 1024,
+            }
+        }
+    }
+
+    impl MultipartLimits {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        pub fn max_part_size(&mut self, size: usize) -> &mut Self {
+            self.max_part_size = size;
+            self
+        }
+
+        //所有部分的内容加起来的字节数
+        pub fn max_total_size(&mut self, size: usize) -> &mut Self {
+            self.max_total_size = size;
+            self
+        }
+
+        pub fn max_parts(&mut self, count: usize) -> &mut Self {
+            self.max_parts = count;
+            self
+        }
+
+        pub fn memory_limit(&mut self, size: usize) -> &mut Self {
+            self.memory_limit = size;
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    enum UploadData {
+        Memory(Vec<u8>),
+        TempFile(std::path::PathBuf),
+    }
+
+    //multipart/form-data 中带 filename 的部分, 临时文件在释放时删除
+    #[derive(Debug)]
+    pub struct UploadedFile {
+        name: String,
+        file_name: String,
+        content_type: String,
+        headers: std::collections::HashMap<String, String>,
+        size: usize,
+        data: UploadData,
+    }
+
+    impl UploadedFile {
+        fn new(name: String, file_name: String, headers: std::collections::HashMap<String, String>, content: &[u8], limits: &MultipartLimits) -> Result<Self, BacktraceError> {
+            let data = if content.len() > limits.memory_limit {
+                let path = std::env::temp_dir().join(format!("rust_web_upload_{:016x}", rand::random::<u64>()));
+                let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+                if let Err(e) = file.write_all(content) {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(e.into());
+                }
+                UploadData::TempFile(path)
+            }
+            else {
+                UploadData::Memory(content.to_vec())
+            };
+
+            let content_type = headers.get("content-type").cloned().unwrap_or_else(|| "application/octet-stream".to_string());
+            Ok(Self { name, file_name, content_type, headers, size: content.len(), data })
+        }
+
+        //表单中的字段名
+        pub fn get_name(&self) -> &str {
+            self.name.as_str()
+        }
+
+        //客户端给出的文件名, 保存前需要自己检查
+        pub fn get_file_name(&self) -> &str {
+            self.file_name.as_str()
+        }
+
+        pub fn get_content_type(&self) -> &str {
+            self.content_type.as_str()
+        }
+
+        pub fn get_headers(&self) -> &std::collections::HashMap<String, String> {
+            &self.headers
+        }
+
+        pub fn get_size(&self) -> usize {
+            self.size
+        }
+
+        //写到临时文件时返回它的路径
+        pub fn get_temp_path(&self) -> Option<&std::path::Path> {
+            match &self.data {
+                UploadData::TempFile(path) => Some(path.as_path()),
+                UploadData::Memory(_) => None,
+            }
+        }
+
+        pub fn read_bytes(&self) -> Result<Vec<u8>, BacktraceError> {
+            match &self.data {
+                UploadData::Memory(data) => Ok(data.clone()),
+                UploadData::TempFile(path) => Ok(std::fs::read(path)?),
+            }
+        }
+
+        //保存到 path, 临时文件优先直接改名
+        pub fn persist<P: AsRef<std::path::Path>>(mut self, path: P) -> Result<(), BacktraceError> {
+            match &self.data {
+                UploadData::Memory(data) => std::fs::write(path, data)?,
+                UploadData::TempFile(temp) => {
+                    if std::fs::rename(temp, path.as_ref()).is_ok() {
+                        self.data = UploadData::Memory(Vec::new());
+                    }
+                    else {
+                        std::fs::copy(temp, path.as_ref())?;
+                    }
+                },
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for UploadedFile {
+        fn drop(&mut self) {
+            if let UploadData::TempFile(path) = &self.data {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    //multipart/form-data 请求体, 普通字段按 parse_form_data 的规则合并成 Json, 例如 user[name] 与 tags[]
+    #[derive(Debug)]
+    pub struct Multipart {
+        fields: Json,
+        files: Vec<UploadedFile>,
+    }
+
+    impl Multipart {
+        pub fn parse(request: &HttpRequest, limits: &MultipartLimits) -> Result<Self, HttpError> {
+            let delimiter = [b"--".as_slice(), Self::boundary(request)?.as_bytes()].concat();
+            let close = [b"\r\n".as_slice(), &delimiter].concat();
+            let body = request.get_body().as_slice();
+
+            //第一个分隔行之前的内容忽略
+            let mut pos = if body.starts_with(&delimiter) {
+                delimiter.len()
+            }
+            else {
+                find_bytes(body, &close).ok_or_else(|| bad_request("multipart boundary not found"))? + close.len()
+            };
+
+            let mut fields = Vec::new();
+            let mut files = Vec::new();
+            let mut total_size = 0usize;
+            let mut parts = 0usize;
+            loop {
+                //分隔行后面是 -- 表示结束, 否则换行后是下一个部分
+                let rest = &body[pos..];
+                if rest.starts_with(b"--") { break; }
+                let line_end = find_bytes(rest, b"\r\n").ok_or_else(|| bad_request("multipart body not terminated"))?;
+                if !rest[..line_end].iter().all(|&item| item == b' ' || item == b'\t') {
+                    return Err(bad_request("invalid multipart boundary line"));
+                }
+                pos += line_end + 2;
+
+                parts += 1;
+                if parts > limits.max_parts {
+                    return Err(HttpError::new(HttpResponseStatusCode::ContentTooLarge, "too many multipart parts"));
+                }
+
+                let (headers, content_start) = if body[pos..].starts_with(b"\r\n") {
+                    (Default::default(), pos + 2)
+                }
+                else {
+                    let header_end = find_bytes(&body[pos..], b"\r\n\r\n").ok_or_else(|| bad_request("multipart header not terminated"))?;
+                    if header_end > MAX_PART_HEADER {
+                        return Err(HttpError::new(HttpResponseStatusCode::RequestHeaderFieldsTooLarge, "multipart header too large"));
+                    }
+                    (Self::parse_headers(&body[pos..pos + header_end])?, pos + header_end + 4)
+                };
+
+                let content_len = find_bytes(&body[content_start..], &close).ok_or_else(|| bad_request("multipart body not terminated"))?;
+                let content = &body[content_start..content_start + content_len];
+                pos = content_start + content_len + close.len();
+
+                total_size += content.len();
+                if content.len() > limits.max_part_size || total_size > limits.max_total_size {
+                    return Err(HttpError::new(HttpResponseStatusCode::ContentTooLarge, "multipart body too large"));
+                }
+
+                let disposition = headers.get("content-disposition").ok_or_else(|| bad_request("missing content-disposition"))?;
+                let (name, file_name) = Self::parse_disposition(disposition).ok_or_else(|| bad_request(format!("invalid content-disposition: {disposition}")))?;
+                match file_name {
+                    Some(file_name) => files.push(UploadedFile::new(name, file_name, headers, content, limits)?),
+                    None => fields.push((name, String::from_utf8(content.to_vec()).map_err(bad_request)?)),
+                }
+            }
+
+            let fields = Self::merge_fields(fields).map_err(|e| bad_request(e.err_desc))?;
+            Ok(Self { fields, files })
+        }
+
+        pub fn get_fields(&self) -> &Json {
+            &self.fields
+        }
+
+        pub fn get_files(&self) -> &Vec<UploadedFile> {
+            &self.files
+        }
+
+        pub fn get_file(&self, name: &str) -> Option<&UploadedFile> {
+            self.files.iter().find(|file| file.name == name)
+        }
+
+        pub fn into_parts(self) -> (Json, Vec<UploadedFile>) {
+            (self.fields, self.files)
+        }
+
+        //multipart/form-data; boundary="----abc"
+        fn boundary(request: &HttpRequest) -> Result<String, HttpError> {
+            let content_type = request.get_headers().get("content-type").map(|val| val.as_str()).unwrap_or_default();
+            let boundary = content_type.split(';').skip(1)
+                .filter_map(|param| param.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+                .map(|(_, val)| val.trim().trim_matches('"'))
+                .ok_or_else(|| bad_request("missing multipart boundary"))?;
+
+            if boundary.is_empty() || boundary.len() > 70 { return Err(bad_request("invalid multipart boundary")); }
+            Ok(boundary.to_string())
+        }
+
+        fn parse_headers(block: &[u8]) -> Result<std::collections::HashMap<String, String>, HttpError> {
+            let block = std::str::from_utf8(block).map_err(bad_request)?;
+            let mut headers = std::collections::HashMap::new();
+            for line in block.split("\r\n") {
+                let (key, val) = line.split_once(':').ok_or_else(|| bad_request("invalid multipart header"))?;
+                headers.insert(key.trim().to_lowercase(), val.trim().to_string());
+            }
+            Ok(headers)
+        }
+
+        //form-data; name="field"; filename="a.txt", filename* 优先
+        fn parse_disposition(value: &str) -> Option<(String, Option<String>)> {
+            let mut params = Vec::new();
+            let mut cur = String::new();
+            let mut in_quote = false;
+            let mut chars = value.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => in_quote = !in_quote,
+                    '\\' if in_quote => cur.extend(chars.next()),
+                    ';' if !in_quote => params.push(std::mem::take(&mut cur)),
+                    c => cur.push(c),
+                }
+            }
+            params.push(cur);
+
+            let mut params = params.into_iter();
+            if !params.next()?.trim().eq_ignore_ascii_case("form-data") { return None; }
+
+            let (mut name, mut file_name, mut ext_file_name) = (None, None, None);
+            for param in params {
+                let Some((key, val)) = param.split_once('=') else { continue; };
+                match key.trim().to_lowercase().as_str() {
+                    "name" => name = Some(val.trim().to_string()),
+                    "filename" => file_name = Some(val.trim().to_string()),
+                    //RFC 5987: UTF-8''%E4%B8%AD.txt
+                    "filename*" => ext_file_name = val.trim().split_once("''").and_then(|(_, encoded)| urldecode(encoded).ok()),
+                    _ => {},
+                }
+            }
+
+            Some((name?, ext_file_name.or(file_name)))
+        }
+
+        //值先用序号代替, 解析出嵌套结构后再换回原值, 这样值不用经过编码与解码
+        fn merge_fields(fields: Vec<(String, String)>) -> Result<Json, BacktraceError> {
+            let form = fields.iter().enumerate()
+                .map(|(index, (name, _))| format!("{}={index}", Self::escape_name(name)))
+                .collect::<Vec<String>>()
+                .join("&");
+
+            let mut json = Json::parse_form_data(form)?;
+            let mut values = fields.into_iter().map(|(_, value)| Some(value)).collect::<Vec<Option<String>>>();
+            Self::fill_values(json.get_mut(), &mut values);
+            Ok(json)
+        }
+
+        fn escape_name(name: &str) -> String {
+            name.replace('%', "%25").replace('&', "%26").replace('=', "%3D").replace('[', "%5B").replace(']', "%5D")
+        }
+
+        fn fill_values(json: &mut JsonType, values: &mut Vec<Option<String>>) {
+            match json {
+                JsonType::String(text) => {
+                    if let Some(value) = text.parse::<usize>().ok().and_then(|index| values.get_mut(index)).and_then(Option::take) {
+                        *text = value;
+                    }
+                },
+                JsonType::Vec(items) => items.iter_mut().for_each(|item| Self::fill_values(item.get_mut(), values)),
+                JsonType::Object(items) => items.values_mut().for_each(|item| Self::fill_values(item.get_mut(), values)),
+                _ => {},
+            }
+        }
+    }
+
+    impl FromRequest for Multipart {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            if !has_content_type(&ctx.request, "multipart/form-data") {
+                return Err(HttpError::new(HttpResponseStatusCode::UnsupportedMediaType, "expected content-type multipart/form-data"));
+            }
+
+            Multipart::parse(&ctx.request, &ctx.multipart_limits)
+        }
+    }
+
+    fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    //Header<T> 按 T::NAME 取请求头, 没有时返回 400, 可选的请求头用 Option<Header<T>>
+    pub trait TypedHeader: Sized {
+        const NAME: &'static str;
+
+        fn decode(value: &str) -> Result<Self, HttpError>;
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Header<T>(pub T);
+
+    impl<T: TypedHeader> FromRequest for Header<T> {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            let val = ctx.request.get_headers().get(T::NAME).ok_or_else(|| bad_request(format!("missing header {}", T::NAME)))?;
+            Ok(Header(T::decode(val)?))
+        }
+    }
+
+    macro_rules! string_header {
+        ($($name:ident => $key:expr),*) => {$(
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $name(pub String);
+
+            impl TypedHeader for $name {
+                const NAME: &'static str = $key;
+
+                fn decode(value: &str) -> Result<Self, HttpError> {
+                    Ok($name(value.to_string()))
+                }
+            }
+        )*};
+    }
+
+    string_header!(Accept => "accept", Authorization => "authorization", ContentType => "content-type", Host => "host", UserAgent => "user-agent");
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ContentLength(pub u64);
+
+    impl TypedHeader for ContentLength {
+        const NAME: &'static str = "content-length";
+
+        fn decode(value: &str) -> Result<Self, HttpError> {
+            Ok(ContentLength(value.trim().parse().map_err(|_| bad_request(format!("invalid content-length {:?}", value)))?))
+        }
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct Cookies {
+        cookies: Vec<(String, String)>,
+    }
+
+    impl Cookies {
+        pub fn parse(header: &str) -> Self {
+            let cookies = header.split(';').filter_map(|pair| {
+                let (name, val) = pair.split_once('=')?;
+                let val = val.trim();
+                let val = val.strip_prefix('"').and_then(|val| val.strip_suffix('"')).unwrap_or(val);
+                Some((name.trim().to_string(), val.to_string()))
+            }).filter(|(name, _)| !name.is_empty()).collect();
+
+            Self { cookies }
+        }
+
+        pub fn get(&self, name: &str) -> Option<&str> {
+            self.cookies.iter().find(|(key, _)| key == name).map(|(_, val)| val.as_str())
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+            self.cookies.iter().map(|(key, val)| (key.as_str(), val.as_str()))
+        }
+
+        pub fn len(&self) -> usize {
+            self.cookies.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.cookies.is_empty()
+        }
+    }
+
+    impl FromRequest for Cookies {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            Ok(ctx.request.get_headers().get("cookie").map(|header| Cookies::parse(header)).unwrap_or_default())
+        }
+    }
+
+    impl FromRequest for RequestBody {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            ctx.take_body().ok_or_else(|| HttpError::new(HttpResponseStatusCode::InternalServerError, "request body is not streamed"))
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct PeerAddr(pub std::net::SocketAddr);
+
+    impl FromRequest for PeerAddr {
+        fn from_request(ctx: &RequestContext) -> Result<Self, HttpError> {
+            let addr = ctx.peer_addr.ok_or_else(|| HttpError::new(HttpResponseStatusCode::InternalServerError, "peer address is not available"))?;
+            Ok(PeerAddr(addr))
+        }
+    }
+
+    //参数都实现 FromRequest 的同步或异步函数, 第一个类型参数只用来区分两种实现
+    pub trait Handler<Args>: Send + Sync + 'static {
+        fn call(&self, ctx: RequestContext) -> futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>>;
+    }
+
+    pub struct SyncHandler;
+    pub struct AsyncHandler;
+
+    macro_rules! handler_impl {
+        ($($arg:ident),*) => {
+            #[allow(non_snake_case, unused_variables, clippy::redundant_closure_call)]
+            impl<F, R, $($arg,)*> Handler<(SyncHandler, $($arg,)*)> for F
+            where
+                F: Fn($($arg),*) -> R + Send + Sync + 'static,
+                R: IntoHandlerResult,
+                $($arg: FromRequest,)*
+            {
+                fn call(&self, ctx: RequestContext) -> futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>> {
+                    let result = (|| {
+                        $(let $arg = $arg::from_request(&ctx)?;)*
+                        self($($arg),*).into_handler_result()
+                    })();
+
+                    futures::future::ready(result).boxed()
+                }
+            }
+
+            #[allow(non_snake_case, unused_variables, clippy::redundant_closure_call)]
+            impl<F, Fut, $($arg,)*> Handler<(AsyncHandler, $($arg,)*)> for F
+            where
+                F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+                Fut: std::future::Future + Send + 'static,
+                Fut::Output: IntoHandlerResult,
+                $($arg: FromRequest,)*
+            {
+                fn call(&self, ctx: RequestContext) -> futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>> {
+                    let args = (|| -> Result<_, HttpError> {
+                        Ok(($($arg::from_request(&ctx)?,)*))
+                    })();
+
+                    match args {
+                        Ok(($($arg,)*)) => self($($arg),*).map(|result| result.into_handler_result()).boxed(),
+                        Err(e) => futures::future::ready(Err(e)).boxed(),
+                    }
+                }
+            }
+        };
+    }
+
+    handler_impl!();
+    handler_impl!(A);
+    handler_impl!(A, B);
+    handler_impl!(A, B, C);
+    handler_impl!(A, B, C, D);
+    handler_impl!(A, B, C, D, E);
+    handler_impl!(A, B, C, D, E, G);
+
+    //按类型保存的共享状态, 通过 State<T> 或 RequestContext::get_state 取出
+    type StateMap = std::collections::HashMap<std::any::TypeId, std::sync::Arc<dyn std::any::Any + Send + Sync>>;
+
+    type WebFunc = dyn Fn(RequestContext) -> futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>> + Send + Sync;
+    type ErrorFunc = dyn Fn(HttpError) -> HttpResponse + Send + Sync;
+    type NotFoundFunc = dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync;
+    type MethodNotAllowedFunc = dyn Fn(&HttpRequest, &[String]) -> HttpResponse + Send + Sync;
+
+    pub type HandlerFuture = futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>>;
+
+    //中间件可以修改请求, 直接返回响应, 或者在 next.run 之后修改响应
+    pub trait Middleware: Send + Sync + 'static {
+        fn handle(&self, ctx: RequestContext, next: Next) -> HandlerFuture;
+    }
+
+    impl<F, Fut> Middleware for F
+    where
+        F: Fn(RequestContext, Next) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<HttpResponse, HttpError>> + Send + 'static,
+    {
+        fn handle(&self, ctx: RequestContext, next: Next) -> HandlerFuture {
+            self(ctx, next).boxed()
+        }
+    }
+
+    //中间件链中剩下的部分, 最后是路由的 handler
+    pub struct Next {
+        chain: std::sync::Arc<Vec<std::sync::Arc<dyn Middleware>>>,
+        index: usize,
+        endpoint: std::sync::Arc<WebFunc>,
+    }
+
+    impl Next {
+        pub fn run(self, ctx: RequestContext) -> HandlerFuture {
+            match self.chain.get(self.index).cloned() {
+                Some(middleware) => middleware.handle(ctx, Next { index: self.index + 1, ..self }),
+                None => (self.endpoint)(ctx),
+            }
+        }
+    }
+
+    //客户端支持时用 gzip 压缩响应体
+    #[derive(Debug, Clone, Copy)]
+    pub struct Compression {
+        level: u32,
+    }
+
+    impl Compression {
+        pub fn new() -> Self {
+            Self {
+                level: flate2::Compression::best().level(),
+            }
+        }
+
+        pub fn level(mut self, level: u32) -> Self {
+            self.level = level.min(9);
+            self
+        }
+
+        //没有写 gzip 时按 * 的 q 值, q=0 表示拒绝
+        fn accept_gzip(accept_encoding: &str) -> bool {
+            let (mut gzip, mut any) = (None, None);
+            for item in accept_encoding.split(',') {
+                let mut params = item.split(';');
+                let coding = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+                let quality = params.filter_map(|param| param.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+                    .map(|(_, val)| val.trim().parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+
+                match coding.as_str() {
+                    "gzip" | "x-gzip" => gzip = Some(quality),
+                    "*" => any = Some(quality),
+                    _ => {},
+                }
+            }
+
+            gzip.or(any).is_some_and(|quality| quality > 0.0)
+        }
+    }
+
+    impl Default for Compression {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Middleware for Compression {
+        fn handle(&self, ctx: RequestContext, next: Next) -> HandlerFuture {
+            let accept_gzip = ctx.request.get_headers().get("accept-encoding").is_some_and(|val| Self::accept_gzip(val));
+            let level = self.level;
+
+            async move {
+                let mut response = next.run(ctx).await?;
+
+                if accept_gzip && !response.is_streaming() && !response.get_body().is_empty() && !response.get_headers().contains_key("content-encoding") {
+                    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                    encoder.write_all(response.get_body())?;
+                    response.set_body(encoder.finish()?);
+                    response.insert_header("content-encoding", "gzip");
+                    response.insert_header("vary", "accept-encoding");
+                }
+
+                Ok(response)
+            }.boxed()
+        }
+    }
+
+    //每个请求打印一行: 地址 方法 路径 状态码 耗时
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct Logger;
+
+    impl Middleware for Logger {
+        fn handle(&self, ctx: RequestContext, next: Next) -> HandlerFuture {
+            let peer_addr = ctx.get_peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|| "-".to_string());
+            let method = ctx.request.get_method().clone();
+            let uri = ctx.request.get_uri().clone();
+            let begin = std::time::Instant::now();
+
+            async move {
+                let result = next.run(ctx).await;
+                let status_code = match &result {
+                    Ok(response) => response.get_status_code().as_u16(),
+                    Err(e) => e.get_status_code().as_u16(),
+                };
+
+                println!("{} {} {} {} {:?}", peer_addr, method, uri, status_code, begin.elapsed());
+                result
+            }.boxed()
+        }
+    }
+
+    #[derive(Clone)]
+    struct Route {
+        //按顺序对应 :param 与 *wildcard 的名字
+        names: Vec<String>,
+        func: std::sync::Arc<WebFunc>,
+        middlewares: Vec<std::sync::Arc<dyn Middleware>>,
+        stream_body: bool,
+        //mount 进来的路由使用子 Router 的设置, None 时用外层的
+        error_handler: Option<std::sync::Arc<ErrorFunc>>,
+        multipart_limits: Option<MultipartLimits>,
+        //子 Router 的状态, 同类型时覆盖外层的
+        states: Option<std::sync::Arc<StateMap>>,
+    }
+
+    //mount 时记录子 Router 的 404/405 处理, 按最长的 prefix 匹配没有路由的请求
+    struct MountHandlers {
+        prefix: Vec<String>,
+        not_found_handler: Option<std::sync::Arc<NotFoundFunc>>,
+        method_not_allowed_handler: Option<std::sync::Arc<MethodNotAllowedFunc>>,
+    }
+
+    impl MountHandlers {
+        fn matches(&self, segments: &[&str]) -> bool {
+            segments.len() >= self.prefix.len() && self.prefix.iter().zip(segments).all(|(prefix, segment)| prefix.starts_with(':') || prefix == segment)
+        }
+    }
+
+    type RouterLink = std::collections::HashMap<String, Route>;
+
+    #[derive(Default)]
+    struct RouteNode {
+        children: std::collections::HashMap<String, RouteNode>,
+        param: Option<Box<RouteNode>>,
+        wildcard: RouterLink,
+        link: RouterLink,
+    }
+
+    impl RouteNode {
+        fn insert(&mut self, segments: &[&str], method: String, route: Route) {
+            let Some((segment, rest)) = segments.split_first() else {
+                self.link.insert(method, route);
+                return;
+            };
+
+            if segment.starts_with('*') {
+                if !rest.is_empty() { panic!("wildcard must be the last segment"); }
+                self.wildcard.insert(method, route);
+            }
+            else if segment.starts_with(':') {
+                self.param.get_or_insert_with(Default::default).insert(rest, method, route);
+            }
+            else {
+                self.children.entry(segment.to_string()).or_default().insert(rest, method, route);
+            }
+        }
+
+        //拆出所有路由, 参数段记为 ":", 通配段记为 "*"
+        fn into_routes(self, pattern: &mut Vec<String>, routes: &mut Vec<(Vec<String>, String, Route)>) {
+            for (method, route) in self.link {
+                routes.push((pattern.clone(), method, route));
+            }
+
+            for (method, route) in self.wildcard {
+                let mut wildcard = pattern.clone();
+                wildcard.push("*".to_string());
+                routes.push((wildcard, method, route));
+            }
+
+            for (segment, child) in self.children {
+                pattern.push(segment);
+                child.into_routes(pattern, routes);
+                pattern.pop();
+            }
+
+            if let Some(child) = self.param {
+                pattern.push(":".to_string());
+                child.into_routes(pattern, routes);
+                pattern.pop();
+            }
+        }
+
+        fn get_mut(&mut self, segments: &[&str], method: &str) -> Option<&mut Route> {
+            let Some((segment, rest)) = segments.split_first() else {
+                return self.link.get_mut(method);
+            };
+
+            if segment.starts_with('*') {
+                self.wildcard.get_mut(method)
+            }
+            else if segment.starts_with(':') {
+                self.param.as_mut()?.get_mut(rest, method)
+            }
+            else {
+                self.children.get_mut(*segment)?.get_mut(rest, method)
+            }
+        }
+
+        //收集能匹配这个路径的所有方法, 用于 405 与 OPTIONS
+        fn methods(&self, segments: &[&str], methods: &mut std::collections::BTreeSet<String>) {
+            methods.extend(self.wildcard.keys().cloned());
+
+            let Some((segment, rest)) = segments.split_first() else {
+                methods.extend(self.link.keys().cloned());
+                return;
+            };
+
+            if let Some(child) = self.children.get(*segment) {
+                child.methods(rest, methods);
+            }
+
+            if let Some(child) = self.param.as_ref().filter(|_| !segment.is_empty()) {
+                child.methods(rest, methods);
+            }
+        }
+
+        //静态段优先, 其次 :param, 最后 *wildcard
+        fn find<'a>(&'a self, segments: &[&str], method: &str, values: &mut Vec<String>) -> Option<&'a Route> {
+            let Some((segment, rest)) = segments.split_first() else {
+                return self.link.get(method).or_else(|| self.wildcard.get(method).inspect(|_| values.push(String::new())));
+            };
+
+            if let Some(child) = self.children.get(*segment) {
+                if let Some(route) = child.find(rest, method, values) { return Some(route); }
+            }
+
+            if let Some(child) = self.param.as_ref().filter(|_| !segment.is_empty()) {
+                values.push(segment.to_string());
+                if let Some(route) = child.find(rest, method, values) { return Some(route); }
+                values.pop();
+            }
+
+            let route = self.wildcard.get(method)?;
+            values.push(segments.join("/"));
+            Some(route)
+        }
+    }
+
+    //#[route] 生成的路由信息, 交给 Router::register_routes 注册
+    pub struct RouteDef {
+        methods: &'static [&'static str],
+        path: &'static str,
+        register: fn(&mut Router, &'static str, &'static str),
+    }
+
+    impl RouteDef {
+        pub fn new(methods: &'static [&'static str], path: &'static str, register: fn(&mut Router, &'static str, &'static str)) -> Self {
+            Self {
+                methods,
+                path,
+                register,
+            }
+        }
+
+        pub fn get_methods(&self) -> &'static [&'static str] {
+            self.methods
+        }
+
+        pub fn get_path(&self) -> &'static str {
+            self.path
+        }
+    }
+
+    //routes![index, api::get_user] 收集 #[route] 函数
+    #[macro_export]
+    macro_rules! routes {
+        ($($($handler:ident)::+),* $(,)?) => {
+            vec![$($crate::web::RouteDef::from($($handler)::+ {})),*]
+        };
+    }
+
+    //按扩展名 (不区分大小写) 确定 content-type, 未知类型按二进制处理
+    fn mime_type(path: &std::path::Path) -> &'static str {
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase();
+        match ext.as_str() {
+            "html" | "htm" => "text/html; charset=utf-8",
+            "css" => "text/css; charset=utf-8",
+            "js" | "mjs" => "text/javascript; charset=utf-8",
+            "json" | "map" => "application/json",
+            "jsonld" => "application/ld+json",
+            "webmanifest" => "application/manifest+json",
+            "xml" => "application/xml",
+            "txt" | "log" => "text/plain; charset=utf-8",
+            "csv" => "text/csv; charset=utf-8",
+            "md" | "markdown" => "text/markdown; charset=utf-8",
+            "ics" => "text/calendar; charset=utf-8",
+            "vtt" => "text/vtt; charset=utf-8",
+            "png" => "image/png",
+            "jpg" | "jpeg" | "jpe" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "avif" => "image/avif",
+            "svg" | "svgz" => "image/svg+xml",
+            "ico" => "image/x-icon",
+            "bmp" => "image/bmp",
+            "tif" | "tiff" => "image/tiff",
+            "apng" => "image/apng",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            "ttf" => "font/ttf",
+            "otf" => "font/otf",
+            "eot" => "application/vnd.ms-fontobject",
+            "mp3" => "audio/mpeg",
+            "wav" => "audio/wav",
+            "ogg" | "oga" => "audio/ogg",
+            "opus" => "audio/opus",
+            "flac" => "audio/flac",
+            "aac" => "audio/aac",
+            "m4a" => "audio/mp4",
+            "weba" => "audio/webm",
+            "mid" | "midi" => "audio/midi",
+            "mp4" | "m4v" => "video/mp4",
+            "webm" => "video/webm",
+            "ogv" => "video/ogg",
+            "mov" => "video/quicktime",
+            "avi" => "video/x-msvideo",
+            "mpeg" | "mpg" => "video/mpeg",
+            "ts" => "video/mp2t",
+            "m3u8" => "application/vnd.apple.mpegurl",
+            "pdf" => "application/pdf",
+            "wasm" => "application/wasm",
+            "zip" => "application/zip",
+            "gz" | "tgz" => "application/gzip",
+            "tar" => "application/x-tar",
+            "bz2" => "application/x-bzip2",
+            "7z" => "application/x-7z-compressed",
+            "rar" => "application/vnd.rar",
+            "rtf" => "application/rtf",
+            "doc" => "application/msword",
+            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "xls" => "application/vnd.ms-excel",
+            "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "ppt" => "application/vnd.ms-powerpoint",
+            "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "odt" => "application/vnd.oasis.opendocument.text",
+            "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+            "epub" => "application/epub+zip",
+            "rss" => "application/rss+xml",
+            "atom" => "application/atom+xml",
+            "yaml" | "yml" => "application/yaml",
+            "toml" => "application/toml",
+            "sh" => "application/x-sh",
+            "jar" => "application/java-archive",
+            _ => "application/octet-stream",
+        }
+    }
+
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    //IMF-fixdate, 例如 Sun, 06 Nov 1994 08:49:37 GMT
+    fn http_date(time: std::time::SystemTime) -> String {
+        let secs = time.duration_since(std::time::UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        let days = secs / 86400;
+        let weekday = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"][(days % 7) as usize];
+
+        //公历日期换算, 见 Howard Hinnant 的 civil_from_days
+        let z = days + 719468;
+        let (era, doe) = (z / 146097, z % 146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+
+        format!("{weekday}, {day:02} {} {year:04} {:02}:{:02}:{:02} GMT", MONTHS[month as usize - 1], secs % 86400 / 3600, secs % 3600 / 60, secs % 60)
+    }
+
+    //只接受 IMF-fixdate, 返回 1970 年以来的秒数, 格式不对时返回 None
+    fn parse_http_date(text: &str) -> Option<u64> {
+        let parts = text.split_whitespace().collect::<Vec<&str>>();
+        let [_, day, month, year, time, "GMT"] = parts.as_slice() else { return None; };
+
+        let day = day.parse::<u64>().ok().filter(|day| (1..=31).contains(day))?;
+        let month = MONTHS.iter().position(|item| item == month)? as u64 + 1;
+        let year = year.parse::<u64>().ok().filter(|year| *year >= 1970)?;
+        let time = time.split(':').map(|item| item.parse::<u64>().ok()).collect::<Option<Vec<u64>>>()?;
+        let [hour, minute, second] = time.as_slice() else { return None; };
+
+        //days_from_civil
+        let year = if month <= 2 { year - 1 } else { year };
+        let (era, yoe) = (year / 400, year % 400);
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = (era * 146097 + doe).checked_sub(719468)?;
+
+        Some(days * 86400 + hour * 3600 + minute * 60 + second)
+    }
+
+    //* 匹配任意字符 (包括 /), 例如 /assets/* 或 *.html
+    fn wildcard_match(pattern: &str, text: &str) -> bool {
+        let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+        let (mut p, mut t) = (0, 0);
+        let mut star = None;
+        while t < text.len() {
+            if p < pattern.len() && pattern[p] == b'*' {
+                star = Some((p, t));
+                p += 1;
+            }
+            else if p < pattern.len() && pattern[p] == text[t] {
+                p += 1;
+                t += 1;
+            }
+            else if let Some((star_p, star_t)) = star {
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            }
+            else {
+                return false;
+            }
+        }
+        pattern[p..].iter().all(|&item| item == b'*')
+    }
+
+    //路径中的 %XX 解码, 不把 + 当成空格
+    fn percent_decode(content: &str) -> Option<String> {
+        let bytes = content.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' {
+                let code = bytes.get(i + 1..i + 3)?;
+                //from_str_radix 允许 "+f" 这样的写法, 先检查是不是两个十六进制数字
+                if !code.iter().all(|item| item.is_ascii_hexdigit()) { return None; }
+                result.push(u8::from_str_radix(std::str::from_utf8(code).ok()?, 16).ok()?);
+                i += 3;
+            }
+            else {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(result).ok()
+    }
+
+    fn percent_encode(content: &str) -> String {
+        content.bytes().map(|item| match item {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (item as char).to_string(),
+            _ => format!("%{item:02X}"),
+        }).collect()
+    }
+
+    fn html_escape(content: &str) -> String {
+        content.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+    }
+
+    //路径解析的结果, 文件在确定要发送时才打开
+    enum StaticTarget {
+        File(std::path::PathBuf, String),
+        Response(HttpResponse),
+    }
+
+    //静态文件目录, 请求路径解码并规范化后只能访问 root 之内的文件 (包括符号链接指向的位置)
+    #[derive(Debug, Clone)]
+    pub struct StaticFiles {
+        root: std::path::PathBuf,
+        index_files: Vec<String>,
+        directory_listing: bool,
+        dotfiles: bool,
+        //(路径模式, Cache-Control), 按添加顺序使用第一个匹配的
+        cache_policies: Vec<(String, String)>,
+    }
+
+    impl Default for StaticFiles {
+        fn default() -> Self {
+            Self::new("wwwroot")
+        }
+    }
+
+    impl StaticFiles {
+        pub fn new<P: Into<std::path::PathBuf>>(root: P) -> Self {
+            Self {
+                root: root.into(),
+                index_files: vec!["index.html".to_string()],
+                directory_listing: false,
+                dotfiles: false,
+                cache_policies: Vec::new(),
+            }
+        }
+
+        //pattern 与规范化后的文件路径匹配, 例如 ("*.html", "no-cache")
+        pub fn cache_control<P: Into<String>, V: Into<String>>(mut self, pattern: P, value: V) -> Self {
+            self.cache_policies.push((pattern.into(), value.into()));
+            self
+        }
+
+        //文件名带内容指纹的资源 (例如 /assets/app.3f9a1c.js) 内容不会变, 可以一直缓存
+        pub fn immutable<P: Into<String>>(self, pattern: P) -> Self {
+            self.cache_control(pattern, "public, max-age=31536000, immutable")
+        }
+
+        //请求目录时按顺序查找的文件
+        pub fn index_files<I: IntoIterator<Item = T>, T: Into<String>>(mut self, index_files: I) -> Self {
+            self.index_files = index_files.into_iter().map(|name| name.into()).collect();
+            self
+        }
+
+        //目录下没有 index 文件时列出目录内容
+        pub fn directory_listing(mut self, enable: bool) -> Self {
+            self.directory_listing = enable;
+            self
+        }
+
+        //默认不提供 . 开头的文件与目录 (.env, .git 等), 当作不存在
+        pub fn dotfiles(mut self, enable: bool) -> Self {
+            self.dotfiles = enable;
+            self
+        }
+
+        pub fn get_root(&self) -> &std::path::Path {
+            self.root.as_path()
+        }
+
+        fn not_found() -> HttpResponse {
+            let mut response = HttpResponse::new(HttpResponseStatusCode::NotFound);
+            response.set_body("404 not found".into());
+            response
+        }
+
+        //uri 是请求中未解码的路径, 不含查询字符串
+        pub fn serve(&self, uri: &str) -> Result<HttpResponse, BacktraceError> {
+            match self.resolve(uri)? {
+                StaticTarget::File(target, file_path) => self.open(&target, &file_path),
+                StaticTarget::Response(response) => Ok(response),
+            }
+        }
+
+        fn open(&self, target: &std::path::Path, file_path: &str) -> Result<HttpResponse, BacktraceError> {
+            match std::fs::File::open(target) {
+                Ok(file) => Ok(self.with_cache_control(HttpResponse::file_response(file, target)?, file_path)),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Ok(HttpResponse::new(HttpResponseStatusCode::Forbidden)),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        fn resolve(&self, uri: &str) -> Result<StaticTarget, BacktraceError> {
+            let Some(path) = percent_decode(uri) else {
+                let mut response = HttpResponse::new(HttpResponseStatusCode::BadRequest);
+                response.set_body("invalid path encoding".into());
+                return Ok(StaticTarget::Response(response));
+            };
+
+            //.. 最多退回到 root, 其余不是普通文件名的段都当作不存在
+            let mut segments: Vec<&str> = Vec::new();
+            for segment in path.split('/') {
+                match segment {
+                    "" | "." => {},
+                    ".." => { segments.pop(); },
+                    segment if segment.contains(['\\', '\0']) => return Ok(StaticTarget::Response(Self::not_found())),
+                    segment if segment.starts_with('.') && !self.dotfiles => return Ok(StaticTarget::Response(Self::not_found())),
+                    segment => {
+                        let mut components = std::path::Path::new(segment).components();
+                        if !matches!((components.next(), components.next()), (Some(std::path::Component::Normal(_)), None)) {
+                            return Ok(StaticTarget::Response(Self::not_found()));
+                        }
+                        segments.push(segment);
+                    },
+                }
+            }
+
+            let (root, target) = match (self.root.canonicalize(), segments.iter().collect::<std::path::PathBuf>()) {
+                (Ok(root), relative) => {
+                    match root.join(relative).canonicalize() {
+                        Ok(target) => (root, target),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(StaticTarget::Response(Self::not_found())),
+                        Err(e) => return Err(e.into()),
+                    }
+                },
+                (Err(e), _) if e.kind() == std::io::ErrorKind::NotFound => return Ok(StaticTarget::Response(Self::not_found())),
+                (Err(e), _) => return Err(e.into()),
+            };
+            if !target.starts_with(&root) { return Ok(StaticTarget::Response(Self::not_found())); }
+
+            let file_path = format!("/{}", segments.join("/"));
+            if !target.is_dir() { return Ok(StaticTarget::File(target, file_path)); }
+
+            //目录要以 / 结尾, 页面里的相对路径才能正确解析
+            //地址用规范化后的路径重新编码, 原始 uri 可能是 //evil.com/..%2fdir 这样指向别的主机的写法
+            if !uri.ends_with('/') {
+                let mut response = HttpResponse::new(HttpResponseStatusCode::MovedPermanently);
+                let location = segments.iter().map(|segment| format!("/{}", percent_encode(segment))).collect::<String>();
+                response.insert_header("location", format!("{location}/"));
+                return Ok(StaticTarget::Response(response));
+            }
+
+            for index_name in self.index_files.iter() {
+                let index = target.join(index_name);
+                if index.is_file() {
+                    return Ok(StaticTarget::File(index, format!("{}/{index_name}", file_path.trim_end_matches('/'))));
+                }
+            }
+
+            if !self.directory_listing { return Ok(StaticTarget::Response(Self::not_found())); }
+            self.listing(&target, &path, target != root).map(StaticTarget::Response)
+        }
+
+        fn with_cache_control(&self, mut response: HttpResponse, file_path: &str) -> HttpResponse {
+            if let Some((_, value)) = self.cache_policies.iter().find(|(pattern, _)| wildcard_match(pattern, file_path)) {
+                response.insert_header("cache-control", value.clone());
+            }
+            response
+        }
+
+        //与 serve 相同, 另外处理 If-None-Match 与 If-Modified-Since, 文件没有变化时返回 304
+        pub fn serve_request(&self, request: &HttpRequest) -> Result<HttpResponse, BacktraceError> {
+            let (target, file_path) = match self.resolve(request.get_uri())? {
+                StaticTarget::File(target, file_path) => (target, file_path),
+                StaticTarget::Response(response) => return Ok(response),
+            };
+
+            //只用 metadata 比较, 文件没有变化时不打开文件
+            let mut not_modified = HttpResponse::new(HttpResponseStatusCode::NotModified);
+            if let Ok(metadata) = std::fs::metadata(&target) {
+                not_modified.insert_validators(&metadata);
+            }
+            if Self::is_not_modified(request, &not_modified) {
+                return Ok(self.with_cache_control(not_modified, &file_path));
+            }
+            self.open(&target, &file_path)
+        }
+
+        //有 If-None-Match 时忽略 If-Modified-Since
+        fn is_not_modified(request: &HttpRequest, response: &HttpResponse) -> bool {
+            if request.get_method() != "GET" && request.get_method() != "HEAD" { return false; }
+
+            let headers = response.get_headers();
+            if let Some(if_none_match) = request.get_headers().get("if-none-match") {
+                let Some(etag) = headers.get("etag") else { return false; };
+                let etag = etag.trim_start_matches("W/");
+                return if_none_match.split(',').map(|item| item.trim()).any(|item| item == "*" || item.trim_start_matches("W/") == etag);
+            }
+
+            let since = request.get_headers().get("if-modified-since").and_then(|val| parse_http_date(val));
+            let modified = headers.get("last-modified").and_then(|val| parse_http_date(val));
+            matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+        }
+
+        fn listing(&self, dir: &std::path::Path, path: &str, has_parent: bool) -> Result<HttpResponse, BacktraceError> {
+            let mut entries = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') && !self.dotfiles { continue; }
+                entries.push((!entry.path().is_dir(), name));
+            }
+            //目录在前, 再按名字排序
+            entries.sort();
+
+            let title = html_escape(path);
+            let mut html = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n");
+            if has_parent { html += "<li><a href=\"../\">../</a></li>\n"; }
+            for (is_file, name) in entries {
+                let suffix = if is_file { "" } else { "/" };
+                html += &format!("<li><a href=\"{}{suffix}\">{}{suffix}</a></li>\n", percent_encode(&name), html_escape(&name));
+            }
+            html += "</ul>\n</body>\n</html>\n";
+
+            let mut response = HttpResponse::new(HttpResponseStatusCode::OK);
+            response.insert_header("content-type", "text/html; charset=utf-8");
+            response.set_body(html.into_bytes());
+            Ok(response)
+        }
+    }
+
+    pub struct Router {
+        root: RouteNode,
+        error_handler: Option<std::sync::Arc<ErrorFunc>>,
+        not_found_handler: Option<std::sync::Arc<NotFoundFunc>>,
+        method_not_allowed_handler: Option<std::sync::Arc<MethodNotAllowedFunc>>,
+        mounts: Vec<MountHandlers>,
+        middlewares: Vec<std::sync::Arc<dyn Middleware>>,
+        states: std::sync::Arc<StateMap>,
+        multipart_limits: Option<MultipartLimits>,
+        static_files: Option<std::sync::Arc<StaticFiles>>,
+    }
+
+    impl Default for Router {
+        fn default() -> Self {
+            Self {
+                root: Default::default(),
+                error_handler: None,
+                not_found_handler: None,
+                method_not_allowed_handler: None,
+                mounts: Vec::new(),
+                middlewares: Default::default(),
+                states: Default::default(),
+                multipart_limits: None,
+                static_files: Some(Default::default()),
+            }
+        }
+    }
+
+    //注册路由后返回, 用来给这一条路由单独添加中间件
+    pub struct RouteHandle<'a> {
+        router: &'a mut Router,
+        method: String,
+        patterns: Vec<Vec<String>>,
+    }
+
+    impl RouteHandle<'_> {
+        pub fn middleware<M: Middleware>(self, middleware: M) -> Self {
+            let middleware: std::sync::Arc<dyn Middleware> = std::sync::Arc::new(middleware);
+            for pattern in self.patterns.iter() {
+                let segments = pattern.iter().map(|segment| segment.as_str()).collect::<Vec<&str>>();
+                if let Some(route) = self.router.root.get_mut(&segments, &self.method) {
+                    route.middlewares.push(middleware.clone());
+                }
+            }
+            self
+        }
+
+        //请求体不再读完后解析成 Json, 而是通过 RequestBody 边收边读
+        pub fn stream_body(self) -> Self {
+            for pattern in self.patterns.iter() {
+                let segments = pattern.iter().map(|segment| segment.as_str()).collect::<Vec<&str>>();
+                if let Some(route) = self.router.root.get_mut(&segments, &self.method) {
+                    route.stream_body = true;
+                }
+            }
+            self
+        }
+    }
+
+    impl Router {
+        pub fn new() -> Self {
+            Self {
+                ..Default::default()
+            }
+        }
+
+        pub fn register_url<F: Fn(Json) -> HttpResponse + Send + Sync + 'static, T: Into<String>>(&mut self, method: T, url: T, func: F) -> RouteHandle<'_> {
+            self.add_route(method.into(), url.into(), move |ctx: RequestContext| {
+                futures::future::ready(Self::parse_context(ctx).map(|mut ctx| func(ctx.take_json()))).boxed()
+            })
+        }
+
+        pub fn register_url_with_params<F: Fn(Json, &PathParams) -> HttpResponse + Send + Sync + 'static, T: Into<String>>(&mut self, method: T, url: T, func: F) -> RouteHandle<'_> {
+            self.add_route(method.into(), url.into(), move |ctx: RequestContext| {
+                futures::future::ready(Self::parse_context(ctx).map(|mut ctx| func(ctx.take_json(), ctx.get_params()))).boxed()
+            })
+        }
+
+        //url 支持 /users/:id, /files/*rest 与可选段 /posts/:page?
+        pub fn register_handler<F, R, T>(&mut self, method: T, url: T, func: F) -> RouteHandle<'_>
+        where
+            F: Fn(RequestContext) -> R + Send + Sync + 'static,
+            R: IntoHandlerResult,
+            T: Into<String>,
+        {
+            self.add_route(method.into(), url.into(), move |ctx: RequestContext| {
+                futures::future::ready(Self::parse_context(ctx).and_then(|ctx| func(ctx).into_handler_result())).boxed()
+            })
+        }
+
+        pub fn register_async<F, Fut, T>(&mut self, method: T, url: T, func: F) -> RouteHandle<'_>
+        where
+            F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+            Fut: std::future::Future + Send + 'static,
+            Fut::Output: IntoHandlerResult,
+            T: Into<String>,
+        {
+            self.add_route(method.into(), url.into(), move |ctx: RequestContext| {
+                match Self::parse_context(ctx) {
+                    Ok(ctx) => func(ctx).map(|result| result.into_handler_result()).boxed(),
+                    Err(e) => futures::future::ready(Err(e)).boxed(),
+                }
+            })
+        }
+
+        //参数用 Path, Query, JsonBody 等提取器声明, 同步与异步函数都可以
+        pub fn register<H, Args, T>(&mut self, method: T, url: T, handler: H) -> RouteHandle<'_>
+        where
+            H: Handler<Args>,
+            T: Into<String>,
+        {
+            self.add_route(method.into(), url.into(), move |ctx: RequestContext| handler.call(ctx))
+        }
+
+        //把另一个 Router 的路由挂到 prefix 下, 它的全局中间件, 状态, 错误处理与 multipart 限制只作用于这些路由,
+        //同类型的状态以子 Router 的为准, 404/405 处理作用于 prefix 下没有路由的请求, 静态文件只使用外层的
+        pub fn mount<T: Into<String>>(&mut self, prefix: T, router: Router) {
+            let prefix = prefix.into();
+            let prefix = prefix.trim_end_matches('/');
+            if !prefix.is_empty() && !prefix.starts_with('/') { panic!("prefix must start with '/'"); }
+            if prefix.contains('*') || prefix.contains('?') { panic!("prefix can not contain wildcard or optional segment"); }
+
+            let prefix_segments = prefix.split('/').collect::<Vec<&str>>();
+            let prefix_names = prefix_segments.iter().filter_map(|segment| segment.strip_prefix(':')).map(|name| name.to_string()).collect::<Vec<String>>();
+
+            let mut routes = Vec::new();
+            router.root.into_routes(&mut Vec::new(), &mut routes);
+
+            let with_prefix = |pattern: &[String]| {
+                let rest = match pattern.first() {
+                    Some(first) if first.is_empty() => &pattern[1..],
+                    _ => pattern,
+                };
+                prefix_segments.iter().map(|segment| segment.to_string()).chain(rest.iter().cloned()).collect::<Vec<String>>()
+            };
+
+            for mut handlers in router.mounts {
+                handlers.prefix = with_prefix(&handlers.prefix);
+                self.mounts.push(handlers);
+            }
+            if router.not_found_handler.is_some() || router.method_not_allowed_handler.is_some() {
+                self.mounts.push(MountHandlers {
+                    prefix: prefix_segments.iter().map(|segment| segment.to_string()).collect(),
+                    not_found_handler: router.not_found_handler,
+                    method_not_allowed_handler: router.method_not_allowed_handler,
+                });
+            }
+
+            for (pattern, method, mut route) in routes {
+                route.names = prefix_names.iter().cloned().chain(route.names).collect();
+                route.middlewares = router.middlewares.iter().cloned().chain(route.middlewares).collect();
+                route.error_handler = route.error_handler.or_else(|| router.error_handler.clone());
+                route.multipart_limits = route.multipart_limits.or(router.multipart_limits);
+                if !router.states.is_empty() || route.states.is_some() {
+                    let mut states = (*router.states).clone();
+                    states.extend(route.states.iter().flat_map(|states| states.iter()).map(|(key, state)| (*key, state.clone())));
+                    route.states = Some(std::sync::Arc::new(states));
+                }
+
+                let rest = match pattern.first() {
+                    Some(first) if first.is_empty() => &pattern[1..],
+                    _ => &pattern[..],
+                };
+
+                //子路由的 "/" 同时匹配不带结尾斜杠的 prefix
+                if rest == [""] && !prefix.is_empty() {
+                    self.root.insert(&prefix_segments, method.clone(), route.clone());
+                }
+
+                let segments = prefix_segments.iter().copied().chain(rest.iter().map(|segment| segment.as_str())).collect::<Vec<&str>>();
+                self.root.insert(&segments, method, route);
+            }
+        }
+
+        //router.scope("/api/v1", |r| { r.add_middleware(auth); r.register(...); })
+        pub fn scope<T: Into<String>, F: FnOnce(&mut Router)>(&mut self, prefix: T, build: F) {
+            let mut router = Router::new();
+            build(&mut router);
+            self.mount(prefix, router);
+        }
+
+        //全局中间件, 按添加顺序由外到内执行, 也作用于没有注册路由的静态文件
+        pub fn add_middleware<M: Middleware>(&mut self, middleware: M) {
+            self.middlewares.push(std::sync::Arc::new(middleware));
+        }
+
+        //没有路由的 GET/HEAD 请求在这里查找文件, 默认是 wwwroot, None 时不提供静态文件
+        pub fn set_static_files(&mut self, static_files: Option<StaticFiles>) {
+            self.static_files = static_files.map(std::sync::Arc::new);
+        }
+
+        pub fn set_multipart_limits(&mut self, limits: MultipartLimits) {
+            self.multipart_limits = Some(limits);
+        }
+
+        //同一类型只保存一份, 再次添加会替换
+        pub fn add_state<T: Send + Sync + 'static>(&mut self, state: T) {
+            std::sync::Arc::make_mut(&mut self.states).insert(std::any::TypeId::of::<T>(), std::sync::Arc::new(state));
+        }
+
+        pub fn register_routes<I: IntoIterator<Item = RouteDef>>(&mut self, routes: I) {
+            for route in routes {
+                for method in route.methods {
+                    (route.register)(self, method, route.path);
+                }
+            }
+        }
+
+        //handler 返回的错误经过这里转换成响应
+        pub fn set_error_handler<F: Fn(HttpError) -> HttpResponse + Send + Sync + 'static>(&mut self, func: F) {
+            self.error_handler = Some(std::sync::Arc::new(func));
+        }
+
+        //没有路由也没有静态文件时调用
+        pub fn set_not_found_handler<F: Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static>(&mut self, func: F) {
+            self.not_found_handler = Some(std::sync::Arc::new(func));
+        }
+
+        //路径存在但方法不对时调用, 第二个参数是允许的方法, Allow 头会自动加上
+        pub fn set_method_not_allowed_handler<F: Fn(&HttpRequest, &[String]) -> HttpResponse + Send + Sync + 'static>(&mut self, func: F) {
+            self.method_not_allowed_handler = Some(std::sync::Arc::new(func));
+        }
+
+        fn add_route<F>(&mut self, method: String, url: String, func: F) -> RouteHandle<'_>
+        where
+            F: Fn(RequestContext) -> futures::future::BoxFuture<'static, Result<HttpResponse, HttpError>> + Send + Sync + 'static,
+        {
+            let func: std::sync::Arc<WebFunc> = std::sync::Arc::new(func);
+
+            //每个可选段展开成有与没有两种写法
+            let mut patterns: Vec<Vec<&str>> = vec![vec![]];
+            for segment in url.split('/') {
+                if let Some(name) = segment.strip_suffix('?').filter(|name| name.starts_with(':')) {
+                    let mut with_segment = patterns.clone();
+                    with_segment.iter_mut().for_each(|pattern| pattern.push(name));
+                    patterns.append(&mut with_segment);
+                }
+                else {
+                    patterns.iter_mut().for_each(|pattern| pattern.push(segment));
+                }
+            }
+
+            for pattern in patterns.iter() {
+                let names = pattern.iter()
+                    .filter_map(|segment| segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')))
+                    .map(|name| name.to_string())
+                    .collect();
+                self.root.insert(pattern, method.clone(), Route { names, func: func.clone(), middlewares: Vec::new(), stream_body: false, error_handler: None, multipart_limits: None, states: None });
+            }
+
+            let patterns = patterns.iter().map(|pattern| pattern.iter().map(|segment| segment.to_string()).collect()).collect();
+            RouteHandle { router: self, method, patterns }
+        }
+
+        //先按 / 分段再逐段解码, %2F 不会产生新的段; 编码不合法时返回 None
+        fn split_path(url: &str) -> Option<Vec<String>> {
+            url.split('/').map(percent_decode).collect()
+        }
+
+        fn find(&self, method: &str, url: &str) -> Option<(&Route, PathParams)> {
+            let segments = Self::split_path(url)?;
+            let segments = segments.iter().map(|segment| segment.as_str()).collect::<Vec<&str>>();
+            let mut values = Vec::new();
+            let route = self.root.find(&segments, method, &mut values)?;
+
+            let params = PathParams { params: route.names.iter().cloned().zip(values).collect() };
+            Some((route, params))
+        }
+
+        pub fn contains_url(&self, method: &str, url: &str) -> bool {
+            self.find(method, url).is_some()
+        }
+
+        pub fn is_stream_body(&self, method: &str, url: &str) -> bool {
+            self.find(method, url).is_some_and(|(route, _)| route.stream_body)
+        }
+
+        //路径允许的方法, 有 GET 时自动包含 HEAD, 有任何方法时包含 OPTIONS
+        pub fn allowed_methods(&self, url: &str) -> Vec<String> {
+            let Some(segments) = Self::split_path(url) else { return Vec::new(); };
+            let segments = segments.iter().map(|segment| segment.as_str()).collect::<Vec<&str>>();
+            let mut methods = std::collections::BTreeSet::new();
+            self.root.methods(&segments, &mut methods);
+
+            if methods.is_empty() { return Vec::new(); }
+            if methods.contains("GET") { methods.insert("HEAD".to_string()); }
+            methods.insert("OPTIONS".to_string());
+
+            methods.into_iter().collect()
+        }
+
+        fn parse_body(request: &HttpRequest) -> Result<Json, HttpError> {
+            let def = String::new();
+            let content_type = request.get_headers().get("content-type").unwrap_or(&def);
+
+            let json = if content_type.contains("application/json") && request.get_body().is_empty() {
+                Ok(Json::new(JsonType::Null))
+            }
+            else if content_type.contains("application/json") {
+                Json::parse(std::str::from_utf8(request.get_body())?)
+            }
+            else if content_type.contains("application/x-www-form-urlencoded") {
+                Json::parse_form_data(std::str::from_utf8(request.get_body())?)
+            }
+            else {
+                Json::parse_form_data(request.get_query_string())
+            };
+
+            json.map_err(|e| HttpError::new(HttpResponseStatusCode::BadRequest, e.err_desc))
+        }
+
+        fn parse_context(mut ctx: RequestContext) -> Result<RequestContext, HttpError> {
+            //stream_body 的路由请求体还没有收到, 留给 handler 通过 take_body 读取, 只解析查询参数
+            if ctx.body.get_mut().is_ok_and(|body| body.is_some()) {
+                ctx.json = Json::parse_form_data(ctx.request.get_query_string()).map_err(|e| HttpError::new(HttpResponseStatusCode::BadRequest, e.err_desc))?;
+            }
+            else if has_content_type(&ctx.request, "multipart/form-data") {
+                (ctx.json, ctx.files) = Multipart::parse(&ctx.request, &ctx.multipart_limits)?.into_parts();
+            }
+            else {
+                ctx.json = Self::parse_body(&ctx.request)?;
+            }
+            Ok(ctx)
+        }
+
+        fn handle_error(error_handler: Option<&ErrorFunc>, err: HttpError) -> HttpResponse {
+            if let Some(error_handler) = error_handler {
+                return error_handler(err);
+            }
+
+            let mut response = HttpResponse::new(err.get_status_code());
+            response.insert_header("content-type", "text/plain; charset=utf-8");
+            response.set_body(err.message.into_bytes());
+            response
+        }
+
+        fn not_found(handler: &Option<std::sync::Arc<NotFoundFunc>>, request: &HttpRequest) -> HttpResponse {
+            if let Some(handler) = handler {
+                return handler(request);
+            }
+
+            let mut response = HttpResponse::new(HttpResponseStatusCode::NotFound);
+            response.insert_header("content-type", "text/plain; charset=utf-8");
+            response.set_body("404 not found".into());
+            response
+        }
+
+        //prefix 最长且设置了处理函数的 mount 优先, 都没有时用自己的
+        fn mount_handler<T: ?Sized>(&self, url: &str, handler: impl Fn(&MountHandlers) -> Option<&std::sync::Arc<T>>) -> Option<std::sync::Arc<T>> {
+            let segments = Self::split_path(url)?;
+            let segments = segments.iter().map(|segment| segment.as_str()).collect::<Vec<&str>>();
+            self.mounts.iter()
+                .filter(|mount| mount.matches(&segments))
+                .filter_map(|mount| handler(mount).map(|handler| (mount.prefix.len(), handler)))
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, handler)| handler.clone())
+        }
+
+        //没有注册的 GET/HEAD 按静态文件处理, 都没有时返回 404
+        fn fallback(&self, request: &HttpRequest) -> std::sync::Arc<WebFunc> {
+            let not_found_handler = self.mount_handler(request.get_uri(), |mount| mount.not_found_handler.as_ref()).or_else(|| self.not_found_handler.clone());
+            let allowed = self.allowed_methods(request.get_uri());
+
+            if allowed.is_empty() {
+                let static_files = self.static_files.clone().filter(|_| request.get_method() == "GET" || request.get_method() == "HEAD");
+                return std::sync::Arc::new(move |ctx: RequestContext| {
+                    let response = match &static_files {
+                        Some(static_files) => static_files.serve_request(&ctx.request).map_err(HttpError::from),
+                        None => Ok(HttpResponse::new(HttpResponseStatusCode::NotFound)),
+                    };
+
+                    let response = match response {
+                        Ok(response) if response.get_status_code() == HttpResponseStatusCode::NotFound => Ok(Self::not_found(&not_found_handler, &ctx.request)),
+                        response => response,
+                    };
+                    futures::future::ready(response).boxed()
+                });
+            }
+
+            let method_not_allowed_handler = self.mount_handler(request.get_uri(), |mount| mount.method_not_allowed_handler.as_ref()).or_else(|| self.method_not_allowed_handler.clone());
+            let is_options = request.get_method() == "OPTIONS";
+            std::sync::Arc::new(move |ctx: RequestContext| {
+                let mut response = if is_options {
+                    HttpResponse::new(HttpResponseStatusCode::NoContent)
+                }
+                else if let Some(handler) = &method_not_allowed_handler {
+                    handler(&ctx.request, &allowed)
+                }
+                else {
+                    let mut response = HttpResponse::new(HttpResponseStatusCode::MethodNotAllowed);
+                    response.insert_header("content-type", "text/plain; charset=utf-8");
+                    response.set_body("405 method not allowed".into());
+                    response
+                };
+
+                if !response.get_headers().contains_key("allow") {
+                    response.insert_header("allow", allowed.join(", "));
+                }
+                futures::future::ready(Ok(response)).boxed()
+            })
+        }
+
+        pub async fn call(&self, request: &HttpRequest, peer_addr: Option<std::net::SocketAddr>) -> Result<HttpResponse, BacktraceError> {
+            self.call_with_body(request, peer_addr, None).await
+        }
+
+        async fn call_with_body(&self, request: &HttpRequest, peer_addr: Option<std::net::SocketAddr>, body: Option<RequestBody>) -> Result<HttpResponse, BacktraceError> {
+            let method = request.get_method().as_str();
+            if Self::split_path(request.get_uri()).is_none() {
+                return Ok(Self::handle_error(self.error_handler.as_deref(), bad_request("invalid path encoding")));
+            }
+
+            let route = self.find(method, request.get_uri())
+                .or_else(|| if method == "HEAD" { self.find("GET", request.get_uri()) } else { None });
+
+            let (endpoint, params, route) = match route {
+                Some((route, params)) => (route.func.clone(), params, Some(route)),
+                None => (self.fallback(request), PathParams::default(), None),
+            };
+
+            let route_middlewares = route.map(|route| route.middlewares.as_slice()).unwrap_or_default();
+            let error_handler = route.and_then(|route| route.error_handler.as_deref()).or(self.error_handler.as_deref());
+            let multipart_limits = route.and_then(|route| route.multipart_limits).or(self.multipart_limits).unwrap_or_default();
+            let states = match route.and_then(|route| route.states.as_ref()) {
+                Some(route_states) => {
+                    let mut states = (*self.states).clone();
+                    states.extend(route_states.iter().map(|(key, state)| (*key, state.clone())));
+                    std::sync::Arc::new(states)
+                },
+                None => self.states.clone(),
+            };
+
+            let chain = self.middlewares.iter().chain(route_middlewares).cloned().collect();
+            let ctx = RequestContext { request: request.clone(), params, json: Json::new(JsonType::Null), peer_addr, states, body: std::sync::Mutex::new(body), multipart_limits, files: Vec::new() };
+            let result = Next { chain: std::sync::Arc::new(chain), index: 0, endpoint }.run(ctx).await;
+
+            let mut response = result.unwrap_or_else(|e| Self::handle_error(error_handler, e));
+
+            //HEAD 保留 GET 的响应头 (包括 content-length), 只去掉响应体
+            if method == "HEAD" {
+                if response.is_chunked() { response.insert_header("transfer-encoding", "chunked"); }
+                response.body.clear();
+                response.stream = None;
+                response.trailers = None;
+            }
+
+            Ok(response)
+        }
+    }
+    
+    #[derive(Debug, Clone)]
+    enum PemSource {
+        File(std::path::PathBuf),
+        Memory(Vec<u8>),
+    }
+
+    impl PemSource {
+        //文件每次 build 时重新读取, 这样 reload 才能拿到新证书
+        fn load(&self) -> Result<Vec<u8>, BacktraceError> {
+            match self {
+                PemSource::File(path) => Ok(std::fs::read(path)?),
+                PemSource::Memory(pem) => Ok(pem.clone()),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct TlsCertificate {
+        cert_chain: PemSource,
+        private_key: PemSource,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TlsVersion {
+        Tls1_0,
+        Tls1_1,
+        Tls1_2,
+        Tls1_3,
+    }
+
+    impl From<TlsVersion> for openssl::ssl::SslVersion {
+        fn from(item: TlsVersion) -> Self {
+            match item {
+                TlsVersion::Tls1_0 => openssl::ssl::SslVersion::TLS1,
+                TlsVersion::Tls1_1 => openssl::ssl::SslVersion::TLS1_1,
+                TlsVersion::Tls1_2 => openssl::ssl::SslVersion::TLS1_2,
+                TlsVersion::Tls1_3 => openssl::ssl::SslVersion::TLS1_3,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ClientAuth {
+        //客户端可以不带证书, 带了就必须能通过校验
+        Optional,
+        Required,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct TlsConfig {
+        default_cert: TlsCertificate,
+        client_auth: Option<(ClientAuth, PemSource)>,
+        //按 SNI 主机名选择证书, 支持 *.example.com
+        sni_certs: Vec<(String, TlsCertificate)>,
+        min_version: Option<TlsVersion>,
+        cipher_list: Option<String>,
+        ciphersuites: Option<String>,
+    }
+
+    impl TlsConfig {
+        fn new(default_cert: TlsCertificate) -> Self {
+            Self {
+                default_cert,
+                client_auth: None,
+                sni_certs: Default::default(),
+                min_version: None,
+                cipher_list: None,
+                ciphersuites: None,
+            }
+        }
+
+        pub fn from_files<P: Into<std::path::PathBuf>>(cert_path: P, key_path: P) -> Self {
+            Self::new(TlsCertificate { cert_chain: PemSource::File(cert_path.into()), private_key: PemSource::File(key_path.into()) })
+        }
+
+        pub fn from_pem<T: Into<Vec<u8>>>(cert_pem: T, key_pem: T) -> Self {
+            Self::new(TlsCertificate { cert_chain: PemSource::Memory(cert_pem.into()), private_key: PemSource::Memory(key_pem.into()) })
+        }
+
+        pub fn add_sni_files<H: Into<String>, P: Into<std::path::PathBuf>>(&mut self, host: H, cert_path: P, key_path: P) -> &mut Self {
+            self.sni_certs.push((host.into().to_lowercase(), TlsCertificate { cert_chain: PemSource::File(cert_path.into()), private_key: PemSource::File(key_path.into()) }));
+            self
+        }
+
+        pub fn add_sni_pem<H: Into<String>, T: Into<Vec<u8>>>(&mut self, host: H, cert_pem: T, key_pem: T) -> &mut Self {
+            self.sni_certs.push((host.into().to_lowercase(), TlsCertificate { cert_chain: PemSource::Memory(cert_pem.into()), private_key: PemSource::Memory(key_pem.into()) }));
+            self
+        }
+
+        //用 ca_path 中的 CA 校验客户端证书
+        pub fn client_auth_files<P: Into<std::path::PathBuf>>(&mut self, ca_path: P, mode: ClientAuth) -> &mut Self {
+            self.client_auth = Some((mode, PemSource::File(ca_path.into())));
+            self
+        }
+
+        pub fn client_auth_pem<T: Into<Vec<u8>>>(&mut self, ca_pem: T, mode: ClientAuth) -> &mut Self {
+            self.client_auth = Some((mode, PemSource::Memory(ca_pem.into())));
+            self
+        }
+
+        pub fn min_version(&mut self, version: TlsVersion) -> &mut Self {
+            self.min_version = Some(version);
+            self
+        }
+
+        //TLS1.2 及以下使用 OpenSSL cipher list 格式
+        pub fn cipher_list<T: Into<String>>(&mut self, ciphers: T) -> &mut Self {
+            self.cipher_list = Some(ciphers.into());
+            self
+        }
+
+        //TLS1.3 的 ciphersuites, 例如 "TLS_AES_256_GCM_SHA384"
+        pub fn ciphersuites<T: Into<String>>(&mut self, ciphersuites: T) -> &mut Self {
+            self.ciphersuites = Some(ciphersuites.into());
+            self
+        }
+
+        fn new_builder(&self, cert: &TlsCertificate) -> Result<openssl::ssl::SslAcceptorBuilder, BacktraceError> {
+            let mut builder = openssl::ssl::SslAcceptor::mozilla_intermediate(openssl::ssl::SslMethod::tls())?;
+
+            let mut chain = openssl::x509::X509::stack_from_pem(&cert.cert_chain.load()?)?.into_iter();
+            let leaf = chain.next().ok_or(std::io::Error::other("certificate pem is empty"))?;
+            builder.set_certificate(&leaf)?;
+            for extra in chain {
+                builder.add_extra_chain_cert(extra)?;
+            }
+            let private_key = openssl::pkey::PKey::private_key_from_pem(&cert.private_key.load()?)?;
+            builder.set_private_key(&private_key)?;
+            builder.check_private_key()?;
+
+            if let Some((mode, ca)) = &self.client_auth {
+                let mut store = openssl::x509::store::X509StoreBuilder::new()?;
+                for ca_cert in openssl::x509::X509::stack_from_pem(&ca.load()?)? {
+                    builder.add_client_ca(&ca_cert)?;
+                    store.add_cert(ca_cert)?;
+                }
+                builder.set_cert_store(store.build());
+
+                let verify_mode = match mode {
+                    ClientAuth::Optional => openssl::ssl::SslVerifyMode::PEER,
+                    ClientAuth::Required => openssl::ssl::SslVerifyMode::PEER | openssl::ssl::SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+                };
+                builder.set_verify(verify_mode);
+                builder.set_session_id_context(b"rust_web")?;
+            }
+
+            builder.set_min_proto_version(self.min_version.map(|version| version.into()))?;
+            if let Some(cipher_list) = &self.cipher_list {
+                builder.set_cipher_list(cipher_list)?;
+            }
+            if let Some(ciphersuites) = &self.ciphersuites {
+                builder.set_ciphersuites(ciphersuites)?;
+            }
+
+            Ok(builder)
+        }
+
+        fn build(&self) -> Result<openssl::ssl::SslAcceptor, BacktraceError> {
+            let mut sni_contexts = Vec::new();
+            for (host, cert) in self.sni_certs.iter() {
+                sni_contexts.push((host.clone(), self.new_builder(cert)?.build().into_context()));
+            }
+
+            let mut builder = self.new_builder(&self.default_cert)?;
+            if !sni_contexts.is_empty() {
+                builder.set_servername_callback(move |ssl, _alert| {
+                    let Some(name) = ssl.servername(openssl::ssl::NameType::HOST_NAME).map(|name| name.to_lowercase()) else { return Ok(()); };
+
+                    let matched = sni_contexts.iter().find(|(host, _)| {
+                        match host.strip_prefix("*.") {
+                            Some(domain) => name.split_once('.').is_some_and(|(_, rest)| rest == domain),
+                            None => *host == name,
+                        }
+                    });
+
+                    if let Some((_, context)) = matched {
+                        ssl.set_ssl_context(context).map_err(|_| openssl::ssl::SniError::ALERT_FATAL)?;
+                    }
+                    Ok(())
+                });
+            }
+
+            Ok(builder.build())
+        }
+    }
+
+    //证书只在创建和 reload 时加载, 每个连接共享同一个 SslAcceptor
+    #[derive(Clone)]
+    pub struct TlsAcceptor {
+        config: std::sync::Arc<TlsConfig>,
+        acceptor: std::sync::Arc<std::sync::RwLock<std::sync::Arc<openssl::ssl::SslAcceptor>>>,
+    }
+
+    impl TlsAcceptor {
+        pub fn new(config: TlsConfig) -> Result<Self, BacktraceError> {
+            let acceptor = config.build()?;
+
+            Ok(Self {
+                config: std::sync::Arc::new(config),
+                acceptor: std::sync::Arc::new(std::sync::RwLock::new(std::sync::Arc::new(acceptor))),
+            })
+        }
+
+        //重新读取证书文件, 只影响之后建立的连接; 失败时继续使用旧证书
+        pub fn reload(&self) -> Result<(), BacktraceError> {
+            let acceptor = self.config.build()?;
+            *self.acceptor.write().map_err(|e| std::io::Error::other(e.to_string()))? = std::sync::Arc::new(acceptor);
+
+            Ok(())
+        }
+
+        fn current(&self) -> std::sync::Arc<openssl::ssl::SslAcceptor> {
+            match self.acceptor.read() {
+                Ok(acceptor) => acceptor.clone(),
+                Err(e) => e.into_inner().clone(),
+            }
+        }
+    }
+
+    //openssl 只做内存里的加解密, 密文由 AsyncSslStream 异步收发
+    #[derive(Debug, Default)]
+    struct SslBuffer {
+        incoming: Vec<u8>,
+        outgoing: Vec<u8>,
+    }
+
+    impl Read for SslBuffer {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.incoming.is_empty() { return Err(std::io::ErrorKind::WouldBlock.into()); }
+
+            let len = buf.len().min(self.incoming.len());
+            buf[..len].copy_from_slice(&self.incoming[..len]);
+            self.incoming.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for SslBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.outgoing.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct AsyncSslStream {
+        socket: async_std::net::TcpStream,
+        ssl: openssl::ssl::SslStream<SslBuffer>,
+    }
+
+    impl AsyncSslStream {
+        async fn accept(acceptor: &openssl::ssl::SslAcceptor, mut socket: async_std::net::TcpStream) -> Result<Self, BacktraceError> {
+            let mut handshake = acceptor.accept(SslBuffer::default());
+            loop {
+                match handshake {
+                    Ok(ssl) => {
+                        let mut stream = Self { socket, ssl };
+                        stream.flush_outgoing().await?;
+                        return Ok(stream);
+                    },
+                    Err(openssl::ssl::HandshakeError::WouldBlock(mut mid)) => {
+                        Self::transfer(&mut socket, mid.get_mut()).await?;
+                        handshake = mid.handshake();
+                    },
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        //先把待发送的密文写出去, 再等对端的密文
+        async fn transfer(socket: &mut async_std::net::TcpStream, buffer: &mut SslBuffer) -> std::io::Result<usize> {
+            socket.write_all(&std::mem::take(&mut buffer.outgoing)).await?;
+
+            let mut buf = [0u8; 4096];
+            let len = socket.read(&mut buf).await?;
+            if len == 0 { return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "socket close")); }
+
+            buffer.incoming.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        async fn flush_outgoing(&mut self) -> std::io::Result<()> {
+            let outgoing = std::mem::take(&mut self.ssl.get_mut().outgoing);
+            self.socket.write_all(&outgoing).await
+        }
+
+        async fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            loop {
+                match self.ssl.ssl_read(buf) {
+                    Ok(len) => return Ok(len),
+                    Err(e) if e.code() == openssl::ssl::ErrorCode::ZERO_RETURN => return Ok(0),
+                    Err(e) if e.code() == openssl::ssl::ErrorCode::WANT_READ => {
+                        if let Err(e) = Self::transfer(&mut self.socket, self.ssl.get_mut()).await {
+                            if e.kind() == std::io::ErrorKind::UnexpectedEof { return Ok(0); }
+                            return Err(e);
+                        }
+                    },
+                    Err(e) => return Err(e.into_io_error().unwrap_or_else(std::io::Error::other)),
+                }
+            }
+        }
+
+        fn peer_certificate(&self) -> Result<Option<PeerCertificate>, BacktraceError> {
+            let ssl = self.ssl.ssl();
+            match ssl.peer_certificate() {
+                Some(cert) if ssl.verify_result() == openssl::x509::X509VerifyResult::OK => Ok(Some(PeerCertificate::from_x509(&cert)?)),
+                _ => Ok(None),
+            }
+        }
+
+        async fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+            let mut had_write = 0usize;
+            while had_write < buf.len() {
+                match self.ssl.ssl_write(&buf[had_write..]) {
+                    Ok(len) => had_write += len,
+                    Err(e) if e.code() == openssl::ssl::ErrorCode::WANT_READ => {
+                        Self::transfer(&mut self.socket, self.ssl.get_mut()).await?;
+                    },
+                    Err(e) => return Err(e.into_io_error().unwrap_or_else(std::io::Error::other)),
+                }
+            }
+
+            self.flush_outgoing().await
+        }
+    }
+
+    enum HttpStream {
+        Tcp(async_std::net::TcpStream),
+        Ssl(Box<AsyncSslStream>),
+    }
+
+    impl HttpStream {
+        async fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self {
+                HttpStream::Tcp(stream) => stream.read(buf).await,
+                HttpStream::Ssl(stream) => stream.read(buf).await,
+            }
+        }
+
+        async fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+            match self {
+                HttpStream::Tcp(stream) => stream.write_all(buf).await,
+                HttpStream::Ssl(stream) => stream.write_all(buf).await,
+            }
+        }
+    }
+
+    const SOCKET_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5000);
+    //每次从 socket 读取的最大字节数, 读到的 Vec 直接交给 HttpRequestReader
+    const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+    #[derive(Debug, Clone, Copy)]
+    struct ConnectionConfig {
+        keep_alive_timeout: std::time::Duration,
+        max_requests: usize,
+        header_timeout: std::time::Duration,
+        request_timeout: std::time::Duration,
+        limits: RequestLimits,
+    }
+
+    impl Default for ConnectionConfig {
+        fn default() -> Self {
+            Self {
+                keep_alive_timeout: std::time::Duration::from_secs(5),
+                max_requests: 100,
+                header_timeout: std::time::Duration::from_secs(10),
+                request_timeout: std::time::Duration::from_secs(60),
+                limits: Default::default(),
+            }
+        }
+    }
+
+    pub struct HttpServer {
+        socket: async_std::net::TcpListener,
+        router: Router,
+        config: ConnectionConfig,
+        use_ssl: bool,
+        tls: Option<TlsAcceptor>,
+    }
+
+    impl HttpServer {
+        async fn send_response(stream: &mut HttpStream, mut response: HttpResponse) -> Result<(), BacktraceError> {
+            //没有响应体的状态码也不能带 content-length 或 transfer-encoding
+            if !response.get_status_code().has_body() {
+                response.body.clear();
+                response.stream = None;
+                response.trailers = None;
+                response.remove_header("content-length");
+                response.remove_header("transfer-encoding");
+            }
+
+            let head_content = format!
+                                (
+                                    "{version} {status_code} {status_desc}\r\n{header}\r\n", 
+                                    version = response.get_version(), 
+                                    status_code = response.get_status_code().as_u16(),
+                                    status_desc = response.get_status_desc(),
+                                    header = (|| {
+                                        let mut result = String::new();
+                                        for (key, val) in response.get_headers().iter() {
+                                            result += format!("{}: {}\r\n", key, val).as_str();
+                                        }
+
+                                        return result;
+                                    })()
+                                );
+
+            let wait_write = vec![head_content.as_bytes(), response.get_body().as_slice()];
+            for cur_buf in wait_write {
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(cur_buf)).await?;
+            }
+
+            let chunked = response.get_headers().get("transfer-encoding").is_some_and(|val| val.eq_ignore_ascii_case("chunked"));
+            match response.stream.take() {
+                Some(ResponseStream::File(file, file_len)) => {
+                    //只发送 content-length 声明的长度, 文件在发送时变长也不会混进下一个响应; 变短时只能关闭连接
+                    let mut file = file.take(file_len);
+                    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+                    let mut sent = 0u64;
+                    loop {
+                        let len = file.read(&mut buf).await?;
+                        if len == 0 { break; }
+                        sent += len as u64;
+                        Self::send_body_data(stream, &buf[..len], chunked).await?;
+                    }
+                    if sent < file_len {
+                        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file shorter than content-length").into());
+                    }
+                },
+                Some(ResponseStream::Stream(mut body)) => {
+                    while let Some(data) = body.next().await {
+                        Self::send_body_data(stream, &data?, chunked).await?;
+                    }
+                },
+                None => return Ok(()),
+            }
+
+            if chunked {
+                let mut last_chunk = String::from("0\r\n");
+                if let Some(Trailers(trailers)) = response.trailers.take() {
+                    for (key, val) in trailers() {
+                        last_chunk += format!("{}: {}\r\n", key, val).as_str();
+                    }
+                }
+                last_chunk += "\r\n";
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(last_chunk.as_bytes())).await?;
+            }
+
+            Ok(())
+        }
+
+        async fn send_body_data(stream: &mut HttpStream, data: &[u8], chunked: bool) -> Result<(), BacktraceError> {
+            //空的 chunk 会被当成结束标记
+            if data.is_empty() { return Ok(()); }
+
+            if chunked {
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(format!("{:x}\r\n", data.len()).as_bytes())).await?;
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(data)).await?;
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(b"\r\n")).await?;
+            }
+            else {
+                async_std::io::timeout(SOCKET_TIMEOUT, stream.write_all(data)).await?;
+            }
+
+            Ok(())
+        }
+
+        //从连接读取一次交给 reader, 从收到请求的第一个字节开始计时, 一点点发送数据的客户端到时间后回复 408
+        async fn read_more(stream: &mut HttpStream, reader: &mut HttpRequestReader, config: &ConnectionConfig, idle_timeout: std::time::Duration, started: &mut Option<std::time::Instant>) -> Result<(), BacktraceError> {
+            if started.is_none() && reader.has_buffered() { *started = Some(std::time::Instant::now()); }
+
+            let (timeout, is_deadline) = match *started {
+                Some(started) => {
+                    let limit = if reader.is_header_finished() { config.request_timeout } else { config.header_timeout.min(config.request_timeout) };
+                    let left = limit.saturating_sub(started.elapsed());
+                    if left < SOCKET_TIMEOUT { (left, true) } else { (SOCKET_TIMEOUT, false) }
+                },
+                None => (idle_timeout, false),
+            };
+
+            let mut buf = vec![0u8; READ_BUFFER_SIZE];
+            let buf_size = match async_std::io::timeout(timeout, stream.read(&mut buf)).await {
+                Err(e) if is_deadline && e.kind() == std::io::ErrorKind::TimedOut => {
+                    return Err(reader.reject(HttpResponseStatusCode::RequestTimeout, "request timeout"));
+                },
+                res => res?,
+            };
+            //println!("{:?}", std::str::from_utf8(&buf[0..buf_size])?);
+
+            if buf_size == 0 { return Err(std::io::Error::other("socket close").into()); }
+
+            buf.truncate(buf_size);
+            reader.read(buf)?;
+            Ok(())
+        }
+
+        //stream_body 的路由读完请求头就返回, 请求体由 pump_body 继续读取
+        async fn handle_accept(stream: &mut HttpStream, reader: &mut HttpRequestReader, config: &ConnectionConfig, idle_timeout: std::time::Duration, router: &Router, started: &mut Option<std::time::Instant>) -> Result<HttpRequest, BacktraceError> {
+            //上一次读取时已经收到的数据可能包含完整的下一个请求
+            reader.read(Vec::new())?;
+
+            *started = None;
+            let mut stream_body = None;
+            loop {
+                if stream_body.is_none() {
+                    stream_body = reader.get_head().map(|head| router.is_stream_body(head.get_method(), head.get_uri()));
+                }
+                if stream_body == Some(true) { return reader.take_head(); }
+                if reader.is_finished() { break; }
+
+                Self::read_more(stream, reader, config, idle_timeout, started).await?;
+            }
+
+            //println!("{:#?}", http_request);
+
+            //println!("finish!!!!!!!!!!!!");
+
+            reader.take_request()
+        }
+
+        //把解码后的请求体交给 handler, handler 不再读取时返回 false, 这时连接里剩下的请求体没有读完
+        async fn pump_body(stream: &mut HttpStream, reader: &mut HttpRequestReader, config: &ConnectionConfig, started: &mut Option<std::time::Instant>, mut sender: BodySender) -> Result<bool, BacktraceError> {
+            loop {
+                let data = reader.take_body_data();
+                if !data.is_empty() && futures::SinkExt::send(&mut sender, Ok(data)).await.is_err() { return Ok(false); }
+                if reader.is_finished() {
+                    reader.take_request()?;
+                    return Ok(true);
+                }
+                if sender.is_closed() { return Ok(false); }
+
+                if let Err(e) = Self::read_more(stream, reader, config, SOCKET_TIMEOUT, started).await {
+                    let _ = futures::SinkExt::send(&mut sender, Err(std::io::Error::other(e.err_desc.clone()))).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        //读取请求出错: 连接关闭时直接结束, 请求格式有问题时回复错误后关闭, 剩下的数据已经无法可靠地分割
+        async fn reject_request(stream: &mut HttpStream, router: &Router, reader: &mut HttpRequestReader, e: BacktraceError) -> Result<(), BacktraceError> {
+            if e.err_desc == "socket close" {
+                return Ok(());
+            }
+
+            let Some(err) = reader.take_rejection() else { return Err(e); };
+            let mut response = Router::handle_error(router.error_handler.as_deref(), err);
+            response.insert_header("connection", "close");
+            Self::send_response(stream, response).await
+        }
+
+        fn is_keep_alive(request: &HttpRequest) -> bool {
+            let connection = request.get_headers().get("connection").map(|val| val.to_lowercase()).unwrap_or_default();
+            let has_token = |token: &str| connection.split(',').any(|item| item.trim() == token);
+
+            if has_token("close") { return false; }
+
+            //HTTP/1.0 默认短连接, HTTP/1.1 默认长连接
+            request.get_version() != "HTTP/1.0" || has_token("keep-alive")
+        }
+
+        async fn accept_process(config: ConnectionConfig, tls: Option<TlsAcceptor>, router: std::sync::Arc::<Router>, stream: async_std::net::TcpStream) -> Result<(), BacktraceError> {
+            println!("accept_process...");
+
+            let peer_addr = stream.peer_addr()?;
+            let mut wrap_stream: HttpStream = if let Some(tls) = tls { 
+                let acceptor = tls.current();
+                HttpStream::Ssl(Box::new(async_std::io::timeout(SOCKET_TIMEOUT, async {
+                    AsyncSslStream::accept(&acceptor, stream).await.map_err(|e| std::io::Error::other(e.err_desc))
+                }).await?))
+            }
+            else {
+                HttpStream::Tcp(stream)
+            };
+
+            let peer_certificate = match &wrap_stream {
+                HttpStream::Ssl(stream) => stream.peer_certificate()?,
+                HttpStream::Tcp(_) => None,
+            };
+
+            let mut reader = HttpRequestReader::with_limits(config.limits);
+            let mut handled_count = 0usize;
+            let mut started = None;
+            loop {
+                println!("ip:{} handle_accept...", peer_addr);
+                let idle_timeout = if handled_count == 0 { SOCKET_TIMEOUT } else { config.keep_alive_timeout };
+                let request_res = Self::handle_accept(&mut wrap_stream, &mut reader, &config, idle_timeout, &router, &mut started).await;
+
+                match request_res {
+                    Err(e) => {
+                        return Self::reject_request(&mut wrap_stream, &router, &mut reader, e).await;
+                    },
+                    Ok(mut request) => {
+                        request.set_peer_certificate(peer_certificate.clone());
+
+                        //handler 与读取请求体同时进行, 两边都结束后才发送响应
+                        let mut body_finished = true;
+                        let mut response = if reader.is_streaming() {
+                            let (sender, body) = RequestBody::channel();
+                            let (response, pumped) = futures::join!(
+                                router.call_with_body(&request, Some(peer_addr), Some(body)),
+                                Self::pump_body(&mut wrap_stream, &mut reader, &config, &mut started, sender)
+                            );
+                            match pumped {
+                                Ok(finished) => body_finished = finished,
+                                Err(e) => return Self::reject_request(&mut wrap_stream, &router, &mut reader, e).await,
+                            }
+                            response?
+                        }
+                        else {
+                            router.call(&request, Some(peer_addr)).await?
+                        };
+
+                        //println!("{:#?}", request);
+
+                        //HTTP/1.0 不支持 chunked, 只能靠关闭连接表示响应体结束
+                        let mut close_delimited = false;
+                        if response.is_chunked() {
+                            close_delimited = request.get_version() == "HTTP/1.0";
+                            if close_delimited { response.remove_header("transfer-encoding"); }
+                            else { response.insert_header("transfer-encoding", "chunked"); }
+                        }
+                        else if !response.get_headers().contains_key("content-length") && !response.get_headers().contains_key("transfer-encoding") {
+                            response.set_body(Vec::new());
+                        }
+
+                        handled_count += 1;
+                        let keep_alive = Self::is_keep_alive(&request)
+                            && body_finished
+                            && !close_delimited
+                            && handled_count < config.max_requests
+                            && !config.keep_alive_timeout.is_zero();
+
+                        if keep_alive {
+                            response.insert_header("connection", "keep-alive");
+                            response.insert_header("keep-alive", format!("timeout={}, max={}", config.keep_alive_timeout.as_secs(), config.max_requests - handled_count));
+                        }
+                        else {
+                            response.insert_header("connection", "close");
+                        }
+
+                        Self::send_response(&mut wrap_stream, response).await?;
+
+                        if !keep_alive { break; }
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        pub async fn new(ip_addr: &str) -> Result<Self, BacktraceError> {
+            let bind_res = async_std::net::TcpListener::bind(ip_addr).await;
+            match bind_res {
+                Ok(val) => { 
+                    //默认打印请求日志并支持 gzip
+                    let mut router = Router::new();
+                    router.add_middleware(Logger);
+                    router.add_middleware(Compression::new());
+
+                    Ok(
+                        Self {
+                            socket: val,
+                            router,
+                            config: Default::default(),
+                            use_ssl: false,
+                            tls: None,
+                        }
+                    )
+                },
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        pub fn get_router(&mut self) -> &mut Router {
+            &mut self.router
+        }
+
+        //替换默认的 Router, 默认的日志与压缩中间件也随之去掉
+        pub fn set_router(&mut self, router: Router) -> &mut Self {
+            self.router = router;
+            self
+        }
+
+        pub fn local_addr(&self) -> Result<std::net::SocketAddr, BacktraceError> {
+            Ok(self.socket.local_addr()?)
+        }
+
+        pub fn use_ssl(&mut self, is_use: bool) -> &mut Self{
+            self.use_ssl = is_use;
+            return self;
+        }
+
+        //证书在这里就加载好, 配置有误时立即返回错误
+        pub fn use_tls(&mut self, config: TlsConfig) -> Result<&mut Self, BacktraceError> {
+            self.tls = Some(TlsAcceptor::new(config)?);
+            self.use_ssl = true;
+            Ok(self)
+        }
+
+        //用于在运行中 reload 证书
+        pub fn get_tls_acceptor(&self) -> Option<TlsAcceptor> {
+            self.tls.clone()
+        }
+
+        //timeout 为 0 时关闭长连接
+        pub fn keep_alive_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+            self.config.keep_alive_timeout = timeout;
+            self
+        }
+
+        pub fn max_keep_alive_requests(&mut self, max_requests: usize) -> &mut Self {
+            self.config.max_requests = max_requests.max(1);
+            self
+        }
+
+        pub fn request_limits(&mut self, limits: RequestLimits) -> &mut Self {
+            self.config.limits = limits;
+            self
+        }
+
+        //从收到第一个字节到请求头读完的最长时间
+        pub fn header_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+            self.config.header_timeout = timeout;
+            self
+        }
+
+        //整个请求 (包括请求体) 读完的最长时间
+        pub fn request_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+            self.config.request_timeout = timeout;
+            self
+        }
+
+        //listen 拿走 server, Router 在这里定型后被所有连接共享
+        pub async fn listen(self) -> Result<(), BacktraceError> { 
+            println!("incoming...");
+            let tls = match (&self.tls, self.use_ssl) {
+                (Some(tls), true) => Some(tls.clone()),
+                (None, true) => Some(TlsAcceptor::new(TlsConfig::from_files("cert.pem", "key.pem"))?),
+                _ => None,
+            };
+
+            let router = std::sync::Arc::new(self.router);
+            let mut incoming = self.socket.incoming();
+            while let Some(stream_res) = incoming.next().await {
+                let router_copy = std::sync::Arc::clone(&router);
+                let config = self.config;
+                let tls = tls.clone();
+                match stream_res {
+                   Ok(stream) => {
+						let _handle = async_std::task::spawn(async move {
+                        	if let Err(e) = Self::accept_process(config, tls, router_copy, stream).await {
+                                if e.err_desc != "future timed out" {
+								    println!("{}", e);
+                                }
+							}
+						});
+                   },
+                   Err(e) => {
+                        return Err(e.into());
+                   }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    pub struct HttpClient {
+        client_cert: Option<(Vec<u8>, Vec<u8>)>,
+        root_ca: Vec<Vec<u8>>,
+    }
+
+    impl HttpClient {
+        //双向 TLS 时发给服务端的证书
+        pub fn set_client_cert_pem<T: Into<Vec<u8>>>(&mut self, cert_pem: T, key_pem: T) -> &mut Self {
+            self.client_cert = Some((cert_pem.into(), key_pem.into()));
+            self
+        }
+
+        //在系统 CA 之外额外信任的 CA
+        pub fn add_root_ca_pem<T: Into<Vec<u8>>>(&mut self, ca_pem: T) -> &mut Self {
+            self.root_ca.push(ca_pem.into());
+            self
+        }
+
+        fn new_connector(&self) -> Result<openssl::ssl::SslConnector, BacktraceError> {
+            let mut connector = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls())?;
+
+            for ca_pem in self.root_ca.iter() {
+                for ca_cert in openssl::x509::X509::stack_from_pem(ca_pem)? {
+                    connector.cert_store_mut().add_cert(ca_cert)?;
+                }
+            }
+
+            if let Some((cert_pem, key_pem)) = &self.client_cert {
+                let mut chain = openssl::x509::X509::stack_from_pem(cert_pem)?.into_iter();
+                let leaf = chain.next().ok_or(std::io::Error::other("certificate pem is empty"))?;
+                connector.set_certificate(&leaf)?;
+                for extra in chain {
+                    connector.add_extra_chain_cert(extra)?;
+                }
+                let private_key = openssl::pkey::PKey::private_key_from_pem(key_pem)?;
+                connector.set_private_key(&private_key)?;
+            }
+
+            Ok(connector.build())
+        }
+
+        fn get_request_header_string(request: &HttpRequest) -> String {
+            format!("{method} {uri}{query_string} {version}\r\n\
+                    {headers}\
+                    \r\n",
+                    method = request.get_method(),
+                    uri = request.get_uri(), 
+                    query_string = request.get_query_string(),
+                    version = request.get_version(),
+                    headers = request.get_headers().iter().map(|(key, val)| {
+                        //let mut new_key = key.clone();
+                        //new_key.replace_range(0..1, &key.as_str()[0..1].to_uppercase());
+                        format!("{key}: {val}\r\n", )
+                    }).collect::<String>()
+            )
+        }
+
+        pub fn send<T: Into<String>>(&self, address: T, mut request: HttpRequest) -> Result<HttpResponse, BacktraceError> {
+            let mut address: String = address.into();
+            let host: String;
+            let port: String;
+            let uri: String;
+            let mut protocol = String::default();
+
+            for item in ["http://", "https://"] {
+                if let Some((left, right)) = address.split_once(item) {
+                    if !left.is_empty() {panic!("")}
+                    protocol = item.to_string();
+                    address = right.to_string();
+                    break;
+                }
+            }
+
+            if protocol.is_empty() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "not match protocol!").into());}
+
+            let host_port: Vec<&str>;
+            if let Some((left, right)) = address.split_once('/') {
+                host_port = left.split(':').collect::<Vec<&str>>();
+                uri = format!("/{right}");
+            }
+            else {
+                host_port = address.split(':').collect::<Vec<&str>>();
+                uri = String::from("/");
+            }
+
+            if host_port.len() == 1 {
+                host = host_port[0].to_string();
+                port = match protocol.as_str() {
+                    "http://" => String::from("80"),
+                    "https://" => String::from("443"),
+                    _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, "not match protocol!").into()),
+                };
+            }
+            else {
+                host = host_port[0].to_string();
+                port = host_port[1].to_string();
+            }
+
+
+            //println!("protocol:{protocol},host:{host},port:{port},uri:{uri}");
+
+            request.insert_header("host", host.as_str());
+            request.set_uri(uri);
+
+
+            let request_str = Self::get_request_header_string(&request);
+
+            //println!("{request_str}");
+
+            let mut stream = std::net::TcpStream::connect(format!("{host}:{port}"))?;
+            let mut ssl_stream = None;
+            let write_obj: Option<&mut (dyn Write)> = match protocol.as_ref() {
+                "https://" => {
+                    let connector = self.new_connector()?;
+                    ssl_stream = Some(connector.connect(host.as_str(), &stream)?);
+                    Some(ssl_stream.as_mut().unwrap())
+                },
+                _ => {
+                    Some(&mut stream)
+                }
+            };
+
+            let write_obj = write_obj.unwrap();
+
+            write_obj.write_all(request_str.as_bytes())?;
+            write_obj.write_all(request.get_body())?;
+
+            let read_obj: &mut (dyn Read) = match protocol.as_ref() {
+                "https://" => ssl_stream.as_mut().unwrap(),
+                _ => { 
+                    drop(ssl_stream);
+                    &mut stream
+                }
+            };
+
+            let mut buffer = [0u8; 1024];
+            let mut response_reader = HttpResponseReader::default();
+
+            while !response_reader.is_finished() {
+                let buffer_size = read_obj.read(&mut buffer)?;
+                response_reader.read(buffer[0..buffer_size].to_vec())?;
+            }
+
+            let res = response_reader.get_response()?;
+
+            Ok(res)
+        }
+    }
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_val() {
+        let mut json = web::Json::new(crate::web::JsonType::Object(Default::default()));
+
+        json.set_val("asd", web::Json::new(crate::web::JsonType::i64(123)));
+
+        assert_eq!(i64::from(json.get_val("asd").unwrap()), 123);
+    }
+
+    #[test]
+    fn get_null_val() {
+        let json = web::Json::new(crate::web::JsonType::Null);
+
+        assert!(json.get_val("asd").is_none()); 
+    }
+
+    #[test]
+    fn parse_json() {
+        let json_str = " { \"a\": 123, \"fgfgfg\": 444.2, \"complex\": { \"son\": 123}, \"c\": \"aaad\" } ";
+        let json = web::Json::parse(json_str).unwrap();
+
+        println!("test_display:\n{}", json);
+
+        assert_eq!(i64::from(json.get_val("a").unwrap()), 123);
+    }
+
+    #[test]
+    fn form_data() {
+        let temp = "w=%E4%B8%AD%E6%96%87test%F0%9F%92%96&foo=bar&lalala=123&ids%5B%5D=1&ids%5B%5D=2&ids%5B%5D=3";
+        //let temp = "ids%5B%5D=1&ids%5B%5D=2";
+        println!("string:\n{}", temp);
+
+        let json = web::Json::parse_form_data(temp).unwrap();
+
+        //println!("decode:{}", web::urldecode(json.to_string().as_str()).unwrap()); 
+
+        assert_eq!("中文test💖", String::from(json.get_val("w").unwrap()));
+        assert_eq!("bar", String::from(json.get_val("foo").unwrap()));
+        assert_eq!("123", String::from(json.get_val("lalala").unwrap()));
+
+        match json.get_val("ids").unwrap() {
+            web::JsonType::Vec(arr) => {
+                assert_eq!("1", String::from(arr[0].get()));
+                assert_eq!("2", String::from(arr[1].get()));
+                assert_eq!("3", String::from(arr[2].get()));
+            },
+            _ => { assert!(false, "wrong type")} 
+        }
+
+
+        println!("form_data:\n{}", json);
+    }
+
+    #[test]
+    fn null_json() {
+        let json_str = "null";
+
+        let json = web::Json::parse(json_str).unwrap();
+        
+        println!("test_display:\n{}", json);
+        assert_eq!("null", String::from(&*json.get()));
+    }
+
+	#[test]
+	fn array_json_number() {
+		let json_str = "[  703,26,322]";
+
+		let json = web::Json::parse(json_str).unwrap();
+
+		assert_eq!(web::JsonType::i64(703), *json.index(0).unwrap().get());
+		assert_eq!(web::JsonType::i64(26), *json.index(1).unwrap().get());
+		assert_eq!(web::JsonType::i64(322), *json.index(2).unwrap().get());
+	}
+
+	#[test]
+	fn array_json_decimal() {
+		let json_str = "[7.4,20.3,3.6]";
+
+		let json = web::Json::parse(json_str).unwrap();
+
+		assert_eq!(web::JsonType::f64(7.4), *json.index(0).unwrap().get());
+		assert_eq!(web::JsonType::f64(20.3), *json.index(1).unwrap().get());
+		assert_eq!(web::JsonType::f64(3.6), *json.index(2).unwrap().get());
+	}
+
+    #[test]
+    fn array_json_string() {
+		let json_str = "[\"first\", \"second\", \"第三个\"]";
+
+        let json = web::Json::parse(json_str).unwrap();
+
+        println!("{}", json);
+		assert_eq!(web::JsonType::String("first".into()), *json.index(0).unwrap().get());
+		assert_eq!(web::JsonType::String("second".into()), *json.index(1).unwrap().get());
+		assert_eq!(web::JsonType::String("第三个".into()), *json.index(2).unwrap().get());
+    }
+
+    #[test]
+    fn parse_complex_json_1() {
+        let json_str = " { \"a\": 123, \"c\": \"a\\\"ha\\\"aad\", \"fgfgfg\": 444.2, \"complex\": { \"son\": 123}, \"zzz\": null} ";
+        let json = web::Json::parse(json_str).unwrap();
+
+        println!("test_display:\n{}", json);
+
+        assert_eq!(String::from(&*json.get_val("c").unwrap()), "a\"ha\"aad");
+    }
+
+    #[test]
+    fn parse_complex_json_2() {
+		let json_str = "[{\"a\": 123, \"bb\": \"abc\"}, {\"a\": 456}, {\"a\": 789}]";
+
+        let json = web::Json::parse(json_str).unwrap();
+
+        assert_eq!(web::JsonType::i64(123), *json.index(0).unwrap().get_val("a").unwrap());
+        assert_eq!(web::JsonType::String("abc".into()), *json.index(0).unwrap().get_val("bb").unwrap());
+
+        assert_eq!(web::JsonType::i64(456), *json.index(1).unwrap().get_val("a").unwrap());
+
+        assert_eq!(web::JsonType::i64(789), *json.index(2).unwrap().get_val("a").unwrap());
+    }
+
+    #[test]
+    fn parse_complex_json_3() {
+        let json_str = "{\"www\": \"中文♥\", \"lalala\": [1, 2, 3]}";
+
+        let json = web::Json::parse(json_str).unwrap();
+
+        assert_eq!(web::JsonType::String("中文♥".into()), *json.get_val("www").unwrap());
+    }
+
+    #[test]
+    fn parse_literals_and_numbers() {
+        let json = web::Json::parse("[true, false, -12, -0.5, 1e3, 2.5E-1, 100000000000000000000]").unwrap();
+
+        assert_eq!(web::JsonType::Bool(true), *json.index(0).unwrap().get());
+        assert_eq!(web::JsonType::Bool(false), *json.index(1).unwrap().get());
+        assert_eq!(web::JsonType::i64(-12), *json.index(2).unwrap().get());
+        assert_eq!(web::JsonType::f64(-0.5), *json.index(3).unwrap().get());
+        assert_eq!(web::JsonType::f64(1000.0), *json.index(4).unwrap().get());
+        assert_eq!(web::JsonType::f64(0.25), *json.index(5).unwrap().get());
+        assert_eq!(web::JsonType::f64(1e20), *json.index(6).unwrap().get());
+    }
+
+    #[test]
+    fn parse_unicode_escapes() {
+        let json = web::Json::parse(r#"["\u4e2d\u6587", "\ud83d\udc96", "a/b"]"#).unwrap();
+
+        assert_eq!(web::JsonType::String("中文".into()), *json.index(0).unwrap().get());
+        assert_eq!(web::JsonType::String("💖".into()), *json.index(1).unwrap().get());
+        assert_eq!(web::JsonType::String("a/b".into()), *json.index(2).unwrap().get());
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let err = web::Json::parse("{\n  \"a\": nul\n}").unwrap_err();
+        assert!(err.to_string().contains("unexpected character '\\n' at line 2 column 11"), "{}", err);
+
+        let err = web::Json::parse("[1, 2] 3").unwrap_err();
+        assert!(err.to_string().contains("unexpected trailing characters at line 1 column 8"), "{}", err);
+
+        let err = web::Json::parse("[1, 2").unwrap_err();
+        assert!(err.to_string().contains("unexpected end of input at line 1 column 6"), "{}", err);
+    }
+
+    #[test]
+    fn serialize_escapes() {
+        let json = web::Json::from("quote\" slash\\ /\n\r\t\u{8}\u{c}\u{1}\u{1f} 中文💖");
+        assert_eq!(json.to_string(), "\"quote\\\" slash\\\\ /\\n\\r\\t\\b\\f\\u0001\\u001f 中文💖\"");
+
+        let json = web::Json::new(web::JsonType::Vec(vec![web::Json::from("it's"), web::Json::from(1.5), web::Json::from(true), web::Json::new(web::JsonType::Null)]));
+        assert_eq!(json.to_string(), "[\"it's\",1.5,true,null]");
+
+        let text = json.to_string();
+        assert_eq!(web::Json::parse(&text).unwrap(), json);
+    }
+
+    #[test]
+    fn serialize_ascii_only() {
+        let json = web::Json::new(web::JsonType::from([("键".to_string(), web::Json::from("é💖"))]));
+        let text = json.to_string_with(web::JsonFormat::new().ascii_only(true)).unwrap();
+
+        assert_eq!(text, "{\"\\u952e\":\"\\u00e9\\ud83d\\udc96\"}");
+        assert_eq!(web::Json::parse(&text).unwrap(), json);
+    }
+
+    #[test]
+    fn serialize_non_finite() {
+        let json = web::Json::new(web::JsonType::Vec(vec![web::Json::from(f64::NAN), web::Json::from(f64::INFINITY), web::Json::from(-0.5)]));
+        assert_eq!(json.to_string(), "[null,null,-0.5]");
+
+        let err = json.to_string_with(web::JsonFormat::new().non_finite(web::NonFiniteFloat::Error)).unwrap_err();
+        assert!(err.to_string().contains("can not serialize NaN"), "{}", err);
+    }
+
+    #[test]
+    fn serialize_pretty() {
+        let json = web::Json::parse("{\"a\": [1, {\"b\": null}, [], {}]}").unwrap();
+        assert_eq!(json.to_pretty_string(), "{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    },\n    [],\n    {}\n  ]\n}");
+
+        let text = json.to_string_with(web::JsonFormat::pretty(0).indent("\t")).unwrap();
+        assert_eq!(text, "{\n\t\"a\": [\n\t\t1,\n\t\t{\n\t\t\t\"b\": null\n\t\t},\n\t\t[],\n\t\t{}\n\t]\n}");
+    }
+}
+
+#[cfg(test)]
+mod json_convert_tests {
+    use super::*;
+    use web::{ToJson, FromJson};
+
+    #[derive(Debug, PartialEq, web::ToJson, web::FromJson)]
+    enum Role {
+        Admin,
+        #[json(rename = "guest")]
+        Visitor,
+    }
+
+    #[derive(Debug, PartialEq, Default, web::ToJson, web::FromJson)]
+    struct Meta {
+        created: i64,
+        tags: Vec<String>,
+    }
+
+    fn default_age() -> u8 { 18 }
+
+    #[derive(Debug, PartialEq, web::ToJson, web::FromJson)]
+    struct User {
+        #[json(rename = "userName")]
+        name: String,
+        #[json(default = "default_age")]
+        age: u8,
+        email: Option<String>,
+        role: Role,
+        #[json(skip)]
+        password: String,
+        #[json(flatten)]
+        meta: Meta,
+        scores: std::collections::HashMap<String, f64>,
+        position: (i32, i32),
+    }
+
+    #[derive(Debug, PartialEq, web::ToJson, web::FromJson)]
+    struct Id(u32);
+
+    #[derive(Debug, PartialEq, web::ToJson)]
+    struct Extra {
+        id: u32,
+        #[json(flatten)]
+        meta: Option<Meta>,
+        #[json(flatten)]
+        tags: std::collections::HashMap<String, bool>,
+    }
+
+    #[derive(Debug, PartialEq, web::ToJson, web::FromJson)]
+    struct Profile {
+        id: u32,
+        #[json(flatten)]
+        meta: Option<Meta>,
+    }
+
+    #[derive(Debug, PartialEq, web::ToJson, web::FromJson)]
+    struct Page<T> {
+        items: Vec<T>,
+        #[json(default)]
+        next: Option<Id>,
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(42u8.to_json(), web::Json::from(42));
+        assert_eq!(u64::MAX.to_json(), web::Json::from(u64::MAX as f64));
+        assert_eq!(Some("a").to_json(), web::Json::from("a"));
+        assert_eq!(None::<i32>.to_json(), web::Json::new(web::JsonType::Null));
+        assert_eq!((1, "b", true).to_json().to_string(), "[1,\"b\",true]");
+
+        let json = web::Json::parse("[1, 2.5, null]").unwrap();
+        assert_eq!(json.decode::<(u8, f32, Option<bool>)>().unwrap(), (1, 2.5, None));
+        assert_eq!(web::Json::parse("1e3").unwrap().decode::<u16>().unwrap(), 1000);
+
+        let err = web::Json::parse("300").unwrap().decode::<u8>().unwrap_err();
+        assert_eq!(err.to_string(), "number out of range for u8 at $");
+
+        //超出 i64 的整数按 f64 保存, 2^63 与 2^64 不能被截断成 MAX
+        assert!(web::Json::parse("9223372036854775808").unwrap().decode::<i64>().is_err());
+        assert!(web::Json::parse("18446744073709551616").unwrap().decode::<u64>().is_err());
+        assert_eq!(web::Json::parse("-9223372036854775808").unwrap().decode::<i64>().unwrap(), i64::MIN);
+        assert_eq!(web::Json::parse("9223372036854775808").unwrap().decode::<u64>().unwrap(), 1u64 << 63);
+        assert_eq!(web::Json::parse("18446744073709549568").unwrap().decode::<u64>().unwrap(), 18446744073709549568);
+        assert!(web::Json::parse("256.0").unwrap().decode::<u8>().is_err());
+        assert_eq!(web::Json::parse("255.0").unwrap().decode::<u8>().unwrap(), 255);
+
+        let err = web::Json::parse("[1, \"x\"]").unwrap().decode::<Vec<i64>>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid type: expected i64, found string at $[1]");
+
+        let err = web::Json::parse("[1]").unwrap().decode::<(i64, i64)>().unwrap_err();
+        assert_eq!(err.get_message(), "invalid length: expected array of 2, found 1");
+    }
+
+    #[test]
+    fn flatten_objects() {
+        let extra = Extra { id: 1, meta: None, tags: [("new".to_string(), true)].into_iter().collect() };
+        assert_eq!(extra.to_json(), web::Json::parse(r#"{"id": 1, "new": true}"#).unwrap());
+
+        let extra = Extra { meta: Some(Meta { created: 5, tags: vec![] }), ..extra };
+        assert_eq!(extra.to_json(), web::Json::parse(r#"{"id": 1, "new": true, "created": 5, "tags": []}"#).unwrap());
+
+        //展开的 Option 在内层字段都不存在时为 None, 只有一部分时仍然报缺少字段
+        assert_eq!(<Profile as web::FromJson>::field_names(), Some(vec!["id", "created", "tags"]));
+        assert_eq!(web::Json::parse(r#"{"id": 1}"#).unwrap().decode::<Profile>().unwrap(), Profile { id: 1, meta: None });
+        let profile = Profile { id: 1, meta: Some(Meta { created: 5, tags: vec!["a".into()] }) };
+        assert_eq!(web::Json::parse(profile.to_json().to_string()).unwrap().decode::<Profile>().unwrap(), profile);
+        let err = web::Json::parse(r#"{"id": 1, "created": 5}"#).unwrap().decode::<Profile>().unwrap_err();
+        assert_eq!(err.to_string(), "missing field at $.tags");
+    }
+
+    #[test]
+    fn derive_round_trip() {
+        let user = User {
+            name: "tom".into(),
+            age: 30,
+            email: None,
+            role: Role::Visitor,
+            password: "secret".into(),
+            meta: Meta { created: 1700000000, tags: vec!["a".into()] },
+            scores: std::collections::HashMap::from([("math".to_string(), 99.5)]),
+            position: (3, -4),
+        };
+
+        let json = user.to_json();
+        assert_eq!(json.get_val("userName"), Some(&web::JsonType::String("tom".into())));
+        assert_eq!(json.get_val("role"), Some(&web::JsonType::String("guest".into())));
+        assert_eq!(json.get_val("created"), Some(&web::JsonType::i64(1700000000)));
+        assert_eq!(json.get_val("email"), Some(&web::JsonType::Null));
+        assert!(json.get_val("password").is_none());
+        assert!(json.get_val("meta").is_none());
+
+        let decoded = web::Json::parse(json.to_string()).unwrap().decode::<User>().unwrap();
+        assert_eq!(decoded, User { password: String::new(), ..user });
+    }
+
+    #[test]
+    fn derive_defaults_and_errors() {
+        let json = web::Json::parse("{\"userName\": \"amy\", \"role\": \"Admin\", \"created\": 1, \"tags\": [], \"scores\": {}, \"position\": [0, 0]}").unwrap();
+        let user = User::from_json(&json).unwrap();
+        assert_eq!(user.age, 18);
+        assert_eq!(user.email, None);
+        assert_eq!(user.role, Role::Admin);
+
+        let json = web::Json::parse("{\"items\": [1, 2]}").unwrap();
+        assert_eq!(json.decode::<Page<Id>>().unwrap(), Page { items: vec![Id(1), Id(2)], next: None });
+
+        let json = web::Json::parse("{\"items\": [{\"items\": [1, \"2\"]}]}").unwrap();
+        let err = json.decode::<Page<Page<Id>>>().unwrap_err();
+        assert_eq!(err.get_path(), "$.items[0].items[1]");
+
+        let json = web::Json::parse("{\"userName\": \"amy\", \"role\": \"root\"}").unwrap();
+        let err = json.decode::<User>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown variant `root`, expected one of `Admin`, `guest` at $.role");
+
+        let json = web::Json::parse("{\"userName\": \"amy\", \"role\": \"guest\", \"tags\": []}").unwrap();
+        let err = json.decode::<User>().unwrap_err();
+        assert_eq!(err.to_string(), "missing field at $.created");
+
+        let json = web::Json::parse("{\"a b\": \"x\"}").unwrap();
+        let err = json.decode::<std::collections::HashMap<String, i64>>().unwrap_err();
+        assert_eq!(err.get_path(), "$[\"a b\"]");
+    }
+}
+
+#[cfg(test)]
+mod urldecode {
+    use super::*;
+
+	#[test]
+	fn test_decode() {
+		let code = "name=123&aaa=444&www=%E4%B8%AD%E6%96%87test%F0%9F%92%96";
+		
+		let result = web::urldecode(code);
+		assert_eq!("name=123&aaa=444&www=中文test💖", result.unwrap());
+	}
+}
+
+#[cfg(test)]
+mod test_util {
+    use super::*;
+
+    //在测试里直接调用 Router, 不经过网络
+    pub fn call(router: &web::Router, method: &str, uri: &str) -> web::HttpResponse {
+        send(router, method, uri, &[], "")
+    }
+
+    pub fn send(router: &web::Router, method: &str, uri: &str, headers: &[(&str, &str)], body: &str) -> web::HttpResponse {
+        let mut request = web::HttpRequest::default();
+        request.set_method(method);
+        request.set_uri(uri);
+        request.set_body(body.as_bytes().to_vec());
+        for (key, val) in headers {
+            request.insert_header(*key, *val);
+        }
+
+        async_std::task::block_on(router.call(&request, Some("127.0.0.1:5000".parse().unwrap()))).unwrap()
+    }
+
+    pub fn text(response: &web::HttpResponse) -> String {
+        String::from_utf8(response.get_body().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod router_tests {
+    use super::*;
+    use test_util::call;
+
+    fn test_response(param: web::Json) -> web::HttpResponse {
+        println!("test_response!!!param:{}", param);
+
+        return web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+    }
+
+    #[test]
+    fn call_back_register() {
+        let mut router = web::Router::new(); 
+
+        println!("add:{:p}", test_response as fn(_) -> _);
+        router.register_url("GET".to_string(), "asd".to_string(), &test_response);
+
+        let mut request = web::HttpRequest::default();
+        request.set_method("GET");
+        request.set_uri("asd");
+        request.set_body("{\"a\": 123123}".as_bytes().to_vec());
+        request.insert_header("content-length", request.get_body().len().to_string());
+
+        async_std::task::block_on(router.call(&request, None)).unwrap();
+    }
+
+    #[test]
+    fn contains_uri() {
+        let mut router = web::Router::new();
+
+        router.register_url("POST", "/asd", &test_response);
+        assert_eq!(true, router.contains_url("POST", "/asd"));
+        assert_eq!(false, router.contains_url("GET", "/asd"));
+        assert_eq!(false, router.contains_url("POST", "asd"));
+    }
+
+    fn param_response(_param: web::Json, params: &web::PathParams) -> web::HttpResponse {
+        let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+        response.set_body(params.iter().map(|(key, val)| format!("{key}={val};")).collect::<String>().into_bytes());
+
+        return response;
+    }
+
+    #[test]
+    fn path_params() {
+        let mut router = web::Router::new();
+
+        router.register_url_with_params("GET", "/users/:id", param_response);
+        router.register_url_with_params("GET", "/users/:id/posts/:post_id", param_response);
+        router.register_url_with_params("GET", "/users/me", param_response);
+
+        assert_eq!(b"id=42;".to_vec(), *call(&router, "GET", "/users/42").get_body());
+        assert_eq!(b"id=42;post_id=7;".to_vec(), *call(&router, "GET", "/users/42/posts/7").get_body());
+        assert_eq!(b"".to_vec(), *call(&router, "GET", "/users/me").get_body());
+        assert_eq!(false, router.contains_url("GET", "/users/"));
+        assert_eq!(false, router.contains_url("GET", "/users/42/posts"));
+    }
+
+    #[test]
+    fn wildcard_and_optional() {
+        let mut router = web::Router::new();
+
+        router.register_url_with_params("GET", "/files/*rest", param_response);
+        router.register_url_with_params("GET", "/files/readme", param_response);
+        router.register_url_with_params("GET", "/posts/:page?", param_response);
+
+        assert_eq!(b"rest=a/b/c.txt;".to_vec(), *call(&router, "GET", "/files/a/b/c.txt").get_body());
+        assert_eq!(b"rest=;".to_vec(), *call(&router, "GET", "/files").get_body());
+        assert_eq!(b"".to_vec(), *call(&router, "GET", "/files/readme").get_body());
+        assert_eq!(b"page=3;".to_vec(), *call(&router, "GET", "/posts/3").get_body());
+        assert_eq!(b"".to_vec(), *call(&router, "GET", "/posts").get_body());
+    }
+
+    #[test]
+    fn encoded_segments() {
+        let mut router = web::Router::new();
+
+        router.register_url_with_params("GET", "/users/:id", param_response);
+        router.register_url_with_params("GET", "/café/:name", param_response);
+        router.register_url_with_params("GET", "/files/*rest", param_response);
+        router.register("GET", "/names/:name", |web::Path(name): web::Path<String>| web::HttpResponse::json(web::Json::from(name)));
+
+        assert_eq!(b"id=John Doe;".to_vec(), *call(&router, "GET", "/users/John%20Doe").get_body());
+        assert_eq!(b"name=x;".to_vec(), *call(&router, "GET", "/caf%C3%A9/x").get_body());
+        assert_eq!(b"\"a b\"".to_vec(), *call(&router, "GET", "/names/a%20b").get_body());
+        assert!(router.contains_url("GET", "/caf%c3%a9/x"));
+
+        //%2F 解码后仍在同一段里
+        assert_eq!(b"id=a/b;".to_vec(), *call(&router, "GET", "/users/a%2Fb").get_body());
+        assert_eq!(b"rest=a/b%/c;".to_vec(), *call(&router, "GET", "/files/a%2Fb%25/c").get_body());
+
+        for uri in ["/users/%zz", "/users/%+f", "/users/%C3", "/users/abc%"] {
+            assert_eq!(web::HttpResponseStatusCode::BadRequest, call(&router, "GET", uri).get_status_code(), "{uri}");
+        }
+    }
+
+    fn fallible_response(ctx: web::RequestContext) -> Result<web::HttpResponse, web::HttpError> {
+        let id = ctx.get_param("id").unwrap().parse::<i64>()?;
+        if id == 0 {
+            return Err(web::HttpError::new(web::HttpResponseStatusCode::NotFound, "no such user"));
+        }
+
+        Ok(web::HttpResponse::json(web::Json::from(id)))
+    }
+
+    #[test]
+    fn handler_errors() {
+        let mut router = web::Router::new();
+
+        router.register_handler("GET", "/users/:id", fallible_response);
+        assert_eq!(b"42".to_vec(), *call(&router, "GET", "/users/42").get_body());
+
+        let response = call(&router, "GET", "/users/0");
+        assert_eq!(web::HttpResponseStatusCode::NotFound.as_u16(), response.get_status_code().as_u16());
+        assert_eq!(b"no such user".to_vec(), *response.get_body());
+
+        assert_eq!(web::HttpResponseStatusCode::InternalServerError.as_u16(), call(&router, "GET", "/users/abc").get_status_code().as_u16());
+
+        router.set_error_handler(|err| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::BadRequest);
+            response.set_body(err.get_message().as_bytes().to_vec());
+            response
+        });
+        assert_eq!(web::HttpResponseStatusCode::BadRequest.as_u16(), call(&router, "GET", "/users/abc").get_status_code().as_u16());
+    }
 
-            loop {
-                match wrap_stream {
-                    HttpStream::TCP(ref stream) => println!("ip:{} handle_accept...", stream.peer_addr()?),
-                    HttpStream::SSL(ref stream) => println!("ip:{} handle_accept...", stream.get_ref().peer_addr()?),
-                }
-                let request_res = Self::handle_accept(&mut wrap_stream).await;
+    async fn async_response(ctx: web::RequestContext) -> Result<web::HttpResponse, web::HttpError> {
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
 
-                match request_res {
-                    Err(e) => {
-                        if e.err_desc == "socket close" {
-                            return Ok(());
-                        }
-                        else {
-                            return Err(e);
-                        }
-                    },
-                    Ok(request) => {
-                        let mut response = Self::handle_request(&router, &request)?;
+        let name = ctx.get_param("name").ok_or(web::HttpError::new(web::HttpResponseStatusCode::BadRequest, "no name"))?;
+        Ok(web::HttpResponse::json(web::Json::from(name)))
+    }
 
-                        //println!("{:#?}", request);
+    #[test]
+    fn async_handler() {
+        let mut router = web::Router::new();
 
-                        if request.get_headers().get("accept-encoding").unwrap_or(&String::new()).split(",").find(|&item| item == "gzip").is_some() {
-                            //println!("boy use gzip");
-                            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
-                            encoder.write_all(response.get_body())?;
-                            response.set_body(encoder.finish()?);
-                            response.insert_header("content-encoding", "gzip");
-                        }
+        router.register_async("GET", "/hello/:name?", async_response);
+        assert_eq!(b"\"world\"".to_vec(), *call(&router, "GET", "/hello/world").get_body());
+        assert_eq!(web::HttpResponseStatusCode::BadRequest.as_u16(), call(&router, "GET", "/hello").get_status_code().as_u16());
+    }
+}
 
-                        response.insert_header("connection", "close");
-                        //response.insert_header("connection", "keep-alive");
-                        //response.insert_header("keep-alive", "timeout=5");
+#[cfg(test)]
+mod route_macro_tests {
+    use super::*;
+    use test_util::call;
+    use web::route;
 
-                        Self::send_response(&mut wrap_stream, &response).await?;
-                    }
-                }
-                //暂时不支持长链接
-                break;
-            }
+    #[web::route_module]
+    mod api {
+        use crate::web::{self, route};
 
-            Ok(())
+        #[route(GET, "/users/:id")]
+        pub fn get_user(ctx: web::RequestContext) -> web::HttpResponse {
+            web::HttpResponse::json(web::Json::from(ctx.get_param("id").unwrap_or_default()))
         }
 
-        pub async fn new(ip_addr: &str) -> Result<Self, BacktraceError> {
-            let bind_res = std::net::TcpListener::bind(ip_addr); 
-            match bind_res {
-                Ok(val) => { 
-                    Ok(
-                        Self {
-                            socket: val,
-                            router: std::sync::Arc::new(Router::new()),
-                            use_ssl: false,
-                        }
-                    )
-                },
-                Err(e) => Err(e.into()),
-            }
+        #[route(POST, PUT, "/users")]
+        pub fn save_user(json: web::Json) -> web::HttpResponse {
+            web::HttpResponse::json(json)
         }
 
-        pub fn get_router(&mut self) -> &mut std::sync::Arc<Router> {
-            return &mut self.router;
+        #[route(GET, "/files/*path")]
+        pub async fn get_file(ctx: web::RequestContext) -> Result<web::HttpResponse, web::HttpError> {
+            Err(web::HttpError::new(web::HttpResponseStatusCode::NotFound, ctx.get_param("path").unwrap_or_default()))
         }
 
-        pub fn use_ssl(&mut self, is_use: bool) -> &mut Self{
-            self.use_ssl = is_use;
-            return self;
+        #[route(GET, "/users/:id/profile")]
+        pub async fn get_profile(web::Path(id): web::Path<u32>, web::Query(query): web::Query<std::collections::HashMap<String, Vec<String>>>) -> web::HttpResponse {
+            web::HttpResponse::json(web::Json::from(format!("{id}:{}", query["f"].join(","))))
         }
 
-        pub async fn listen(&self) -> Result<(), BacktraceError> { 
-            println!("incoming...");
-            println!("{:?}", self.router.routes.get("POST").unwrap().keys());
-            for stream_res in self.socket.incoming() {			
-                let router_copy = std::sync::Arc::clone(&self.router);
-                let use_ssl = self.use_ssl;
-                match stream_res {
-                   Ok(stream) => {
-                        stream.set_read_timeout(Some(std::time::Duration::from_millis(5000)))?;
-                        stream.set_write_timeout(Some(std::time::Duration::from_millis(5000)))?;
+        pub fn helper() {}
+    }
 
-						let _handle = async_std::task::spawn(async move {
-                        	if let Err(e) = Self::accept_process(use_ssl, router_copy, stream).await {
-                                if e.err_desc != "future timed out" {
-								    println!("{}", e);
-                                }
-							}
-						});
-                   },
-                   Err(e) => {
-                        return Err(e.into());
-                   }
-                }
-            }
+    #[route(GET, "/")]
+    fn index(_json: web::Json, _params: &web::PathParams) -> web::HttpResponse {
+        web::HttpResponse::new(web::HttpResponseStatusCode::OK)
+    }
 
-            Ok(())
-        }
+    #[test]
+    fn register_module() {
+        let mut router = web::Router::new();
+        router.register_routes(api::routes());
+
+        assert_eq!(b"\"7\"".to_vec(), *call(&router, "GET", "/users/7").get_body());
+        assert_eq!(b"\"7:a,b\"".to_vec(), *call(&router, "GET", "/users/7/profile?f%5B%5D=a&f%5B%5D=b").get_body());
+        assert!(router.contains_url("POST", "/users"));
+        assert!(router.contains_url("PUT", "/users"));
+        assert!(!router.contains_url("GET", "/users"));
+        assert_eq!(web::HttpResponseStatusCode::NotFound.as_u16(), call(&router, "GET", "/files/a/b").get_status_code().as_u16());
+        assert_eq!(b"a/b".to_vec(), *call(&router, "GET", "/files/a/b").get_body());
+        api::helper();
     }
 
-    #[derive(Default)]
-    pub struct HttpClient {
+    #[test]
+    fn register_list() {
+        let routes = rust_web::routes![index, api::get_user];
+        assert_eq!(routes.iter().map(|route| route.get_path()).collect::<Vec<_>>(), vec!["/", "/users/:id"]);
+        assert_eq!(routes[0].get_methods(), &["GET"]);
+
+        let mut router = web::Router::new();
+        router.register_routes(routes);
+        assert_eq!(web::HttpResponseStatusCode::OK.as_u16(), call(&router, "GET", "/").get_status_code().as_u16());
+        assert!(router.contains_url("GET", "/users/1"));
     }
+}
 
-    impl HttpClient {
-        fn get_request_header_string(request: &HttpRequest) -> String {
-            format!("{method} {uri}{query_string} {version}\r\n\
-                    {headers}\
-                    \r\n",
-                    method = request.get_method(),
-                    uri = request.get_uri(), 
-                    query_string = request.get_query_string(),
-                    version = request.get_version(),
-                    headers = request.get_headers().iter().map(|(key, val)| {
-                        //let mut new_key = key.clone();
-                        //new_key.replace_range(0..1, &key.as_str()[0..1].to_uppercase());
-                        format!("{key}: {val}\r\n", )
-                    }).collect::<String>()
-            )
-        }
+#[cfg(test)]
+mod extractor_tests {
+    use super::*;
+    use test_util::{send, text};
+    use web::{Path, Query, JsonBody, Form, Header, Cookies, PeerAddr};
 
-        pub fn send<T: Into<String>>(&self, address: T, mut request: HttpRequest) -> Result<HttpResponse, BacktraceError> {
-            let mut address: String = address.into();
-            let host: String;
-            let port: String;
-            let uri: String;
-            let mut protocol = String::default();
+    #[derive(Debug, PartialEq, web::FromJson)]
+    struct UserPath {
+        group: String,
+        id: u32,
+    }
 
-            for item in ["http://", "https://"] {
-                if let Some((left, right)) = address.split_once(item) {
-                    if !left.is_empty() {panic!("")}
-                    protocol = item.to_string();
-                    address = right.to_string();
-                    break;
-                }
-            }
+    #[derive(Debug, PartialEq, web::FromJson)]
+    struct Paging {
+        page: u32,
+        #[json(default)]
+        size: Option<u32>,
+        ids: Vec<i64>,
+    }
 
-            if protocol.is_empty() { return Err(std::io::Error::new(std::io::ErrorKind::Other, "not match protocol!").into());}
+    #[derive(Debug, PartialEq, web::FromJson, web::ToJson)]
+    struct Login {
+        name: String,
+        remember: bool,
+    }
 
-            let host_port: Vec<&str>;
-            if let Some((left, right)) = address.split_once('/') {
-                host_port = left.split(':').collect::<Vec<&str>>();
-                uri = format!("/{right}");
-            }
-            else {
-                host_port = address.split(':').collect::<Vec<&str>>();
-                uri = String::from("/");
-            }
+    fn user(Path(path): Path<UserPath>, Query(paging): Query<Paging>) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(format!("{}/{} page={} size={:?} ids={:?}", path.group, path.id, paging.page, paging.size, paging.ids)))
+    }
 
-            if host_port.len() == 1 {
-                host = host_port[0].to_string();
-                port = match protocol.as_str() {
-                    "http://" => String::from("80"),
-                    "https://" => String::from("443"),
-                    _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, "not match protocol!").into()),
-                };
-            }
-            else {
-                host = host_port[0].to_string();
-                port = host_port[1].to_string();
-            }
+    fn item(Path((name, id)): Path<(String, u8)>) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(format!("{name}:{id}")))
+    }
 
+    async fn login(JsonBody(login): JsonBody<Login>, agent: Option<Header<web::UserAgent>>, PeerAddr(addr): PeerAddr) -> Result<web::HttpResponse, web::HttpError> {
+        let agent = agent.map(|Header(web::UserAgent(agent))| agent).unwrap_or_default();
+        Ok(web::HttpResponse::json(web::Json::from(format!("{} {} {} {}", login.name, login.remember, agent, addr.port()))))
+    }
 
-            //println!("protocol:{protocol},host:{host},port:{port},uri:{uri}");
+    fn form(Form(login): Form<Login>, cookies: Cookies) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(format!("{} {} {:?}", login.name, login.remember, cookies.get("session"))))
+    }
 
-            request.insert_header("host", host.as_str());
-            request.set_uri(uri);
+    fn length(Header(web::ContentLength(len)): Header<web::ContentLength>) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(len as i64))
+    }
 
+    fn auth(Header(web::Authorization(token)): Header<web::Authorization>) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(token))
+    }
 
-            let request_str = Self::get_request_header_string(&request);
+    fn router() -> web::Router {
+        let mut router = web::Router::new();
+        router.register("GET", "/users/:group/:id", user);
+        router.register("GET", "/items/:name/:id", item);
+        router.register("POST", "/login", login);
+        router.register("POST", "/form", form);
+        router.register("POST", "/length", length);
+        router.register("GET", "/auth", auth);
+        router
+    }
 
-            //println!("{request_str}");
+    #[test]
+    fn path_and_query() {
+        let router = router();
 
-            let mut stream = std::net::TcpStream::connect(format!("{host}:{port}"))?;
-            let mut ssl_stream = None;
-            let write_obj: Option<&mut (dyn Write)> = match protocol.as_ref() {
-                "https://" => {
-                    let connector = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls())?.build();
-                    ssl_stream = Some(connector.connect(host.as_str(), &stream)?);
-                    Some(ssl_stream.as_mut().unwrap())
-                },
-                _ => {
-                    Some(&mut stream)
-                }
-            };
+        let response = send(&router, "GET", "/users/admin/7?page=2&ids%5B%5D=1&ids%5B%5D=2", &[], "");
+        assert_eq!(text(&response), "\"admin/7 page=2 size=None ids=[1, 2]\"");
 
-            let write_obj = write_obj.unwrap();
+        let response = send(&router, "GET", "/users/admin/7?page=2&size=10&ids=5", &[], "");
+        assert_eq!(text(&response), "\"admin/7 page=2 size=Some(10) ids=[5]\"");
 
-            write_obj.write_all(request_str.as_bytes())?;
-            write_obj.write_all(request.get_body())?;
+        let response = send(&router, "GET", "/users/admin/x?page=1&ids=1", &[], "");
+        assert_eq!(response.get_status_code().as_u16(), 400);
+        assert!(text(&response).contains("at $.id"), "{}", text(&response));
 
-            let read_obj: &mut (dyn Read) = match protocol.as_ref() {
-                "https://" => ssl_stream.as_mut().unwrap(),
-                _ => { 
-                    drop(ssl_stream);
-                    &mut stream
-                }
-            };
+        let response = send(&router, "GET", "/users/admin/7?ids=1", &[], "");
+        assert_eq!(response.get_status_code().as_u16(), 400);
+        assert!(text(&response).contains("missing field at $.page"), "{}", text(&response));
 
-            let mut buffer = [0u8; 1024];
-            let mut response_reader = HttpResponseReader::default();
+        assert_eq!(text(&send(&router, "GET", "/items/pen/3", &[], "")), "\"pen:3\"");
+        assert_eq!(send(&router, "GET", "/items/pen/300", &[], "").get_status_code().as_u16(), 400);
+    }
 
-            while !response_reader.is_finished() {
-                let buffer_size = read_obj.read(&mut buffer)?;
-                response_reader.read(buffer[0..buffer_size].to_vec())?;
-            }
+    #[test]
+    fn json_and_form_bodies() {
+        let router = router();
 
-            let res = response_reader.get_response()?;
+        let json = [("content-type", "application/json; charset=utf-8"), ("user-agent", "curl")];
+        let response = send(&router, "POST", "/login", &json, "{\"name\": \"amy\", \"remember\": true}");
+        assert_eq!(text(&response), "\"amy true curl 5000\"");
 
-            Ok(res)
-        }
+        let response = send(&router, "POST", "/login", &json[..1], "{\"name\": \"amy\", \"remember\": 1}");
+        assert_eq!(response.get_status_code().as_u16(), 400);
+        assert!(text(&response).contains("expected boolean, found number at $.remember"), "{}", text(&response));
+
+        let response = send(&router, "POST", "/login", &json[..1], "{\"name\": ");
+        assert_eq!(response.get_status_code().as_u16(), 400);
+
+        let response = send(&router, "POST", "/login", &[("content-type", "text/plain")], "{}");
+        assert_eq!(response.get_status_code().as_u16(), 415);
+
+        let form = [("content-type", "application/x-www-form-urlencoded"), ("cookie", "theme=dark; session=\"abc\"")];
+        let response = send(&router, "POST", "/form", &form, "name=%E4%B8%AD&remember=on");
+        assert_eq!(text(&response), "\"中 true Some(\\\"abc\\\")\"");
+
+        let response = send(&router, "POST", "/form", &json[..1], "{}");
+        assert_eq!(response.get_status_code().as_u16(), 415);
+    }
+
+    #[test]
+    fn headers() {
+        let router = router();
+
+        assert_eq!(text(&send(&router, "POST", "/length", &[("content-length", "3")], "abc")), "3");
+        assert_eq!(send(&router, "POST", "/length", &[("content-length", "x")], "").get_status_code().as_u16(), 400);
+
+        assert_eq!(text(&send(&router, "GET", "/auth", &[("Authorization", "Bearer t")], "")), "\"Bearer t\"");
+        let response = send(&router, "GET", "/auth", &[], "");
+        assert_eq!(response.get_status_code().as_u16(), 400);
+        assert_eq!(text(&response), "missing header authorization");
+
+        let cookies = web::Cookies::parse("a=1; b=x=y;; c");
+        assert_eq!(cookies.iter().collect::<Vec<_>>(), vec![("a", "1"), ("b", "x=y")]);
     }
 }
 
 #[cfg(test)]
-mod json_tests {
+mod middleware_tests {
     use super::*;
+    use test_util::send;
+    use std::io::Read;
 
-    #[test]
-    fn set_and_get_val() {
-        let mut json = web::Json::new(crate::web::JsonType::Object(Default::default()));
+    fn hello() -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from("hello ".repeat(100)))
+    }
 
-        json.set_val("asd", web::Json::new(crate::web::JsonType::i64(123)));
+    //把经过的中间件名字记在响应头里
+    fn trace(name: &'static str) -> impl web::Middleware {
+        move |ctx: web::RequestContext, next: web::Next| async move {
+            let mut response = next.run(ctx).await?;
+            let trace = response.get_headers().get("x-trace").cloned().unwrap_or_default();
+            response.insert_header("x-trace", format!("{trace}{name};"));
+            Ok(response)
+        }
+    }
 
-        assert_eq!(i64::from(json.get_val("asd").unwrap()), 123);
+    fn require_token(ctx: web::RequestContext, next: web::Next) -> web::HandlerFuture {
+        match ctx.get_request().get_headers().get("authorization") {
+            Some(token) if token == "secret" => next.run(ctx),
+            _ => Box::pin(async { Err(web::HttpError::new(web::HttpResponseStatusCode::Unauthorized, "token required")) }),
+        }
     }
 
     #[test]
-    fn get_null_val() {
-        let json = web::Json::new(crate::web::JsonType::Null);
-
-        assert!(json.get_val("asd").is_none()); 
+    fn order_and_route_middleware() {
+        let mut router = web::Router::new();
+        router.add_middleware(trace("outer"));
+        router.add_middleware(trace("inner"));
+        router.register("GET", "/hello", hello);
+        router.register("GET", "/admin/:page?", hello).middleware(require_token).middleware(trace("route"));
+
+        assert_eq!(send(&router, "GET", "/hello", &[], "").get_headers()["x-trace"], "inner;outer;");
+
+        //路由中间件在全局中间件之后执行, 短路时后面的都不会执行
+        let response = send(&router, "GET", "/admin", &[], "");
+        assert_eq!(response.get_status_code().as_u16(), 401);
+        assert_eq!(*response.get_body(), b"token required".to_vec());
+
+        let response = send(&router, "GET", "/admin/2", &[("authorization", "secret")], "");
+        assert_eq!(response.get_status_code().as_u16(), 200);
+        assert_eq!(response.get_headers()["x-trace"], "route;inner;outer;");
+
+        //没有注册的路径也会经过全局中间件
+        let response = send(&router, "GET", "/not-exist.html", &[], "");
+        assert_eq!(response.get_status_code().as_u16(), 404);
+        assert_eq!(response.get_headers()["x-trace"], "inner;outer;");
     }
 
     #[test]
-    fn parse_json() {
-        let json_str = " { \"a\": 123, \"fgfgfg\": 444.2, \"complex\": { \"son\": 123}, \"c\": \"aaad\" } ";
-        let json = web::Json::parse(json_str).unwrap();
+    fn compression() {
+        let mut router = web::Router::new();
+        router.add_middleware(web::Logger);
+        router.add_middleware(web::Compression::new().level(6));
+        router.register("GET", "/hello", hello);
 
-        println!("test_display:\n{}", json);
+        let response = send(&router, "GET", "/hello", &[], "");
+        assert!(!response.get_headers().contains_key("content-encoding"));
 
-        assert_eq!(i64::from(json.get_val("a").unwrap()), 123);
-    }
+        let response = send(&router, "GET", "/hello", &[("accept-encoding", "deflate, gzip;q=0.8")], "");
+        assert_eq!(response.get_headers()["content-encoding"], "gzip");
+        assert_eq!(response.get_headers()["content-length"], response.get_body().len().to_string());
 
-    #[test]
-    fn form_data() {
-        let temp = "w=%E4%B8%AD%E6%96%87test%F0%9F%92%96&foo=bar&lalala=123&ids%5B%5D=1&ids%5B%5D=2&ids%5B%5D=3";
-        //let temp = "ids%5B%5D=1&ids%5B%5D=2";
-        println!("string:\n{}", temp);
+        let mut body = String::new();
+        flate2::read::GzDecoder::new(response.get_body().as_slice()).read_to_string(&mut body).unwrap();
+        assert_eq!(body, web::Json::from("hello ".repeat(100)).to_string());
 
-        let json = web::Json::parse_form_data(temp).unwrap();
+        //q=0 表示客户端拒绝这种编码
+        for accept_encoding in ["gzip;q=0", "gzip; q=0.000, deflate", "*;q=0", "br, *;q=0.5, gzip;q=0"] {
+            let response = send(&router, "GET", "/hello", &[("accept-encoding", accept_encoding)], "");
+            assert!(!response.get_headers().contains_key("content-encoding"), "{accept_encoding}");
+        }
+        assert_eq!(send(&router, "GET", "/hello", &[("accept-encoding", "br, *;q=0.5")], "").get_headers()["content-encoding"], "gzip");
+    }
+}
 
-        //println!("decode:{}", web::urldecode(json.to_string().as_str()).unwrap()); 
+#[cfg(test)]
+mod scope_tests {
+    use super::*;
+    use test_util::{call, text};
 
-        assert_eq!("中文test💖", String::from(json.get_val("w").unwrap()));
-        assert_eq!("bar", String::from(json.get_val("foo").unwrap()));
-        assert_eq!("123", String::from(json.get_val("lalala").unwrap()));
+    fn params(params: web::PathParams) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(params.iter().map(|(key, val)| format!("{key}={val};")).collect::<String>()))
+    }
 
-        match json.get_val("ids").unwrap() {
-            web::JsonType::Vec(arr) => {
-                assert_eq!("1", String::from(arr[0].get()));
-                assert_eq!("2", String::from(arr[1].get()));
-                assert_eq!("3", String::from(arr[2].get()));
-            },
-            _ => { assert!(false, "wrong type")} 
+    fn tag(name: &'static str) -> impl web::Middleware {
+        move |ctx: web::RequestContext, next: web::Next| async move {
+            let mut response = next.run(ctx).await?;
+            let tags = response.get_headers().get("x-tag").cloned().unwrap_or_default();
+            response.insert_header("x-tag", format!("{tags}{name};"));
+            Ok(response)
         }
+    }
 
+    //独立构建的功能模块, 不知道自己会被挂到哪里
+    fn users_router() -> web::Router {
+        let mut router = web::Router::new();
+        router.add_middleware(tag("users"));
+        router.register("GET", "/", params);
+        router.register("GET", "/:id", params);
+        router.register("GET", "/:id/files/*path", params);
+        router
+    }
 
-        println!("form_data:\n{}", json);
+    #[test]
+    fn scopes_and_mount() {
+        let mut router = web::Router::new();
+        router.add_middleware(tag("global"));
+        router.register("GET", "/health", params);
+        router.scope("/api/v1/", |api| {
+            api.add_middleware(tag("api"));
+            api.register("GET", "/version", params).middleware(tag("route"));
+            api.scope("/orgs/:org", |org| {
+                org.mount("/users", users_router());
+            });
+        });
+
+        assert_eq!(call(&router, "GET", "/health").get_headers()["x-tag"], "global;");
+        assert!(!router.contains_url("GET", "/version"));
+
+        let response = call(&router, "GET", "/api/v1/version");
+        assert_eq!(response.get_headers()["x-tag"], "route;api;global;");
+
+        let response = call(&router, "GET", "/api/v1/orgs/acme/users/7");
+        assert_eq!(text(&response), "\"org=acme;id=7;\"");
+        assert_eq!(response.get_headers()["x-tag"], "users;api;global;");
+
+        assert_eq!(text(&call(&router, "GET", "/api/v1/orgs/acme/users/7/files/a/b.txt")), "\"org=acme;id=7;path=a/b.txt;\"");
+        assert_eq!(text(&call(&router, "GET", "/api/v1/orgs/acme/users")), "\"org=acme;\"");
+        assert_eq!(text(&call(&router, "GET", "/api/v1/orgs/acme/users/")), "\"org=acme;\"");
     }
 
     #[test]
-    fn null_json() {
-        let json_str = "null";
+    fn mount_at_root() {
+        let mut router = web::Router::new();
+        router.mount("/", users_router());
 
-        let json = web::Json::parse(json_str).unwrap();
-        
-        println!("test_display:\n{}", json);
-        assert_eq!("null", String::from(&*json.get()));
+        assert_eq!(text(&call(&router, "GET", "/3")), "\"id=3;\"");
+        assert_eq!(call(&router, "GET", "/").get_headers()["x-tag"], "users;");
     }
 
-	#[test]
-	fn array_json_number() {
-		let json_str = "[  703,26,322]";
+    fn labeled(label: &'static str, status_code: web::HttpResponseStatusCode) -> web::HttpResponse {
+        let mut response = web::HttpResponse::new(status_code);
+        response.set_body(label.into());
+        response
+    }
 
-		let json = web::Json::parse(json_str).unwrap();
+    #[test]
+    fn mount_handlers() {
+        let mut api = web::Router::new();
+        api.register("GET", "/fail", || Err::<web::HttpResponse, _>(web::HttpError::new(web::HttpResponseStatusCode::Forbidden, "denied")));
+        api.register("POST", "/upload", |_: web::Multipart| labeled("uploaded", web::HttpResponseStatusCode::OK));
+        api.set_error_handler(|err| labeled("api error", err.get_status_code()));
+        api.set_not_found_handler(|_| labeled("api not found", web::HttpResponseStatusCode::NotFound));
+        api.set_multipart_limits(*web::MultipartLimits::new().max_parts(1));
 
-		assert_eq!(web::JsonType::i64(703), *json.index(0).unwrap().get());
-		assert_eq!(web::JsonType::i64(26), *json.index(1).unwrap().get());
-		assert_eq!(web::JsonType::i64(322), *json.index(2).unwrap().get());
-	}
+        let mut router = web::Router::new();
+        router.register("GET", "/fail", || Err::<web::HttpResponse, _>(web::HttpError::new(web::HttpResponseStatusCode::Forbidden, "denied")));
+        router.set_error_handler(|err| labeled("root error", err.get_status_code()));
+        router.set_not_found_handler(|_| labeled("root not found", web::HttpResponseStatusCode::NotFound));
+        router.set_method_not_allowed_handler(|_, _| labeled("root 405", web::HttpResponseStatusCode::MethodNotAllowed));
+        router.set_static_files(None);
+        router.mount("/api", api);
+
+        assert_eq!(text(&call(&router, "GET", "/fail")), "root error");
+        assert_eq!(text(&call(&router, "GET", "/api/fail")), "api error");
+        assert_eq!(call(&router, "GET", "/api/fail").get_status_code(), web::HttpResponseStatusCode::Forbidden);
+
+        assert_eq!(text(&call(&router, "GET", "/nothing")), "root not found");
+        assert_eq!(text(&call(&router, "GET", "/api/nothing")), "api not found");
+        assert_eq!(text(&call(&router, "GET", "/apis")), "root not found");
+        //子 Router 没有设置 405 处理时用外层的
+        assert_eq!(text(&call(&router, "DELETE", "/api/fail")), "root 405");
 
-	#[test]
-	fn array_json_decimal() {
-		let json_str = "[7.4,20.3,3.6]";
+        let mut request = web::HttpRequest::default();
+        request.set_method("POST");
+        request.set_uri("/api/upload");
+        request.insert_header("content-type", "multipart/form-data; boundary=b");
+        request.set_body(b"--b\r\ncontent-disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b\r\ncontent-disposition: form-data; name=\"b\"\r\n\r\n2\r\n--b--\r\n".to_vec());
+        let response = async_std::task::block_on(router.call(&request, None)).unwrap();
+        assert_eq!(response.get_status_code(), web::HttpResponseStatusCode::ContentTooLarge);
+        assert_eq!(text(&response), "api error");
+    }
+}
+
+#[cfg(test)]
+mod dispatch_tests {
+    use super::*;
+    use test_util::call;
 
-		let json = web::Json::parse(json_str).unwrap();
+    fn hello() -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from("hello"))
+    }
 
-		assert_eq!(web::JsonType::f64(7.4), *json.index(0).unwrap().get());
-		assert_eq!(web::JsonType::f64(20.3), *json.index(1).unwrap().get());
-		assert_eq!(web::JsonType::f64(3.6), *json.index(2).unwrap().get());
-	}
+    fn router() -> web::Router {
+        let mut router = web::Router::new();
+        router.register("GET", "/users/:id", hello);
+        router.register("DELETE", "/users/:id", hello);
+        router.register("POST", "/upload/*path", hello);
+        router
+    }
 
     #[test]
-    fn array_json_string() {
-		let json_str = "[\"first\", \"second\", \"第三个\"]";
+    fn method_not_allowed() {
+        let router = router();
+        assert_eq!(router.allowed_methods("/users/1"), ["DELETE", "GET", "HEAD", "OPTIONS"]);
+        assert_eq!(router.allowed_methods("/upload/a/b"), ["OPTIONS", "POST"]);
+        assert!(router.allowed_methods("/nothing").is_empty());
+
+        let response = call(&router, "POST", "/users/1");
+        assert_eq!(response.get_status_code(), web::HttpResponseStatusCode::MethodNotAllowed);
+        assert_eq!(response.get_headers()["allow"], "DELETE, GET, HEAD, OPTIONS");
+    }
 
-        let json = web::Json::parse(json_str).unwrap();
+    #[test]
+    fn head_and_options() {
+        let router = router();
 
-        println!("{}", json);
-		assert_eq!(web::JsonType::String("first".into()), *json.index(0).unwrap().get());
-		assert_eq!(web::JsonType::String("second".into()), *json.index(1).unwrap().get());
-		assert_eq!(web::JsonType::String("第三个".into()), *json.index(2).unwrap().get());
+        let response = call(&router, "HEAD", "/users/1");
+        assert_eq!(response.get_status_code(), web::HttpResponseStatusCode::OK);
+        assert!(response.get_body().is_empty());
+        assert_eq!(response.get_headers()["content-length"], "7");
+
+        let response = call(&router, "OPTIONS", "/upload/x");
+        assert_eq!(response.get_status_code(), web::HttpResponseStatusCode::NoContent);
+        assert_eq!(response.get_headers()["allow"], "OPTIONS, POST");
     }
 
     #[test]
-    fn parse_complex_json_1() {
-        let json_str = " { \"a\": 123, \"c\": \"a\\\"ha\\\"aad\", \"fgfgfg\": 444.2, \"complex\": { \"son\": 123}, \"zzz\": null} ";
-        let json = web::Json::parse(json_str).unwrap();
+    fn custom_handlers() {
+        let mut router = router();
+        router.set_not_found_handler(|request| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::NotFound);
+            response.set_body(format!("missing {}", request.get_uri()).into_bytes());
+            response
+        });
+        router.set_method_not_allowed_handler(|_, allowed| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::MethodNotAllowed);
+            response.set_body(allowed.join("|").into_bytes());
+            response
+        });
+
+        let response = call(&router, "DELETE", "/nothing/here");
+        assert_eq!(response.get_status_code(), web::HttpResponseStatusCode::NotFound);
+        assert_eq!(response.get_body(), b"missing /nothing/here");
+
+        let response = call(&router, "PUT", "/users/1");
+        assert_eq!(response.get_body(), b"DELETE|GET|HEAD|OPTIONS");
+        assert_eq!(response.get_headers()["allow"], "DELETE, GET, HEAD, OPTIONS");
+    }
+}
 
-        println!("test_display:\n{}", json);
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+    use test_util::{call, text};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-        assert_eq!(String::from(&*json.get_val("c").unwrap()), "a\"ha\"aad");
+    struct Config {
+        name: String,
+    }
+
+    fn config_name(config: web::State<Config>) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from(config.name.as_str()))
     }
 
     #[test]
-    fn parse_complex_json_2() {
-		let json_str = "[{\"a\": 123, \"bb\": \"abc\"}, {\"a\": 456}, {\"a\": 789}]";
+    fn owned_closures() {
+        let counter = std::sync::Arc::new(AtomicUsize::new(0));
+        let prefix = String::from("hits");
 
-        let json = web::Json::parse(json_str).unwrap();
+        let mut router = web::Router::new();
+        let hits = counter.clone();
+        router.register("GET", "/count", move || {
+            let count = hits.fetch_add(1, Ordering::SeqCst) + 1;
+            web::HttpResponse::json(web::Json::from(format!("{prefix}={count}")))
+        });
+        let hits = counter.clone();
+        router.register_url("GET", "/legacy", move |_| {
+            web::HttpResponse::json(web::Json::from(hits.load(Ordering::SeqCst) as i64))
+        });
+
+        assert_eq!(text(&call(&router, "GET", "/count")), "\"hits=1\"");
+        assert_eq!(text(&call(&router, "GET", "/count")), "\"hits=2\"");
+        assert_eq!(text(&call(&router, "GET", "/legacy")), "2");
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
 
-        assert_eq!(web::JsonType::i64(123), *json.index(0).unwrap().get_val("a").unwrap());
-        assert_eq!(web::JsonType::String("abc".into()), *json.index(0).unwrap().get_val("bb").unwrap());
+    #[test]
+    fn shared_state() {
+        let mut users = web::Router::new();
+        users.add_state(AtomicUsize::new(0));
+        users.register_handler("GET", "/", |ctx: web::RequestContext| {
+            let visits = ctx.get_state::<AtomicUsize>().unwrap();
+            web::HttpResponse::json(web::Json::from(visits.fetch_add(1, Ordering::SeqCst) as i64))
+        });
 
-        assert_eq!(web::JsonType::i64(456), *json.index(1).unwrap().get_val("a").unwrap());
+        let mut router = web::Router::new();
+        router.add_state(Config { name: "demo".into() });
+        router.register("GET", "/name", config_name);
+        router.mount("/users", users);
+
+        assert_eq!(text(&call(&router, "GET", "/name")), "\"demo\"");
+        assert_eq!(text(&call(&router, "GET", "/users")), "0");
+        assert_eq!(text(&call(&router, "GET", "/users/")), "1");
+        //子 Router 的状态不会泄漏到外层
+        router.register_handler("GET", "/visits", |ctx: web::RequestContext| {
+            web::HttpResponse::json(web::Json::from(ctx.get_state::<AtomicUsize>().is_some()))
+        });
+        assert_eq!(text(&call(&router, "GET", "/visits")), "false");
+
+        //同类型的状态, mount 进来的路由用子 Router 的, 外层路由用自己的, mount 之后添加的也一样
+        let mut admin = web::Router::new();
+        admin.add_state(Config { name: "admin".into() });
+        admin.register("GET", "/name", config_name);
+        router.mount("/admin", admin);
+        router.add_state(Config { name: "changed".into() });
+
+        assert_eq!(text(&call(&router, "GET", "/admin/name")), "\"admin\"");
+        assert_eq!(text(&call(&router, "GET", "/name")), "\"changed\"");
+        assert_eq!(text(&call(&router, "GET", "/users")), "2");
 
-        assert_eq!(web::JsonType::i64(789), *json.index(2).unwrap().get_val("a").unwrap());
+        let mut router = web::Router::new();
+        router.register("GET", "/name", config_name);
+        assert_eq!(call(&router, "GET", "/name").get_status_code(), web::HttpResponseStatusCode::InternalServerError);
     }
+}
+
+#[cfg(test)]
+mod status_tests {
+    use super::*;
 
     #[test]
-    fn parse_complex_json_3() {
-        let json_str = "{\"www\": \"中文♥\", \"lalala\": [1, 2, 3]}";
+    fn status_codes() {
+        let code = web::HttpResponseStatusCode::from(503u16);
+        assert_eq!(web::HttpResponseStatusCode::ServiceUnavailable, code);
+        assert_eq!("Service Unavailable", code.get_reason_phrase());
+        assert_eq!(503, code.as_u16());
+
+        let code = web::HttpResponseStatusCode::from(599u16);
+        assert_eq!(web::HttpResponseStatusCode::Unknown(599), code);
+        assert_eq!("", code.get_reason_phrase());
+        assert_eq!(599, code.as_u16());
+
+        let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+        response.set_status_code(web::HttpResponseStatusCode::NotModified);
+        assert_eq!("Not Modified", response.get_status_desc());
+    }
 
-        let json = web::Json::parse(json_str).unwrap();
+    #[test]
+    fn read_response() {
+        let cases = [
+            ("HTTP/1.1 201 Created\r\ncontent-length: 2\r\n\r\n{}", web::HttpResponseStatusCode::Created, "Created"),
+            ("HTTP/1.1 304 Not Modified\r\ncontent-length: 0\r\n\r\n", web::HttpResponseStatusCode::NotModified, "Not Modified"),
+            ("HTTP/1.1 304 Not Modified\r\netag: \"a\"\r\n\r\n", web::HttpResponseStatusCode::NotModified, "Not Modified"),
+            ("HTTP/1.1 304 Not Modified\r\ncontent-length: 1024\r\n\r\n", web::HttpResponseStatusCode::NotModified, "Not Modified"),
+            ("HTTP/1.1 204 No Content\r\n\r\n", web::HttpResponseStatusCode::NoContent, "No Content"),
+            ("HTTP/1.1 299 Custom Thing\r\ncontent-length: 0\r\n\r\n", web::HttpResponseStatusCode::Unknown(299), "Custom Thing"),
+        ];
+
+        for (content, code, desc) in cases {
+            let mut reader = web::HttpResponseReader::default();
+            reader.read(content.as_bytes().to_vec()).unwrap();
+            let response = reader.get_response().unwrap();
+            assert_eq!(code, response.get_status_code());
+            assert_eq!(desc, response.get_status_desc());
+        }
 
-        assert_eq!(web::JsonType::String("中文♥".into()), *json.get_val("www").unwrap());
+        let mut reader = web::HttpResponseReader::default();
+        assert!(reader.read(b"HTTP/1.1 2000 OK\r\n\r\n".to_vec()).is_err());
     }
 }
 
 #[cfg(test)]
-mod urldecode {
+mod multipart_tests {
     use super::*;
 
-	#[test]
-	fn test_decode() {
-		let code = "name=123&aaa=444&www=%E4%B8%AD%E6%96%87test%F0%9F%92%96";
-		
-		let result = web::urldecode(code);
-		assert_eq!("name=123&aaa=444&www=中文test💖", result.unwrap());
-	}
+    fn multipart_request(body: &str) -> web::HttpRequest {
+        let mut request = web::HttpRequest::default();
+        request.set_method("POST");
+        request.set_uri("/upload");
+        request.set_version("HTTP/1.1");
+        request.insert_header("content-type", "multipart/form-data; boundary=\"XyZ\"");
+        request.set_body(body.replace('\n', "\r\n").into_bytes());
+        request
+    }
+
+    const BODY: &str = "preamble\n--XyZ\nContent-Disposition: form-data; name=\"user[name]\"\n\nalice & bob=1%\n--XyZ\nContent-Disposition: form-data; name=\"tags[]\"\n\na\n--XyZ\nContent-Disposition: form-data; name=\"tags[]\"\n\nb\n--XyZ\nContent-Disposition: form-data; name=\"avatar\"; filename=\"a;b.txt\"\nContent-Type: text/plain\n\nline1\nline2\n--XyZ\nContent-Disposition: form-data; name=\"doc\"; filename=\"x\"; filename*=UTF-8''%E4%B8%AD.bin\n\n0123456789\n--XyZ--\nepilogue";
+
+    #[test]
+    fn parse_parts() {
+        let multipart = web::Multipart::parse(&multipart_request(BODY), &web::MultipartLimits::new()).unwrap();
+
+        let fields = web::Json::parse(r#"{"user": {"name": "alice & bob=1%"}, "tags": ["a", "b"]}"#).unwrap();
+        assert_eq!(fields, *multipart.get_fields());
+
+        let avatar = multipart.get_file("avatar").unwrap();
+        assert_eq!("a;b.txt", avatar.get_file_name());
+        assert_eq!("text/plain", avatar.get_content_type());
+        assert_eq!(b"line1\r\nline2".to_vec(), avatar.read_bytes().unwrap());
+        assert!(avatar.get_temp_path().is_none());
+
+        let doc = multipart.get_file("doc").unwrap();
+        assert_eq!("中.bin", doc.get_file_name());
+        assert_eq!("application/octet-stream", doc.get_content_type());
+        assert_eq!(10, doc.get_size());
+    }
+
+    #[test]
+    fn temp_files_and_limits() {
+        //超过 memory_limit 的文件写到临时文件, 释放或保存后临时文件不再存在
+        let multipart = web::Multipart::parse(&multipart_request(BODY), web::MultipartLimits::new().memory_limit(8)).unwrap();
+        let (_, mut files) = multipart.into_parts();
+        let doc = files.pop().unwrap();
+        let temp_path = doc.get_temp_path().unwrap().to_path_buf();
+        assert_eq!(b"0123456789".to_vec(), std::fs::read(&temp_path).unwrap());
+
+        let saved = std::env::temp_dir().join(format!("rust_web_saved_{}", rand::random::<u64>()));
+        doc.persist(&saved).unwrap();
+        assert!(!temp_path.exists());
+        assert_eq!(b"0123456789".to_vec(), std::fs::read(&saved).unwrap());
+        std::fs::remove_file(&saved).unwrap();
+
+        let avatar = files.pop().unwrap();
+        let temp_path = avatar.get_temp_path().unwrap().to_path_buf();
+        drop(avatar);
+        assert!(!temp_path.exists());
+
+        let status = |limits: &web::MultipartLimits, body: &str| web::Multipart::parse(&multipart_request(body), limits).unwrap_err().get_status_code();
+        assert_eq!(web::HttpResponseStatusCode::ContentTooLarge, status(web::MultipartLimits::new().max_part_size(9), BODY));
+        assert_eq!(web::HttpResponseStatusCode::ContentTooLarge, status(web::MultipartLimits::new().max_total_size(30), BODY));
+        assert_eq!(web::HttpResponseStatusCode::ContentTooLarge, status(web::MultipartLimits::new().max_parts(4), BODY));
+        assert_eq!(web::HttpResponseStatusCode::BadRequest, status(&web::MultipartLimits::new(), "--XyZ\nContent-Disposition: form-data; name=\"a\"\n\nvalue"));
+        assert_eq!(web::HttpResponseStatusCode::BadRequest, status(&web::MultipartLimits::new(), "--XyZ\nContent-Type: text/plain\n\nvalue\n--XyZ--"));
+    }
+
+    #[test]
+    fn router_integration() {
+        let mut router = web::Router::new();
+        router.register_handler("POST", "/upload", |ctx: web::RequestContext| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            let web::JsonType::Vec(tags) = ctx.get_json().get_val("tags").unwrap() else { panic!("tags must be array"); };
+            response.set_body(format!("{}:{}", tags.len(), ctx.get_files().len()).into_bytes());
+            response
+        });
+        router.register("POST", "/extract", |multipart: web::Multipart| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_body(multipart.get_file("avatar").unwrap().read_bytes()?);
+            Ok::<_, web::HttpError>(response)
+        });
+
+        let response = async_std::task::block_on(router.call(&multipart_request(BODY), None)).unwrap();
+        assert_eq!(b"2:2".to_vec(), *response.get_body());
+
+        let mut request = multipart_request(BODY);
+        request.set_uri("/extract");
+        let response = async_std::task::block_on(router.call(&request, None)).unwrap();
+        assert_eq!(b"line1\r\nline2".to_vec(), *response.get_body());
+
+        router.set_multipart_limits(*web::MultipartLimits::new().max_parts(1));
+        let response = async_std::task::block_on(router.call(&request, None)).unwrap();
+        assert_eq!(web::HttpResponseStatusCode::ContentTooLarge, response.get_status_code());
+    }
 }
 
 #[cfg(test)]
-mod router_tests {
+mod static_tests {
     use super::*;
 
-    fn test_response(param: web::Json) -> web::HttpResponse {
-        println!("test_response!!!param:{}", param);
-
-        return web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+    //root 下: a b.txt, style.CSS, docs/index.html, empty/<tag>.txt, link -> ../secret.txt; root 外: secret.txt
+    fn make_root() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust_web_static_{}", rand::random::<u64>()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::write(root.join("a b.txt"), "hello").unwrap();
+        std::fs::write(root.join("style.CSS"), "body {}").unwrap();
+        std::fs::write(root.join("docs").join("index.html"), "<h1>docs</h1>").unwrap();
+        std::fs::write(root.join("empty").join("<tag>.txt"), "").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link")).unwrap();
+        root
     }
 
     #[test]
-    fn call_back_register() {
-        let mut router = web::Router::new(); 
+    fn serve_files() {
+        let root = make_root();
+        let files = web::StaticFiles::new(&root);
+
+        let response = files.serve("/a%20b.txt").unwrap();
+        assert_eq!(web::HttpResponseStatusCode::OK, response.get_status_code());
+        assert_eq!("text/plain; charset=utf-8", response.get_headers()["content-type"]);
+        assert_eq!(b"hello".to_vec(), *response.get_body());
+        assert_eq!("text/css; charset=utf-8", files.serve("/style.CSS").unwrap().get_headers()["content-type"]);
+
+        //目录先重定向到带 / 的地址, 再返回 index.html
+        let response = files.serve("/docs").unwrap();
+        assert_eq!(web::HttpResponseStatusCode::MovedPermanently, response.get_status_code());
+        assert_eq!("/docs/", response.get_headers()["location"]);
+
+        //重定向地址总是本站的规范化路径
+        for uri in ["//evil.com/..%2fdocs", "//evil.com/../docs", "/empty/../docs"] {
+            let response = files.serve(uri).unwrap();
+            assert_eq!(web::HttpResponseStatusCode::MovedPermanently, response.get_status_code(), "{uri}");
+            assert_eq!("/docs/", response.get_headers()["location"], "{uri}");
+        }
+        std::fs::create_dir_all(root.join("a b")).unwrap();
+        assert_eq!("/a%20b/", files.serve("/a%20b").unwrap().get_headers()["location"]);
+        assert_eq!(b"<h1>docs</h1>".to_vec(), *files.serve("/docs/").unwrap().get_body());
+        assert_eq!(b"<h1>docs</h1>".to_vec(), *files.serve("/empty/../docs/./").unwrap().get_body());
 
-        println!("add:{:p}", test_response as fn(_) -> _);
-        router.register_url("GET".to_string(), "asd".to_string(), &test_response);
+        for uri in ["/../secret.txt", "/%2e%2e/secret.txt", "/docs/..%2F..%2Fsecret.txt", "/link", "/empty/", "/missing", "/a%00b", "/..\\secret.txt"] {
+            assert_eq!(web::HttpResponseStatusCode::NotFound, files.serve(uri).unwrap().get_status_code(), "{uri}");
+        }
+        assert_eq!(web::HttpResponseStatusCode::BadRequest, files.serve("/%zz").unwrap().get_status_code());
+
+        let response = web::StaticFiles::new(&root).directory_listing(true).serve("/empty/").unwrap();
+        let html = String::from_utf8(response.get_body().clone()).unwrap();
+        assert!(html.contains("<a href=\"../\">"));
+        assert!(html.contains("<a href=\"%3Ctag%3E.txt\">&lt;tag&gt;.txt</a>"));
+
+        let response = web::StaticFiles::new(&root).index_files(["missing.html"]).serve("/docs/").unwrap();
+        assert_eq!(web::HttpResponseStatusCode::NotFound, response.get_status_code());
+
+        //. 开头的文件与目录默认不提供, 也不出现在目录列表里
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".git").join("config"), "[core]").unwrap();
+        std::fs::write(root.join(".env"), "TOKEN=1").unwrap();
+        std::fs::write(root.join("docs").join(".htpasswd"), "admin:x").unwrap();
+        for uri in ["/.env", "/.git/config", "/docs/.htpasswd", "/%2eenv", "/docs/../.env"] {
+            assert_eq!(web::HttpResponseStatusCode::NotFound, files.serve(uri).unwrap().get_status_code(), "{uri}");
+        }
+        let response = web::StaticFiles::new(&root).directory_listing(true).serve("/").unwrap();
+        assert!(!String::from_utf8(response.get_body().clone()).unwrap().contains(".env"));
 
-        let mut request = web::HttpRequest::default();
-        request.set_body("{\"a\": 123123}".as_bytes().to_vec());
-        request.insert_header("content-length", request.get_body().len().to_string());
+        let files = web::StaticFiles::new(&root).dotfiles(true);
+        assert_eq!(b"TOKEN=1".to_vec(), *files.serve("/.env").unwrap().get_body());
+        assert_eq!(b"[core]".to_vec(), *files.serve("/.git/config").unwrap().get_body());
 
-        router.call("GET", "asd", &request).unwrap();
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn contains_uri() {
+    fn conditional_requests() {
+        let root = make_root();
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("assets").join("app.3f9a1c.js"), "run()").unwrap();
+        std::fs::File::options().write(true).open(root.join("a b.txt")).unwrap()
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(784111777)).unwrap();
+
+        let files = web::StaticFiles::new(&root)
+            .immutable("/assets/*.js")
+            .cache_control("*.html", "no-cache")
+            .cache_control("/*", "public, max-age=60");
+        let request = |uri: &str, headers: &[(&str, &str)]| {
+            let mut request = web::HttpRequest::default();
+            request.set_method("GET");
+            request.set_uri(uri);
+            for (key, val) in headers {
+                request.insert_header(*key, *val);
+            }
+            files.serve_request(&request).unwrap()
+        };
+
+        let response = request("/a%20b.txt", &[]);
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", response.get_headers()["last-modified"]);
+        assert_eq!("public, max-age=60", response.get_headers()["cache-control"]);
+        let etag = response.get_headers()["etag"].clone();
+        assert!(etag.starts_with("W/\""));
+
+        let response = request("/a%20b.txt", &[("if-none-match", &format!("\"other\", {}", etag.trim_start_matches("W/")))]);
+        assert_eq!(web::HttpResponseStatusCode::NotModified, response.get_status_code());
+        assert_eq!(etag, response.get_headers()["etag"]);
+        assert_eq!("public, max-age=60", response.get_headers()["cache-control"]);
+        assert!(!response.get_headers().contains_key("content-length"));
+        assert!(response.get_body().is_empty());
+        assert_eq!(web::HttpResponseStatusCode::NotModified, request("/a%20b.txt", &[("if-none-match", "*")]).get_status_code());
+
+        //If-None-Match 不匹配时不再看 If-Modified-Since
+        let response = request("/a%20b.txt", &[("if-none-match", "\"other\""), ("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert_eq!(web::HttpResponseStatusCode::OK, response.get_status_code());
+        assert_eq!(web::HttpResponseStatusCode::NotModified, request("/a%20b.txt", &[("if-modified-since", "Mon, 07 Nov 1994 00:00:00 GMT")]).get_status_code());
+        assert_eq!(web::HttpResponseStatusCode::OK, request("/a%20b.txt", &[("if-modified-since", "Sun, 06 Nov 1994 08:49:36 GMT")]).get_status_code());
+        assert_eq!(web::HttpResponseStatusCode::OK, request("/a%20b.txt", &[("if-modified-since", "yesterday")]).get_status_code());
+
+        assert_eq!("public, max-age=31536000, immutable", request("/assets/app.3f9a1c.js", &[]).get_headers()["cache-control"]);
+        assert_eq!("no-cache", request("/docs/", &[]).get_headers()["cache-control"]);
+
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn router_fallback() {
+        let root = make_root();
         let mut router = web::Router::new();
+        router.set_static_files(Some(web::StaticFiles::new(&root)));
 
-        router.register_url("POST", "/asd", &test_response);
-        assert_eq!(true, router.contains_url("POST", "/asd"));
-        assert_eq!(false, router.contains_url("GET", "/asd"));
-        assert_eq!(false, router.contains_url("POST", "asd"));
+        let mut request = web::HttpRequest::default();
+        request.set_method("HEAD");
+        request.set_uri("/a%20b.txt?v=1");
+        let response = async_std::task::block_on(router.call(&request, None)).unwrap();
+        assert_eq!(web::HttpResponseStatusCode::OK, response.get_status_code());
+        assert_eq!("5", response.get_headers()["content-length"]);
+        assert!(response.get_body().is_empty());
+
+        router.set_static_files(None);
+        let response = async_std::task::block_on(router.call(&request, None)).unwrap();
+        assert_eq!(web::HttpResponseStatusCode::NotFound, response.get_status_code());
+
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
     }
 }
 
@@ -1724,6 +6574,204 @@ mod request_tests {
         assert_eq!(true, reader.is_finished());
 
     }
+
+    #[test]
+    fn pipelined_requests() {
+        let mut reader = web::HttpRequestReader::new();
+
+        let content = "POST /a HTTP/1.1\r\ncontent-length: 5\r\n\r\nhelloGET /b HTTP/1.1\r\n\r\nGET /c HT";
+        reader.read(content.as_bytes().to_vec()).unwrap();
+
+        let request = reader.take_request().unwrap();
+        assert_eq!("/a", request.get_uri());
+        assert_eq!(b"hello".to_vec(), *request.get_body());
+
+        reader.read(vec![]).unwrap();
+        assert_eq!("/b", reader.take_request().unwrap().get_uri());
+
+        reader.read(vec![]).unwrap();
+        assert!(!reader.is_finished());
+        reader.read(b"TP/1.1\r\n\r\n".to_vec()).unwrap();
+        assert_eq!("/c", reader.take_request().unwrap().get_uri());
+        assert!(!reader.has_buffered());
+    }
+
+    #[test]
+    fn chunked_body() {
+        let mut reader = web::HttpRequestReader::new();
+
+        let content = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5;name=value\r\nhello\r\n";
+        reader.read(content.as_bytes().to_vec()).unwrap();
+        assert!(!reader.is_finished());
+
+        reader.read(b"7\r\n, world\r\n0\r\nChecksum: abc\r\n\r\nGET /next HTTP/1.1\r\n\r\n".to_vec()).unwrap();
+        let request = reader.take_request().unwrap();
+        assert_eq!(b"hello, world".to_vec(), *request.get_body());
+        assert_eq!("abc", request.get_trailers()["checksum"]);
+
+        reader.read(vec![]).unwrap();
+        assert_eq!("/next", reader.take_request().unwrap().get_uri());
+    }
+
+    #[test]
+    fn rejected_framing() {
+        let cases = [
+            ("POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n", web::HttpResponseStatusCode::NotImplemented),
+            ("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n+5\r\nhello\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhello\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 100\r\n\r\nhello", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nContent-Length: 5, 5\r\n\r\nhello", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\nhello", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nContent-Length: \r\n\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nContent-Length: 3\r\ncontent-length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", web::HttpResponseStatusCode::BadRequest),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", web::HttpResponseStatusCode::NotImplemented),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n", web::HttpResponseStatusCode::NotImplemented),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", web::HttpResponseStatusCode::NotImplemented),
+        ];
+
+        for (content, code) in cases {
+            let mut reader = web::HttpRequestReader::new();
+            assert!(reader.read(content.as_bytes().to_vec()).is_err(), "{content}");
+            assert_eq!(code, reader.take_rejection().unwrap().get_status_code(), "{content}");
+        }
+
+        //重复但相同的 Content-Length 可以接受
+        let mut reader = web::HttpRequestReader::new();
+        reader.read(b"POST / HTTP/1.1\r\nContent-Length: 5\r\ncontent-length: 5\r\n\r\nhello".to_vec()).unwrap();
+        assert_eq!(b"hello".to_vec(), *reader.take_request().unwrap().get_body());
+
+        //没有请求体大小限制时, 接近 usize::MAX 的 chunk 长度也要拒绝
+        let content = format!("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\nhello\r\n", usize::MAX - 1);
+        let mut reader = web::HttpRequestReader::with_limits(*web::RequestLimits::new().max_body_size(usize::MAX));
+        assert!(reader.read(content.into_bytes()).is_err());
+        assert_eq!(web::HttpResponseStatusCode::BadRequest, reader.take_rejection().unwrap().get_status_code());
+    }
+
+    #[test]
+    fn request_limits() {
+        let mut limits = web::RequestLimits::new();
+        limits.max_request_line(32).max_header_count(2).max_header_size(64).max_body_size(8);
+
+        let many_headers = "GET / HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\n\r\n".to_string();
+        let long_header = format!("GET / HTTP/1.1\r\nx: {}", "a".repeat(64));
+        let cases = [
+            (format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(32)), web::HttpResponseStatusCode::URITooLong),
+            (format!("GET /{}", "a".repeat(32)), web::HttpResponseStatusCode::URITooLong),
+            (many_headers, web::HttpResponseStatusCode::RequestHeaderFieldsTooLarge),
+            (long_header, web::HttpResponseStatusCode::RequestHeaderFieldsTooLarge),
+            ("POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\n".to_string(), web::HttpResponseStatusCode::ContentTooLarge),
+            ("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n4\r\n".to_string(), web::HttpResponseStatusCode::ContentTooLarge),
+        ];
+
+        for (content, code) in cases {
+            let mut reader = web::HttpRequestReader::with_limits(limits);
+            assert!(reader.read(content.into_bytes()).is_err());
+            assert_eq!(code, reader.take_rejection().unwrap().get_status_code());
+        }
+
+        //限制按单个请求计算, 长连接上的下一个请求重新计数
+        let mut reader = web::HttpRequestReader::with_limits(limits);
+        reader.read(b"GET / HTTP/1.1\r\na: 1\r\nb: 2\r\n\r\nGET / HTTP/1.1\r\na: 1\r\nb: 2\r\n\r\n".to_vec()).unwrap();
+        reader.take_request().unwrap();
+        reader.read(vec![]).unwrap();
+        assert!(reader.take_request().is_ok());
+    }
+
+    #[test]
+    fn incremental_read() {
+        let content = b"\r\nPOST /form?a=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabcGET / HTTP/1.1\r\n\r\n";
+
+        //一次一个字节地喂进去, 结果应该与一次读完相同
+        let mut reader = web::HttpRequestReader::new();
+        let mut requests = Vec::new();
+        for &byte in content.iter() {
+            reader.read(vec![byte]).unwrap();
+            if reader.is_finished() { requests.push(reader.take_request().unwrap()); }
+        }
+
+        assert_eq!(2, requests.len());
+        assert_eq!("POST", requests[0].get_method());
+        assert_eq!("/form", requests[0].get_uri());
+        assert_eq!("a=1", requests[0].get_query_string());
+        assert_eq!("localhost", requests[0].get_headers()["host"]);
+        assert_eq!(b"abc".to_vec(), *requests[0].get_body());
+        assert_eq!("/", requests[1].get_uri());
+        assert!(!reader.has_buffered());
+    }
+
+    #[test]
+    fn streaming_body() {
+        let content = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+
+        //请求头读完就取出, 请求体收到多少解码多少, chunk 没有收全也先交出去
+        let mut reader = web::HttpRequestReader::new();
+        let mut head = None;
+        let mut body = Vec::new();
+        let mut pieces = 0;
+        for &byte in content.iter() {
+            reader.read(vec![byte]).unwrap();
+            if head.is_none() && reader.get_head().is_some() { head = Some(reader.take_head().unwrap()); }
+            if reader.is_streaming() {
+                let data = reader.take_body_data();
+                if !data.is_empty() { pieces += 1; }
+                body.extend(data);
+                if reader.is_finished() { reader.take_request().unwrap(); }
+            }
+        }
+
+        assert_eq!("/upload", head.unwrap().get_uri());
+        assert_eq!(b"hello world".to_vec(), body);
+        assert_eq!(11, pieces);
+        assert!(reader.is_finished());
+        assert_eq!("/", reader.take_request().unwrap().get_uri());
+
+        let mut reader = web::HttpRequestReader::new();
+        reader.read(b"POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc".to_vec()).unwrap();
+        let head = reader.take_head().unwrap();
+        assert!(head.get_body().is_empty());
+        assert_eq!(b"abc".to_vec(), reader.take_body_data());
+        reader.read(b"defghij".to_vec()).unwrap();
+        assert_eq!(b"defghij".to_vec(), reader.take_body_data());
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn many_headers() {
+        let mut limits = web::RequestLimits::new();
+        limits.max_header_count(usize::MAX).max_header_size(usize::MAX);
+
+        let mut content = String::from("GET / HTTP/1.1\r\n");
+        for i in 0..200000 {
+            content += &format!("x-{i}: {i}\r\n");
+        }
+        content += "\r\n";
+
+        let mut reader = web::HttpRequestReader::with_limits(limits);
+        for chunk in content.as_bytes().chunks(4096) {
+            reader.read(chunk.to_vec()).unwrap();
+        }
+        assert_eq!(200000, reader.take_request().unwrap().get_headers().len());
+    }
+
+    #[test]
+    fn request_head() {
+        let content = b"GET /a?b=c HTTP/1.1\r\nHost: example.com\r\nAccept:  */* \r\n\r\nbody";
+        let head = web::RequestHead::parse(content).unwrap();
+
+        assert_eq!("GET", head.get_method());
+        assert_eq!("/a?b=c", head.get_uri());
+        assert_eq!("HTTP/1.1", head.get_version());
+        assert_eq!(&[("Host", "example.com"), ("Accept", "*/*")], head.get_headers());
+        assert_eq!(Some("example.com"), head.get_header("host"));
+
+        for content in ["GET /\r\n\r\n", "GET / FTP/1.0\r\n\r\n", "GET / HTTP/1.1\r\nno-colon\r\n\r\n", "GET / HTTP/1.1\r\nHost : a\r\n\r\n"] {
+            let mut reader = web::HttpRequestReader::new();
+            assert!(reader.read(content.as_bytes().to_vec()).is_err());
+            assert_eq!(web::HttpResponseStatusCode::BadRequest, reader.take_rejection().unwrap().get_status_code());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1753,6 +6801,517 @@ mod http_client_tests {
     }
 }
 
+#[cfg(test)]
+mod http_server_tests {
+    use super::*;
+
+    fn hello(_param: web::Json) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from("hello"))
+    }
+
+    fn start_server() -> std::net::SocketAddr {
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        server.get_router().register_url("GET", "/hello", hello);
+        server.get_router().register("POST", "/echo", |request: web::HttpRequest| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_body(request.get_body().clone());
+            response
+        });
+
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        addr
+    }
+
+    #[test]
+    fn concurrent_requests() {
+        let addr = start_server();
+
+        //先占住一个只发了一半请求的连接, 其他请求不应被它阻塞
+        let idle = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut &idle, b"GET /hello HTTP/1.1\r\n").unwrap();
+
+        let handles = (0..8).map(|_| std::thread::spawn(move || {
+            let mut request = web::HttpRequest::default();
+            request.set_method("GET");
+            request.set_version("HTTP/1.1");
+
+            web::HttpClient::default().send(format!("http://{addr}/hello"), request).unwrap()
+        })).collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(b"\"hello\"".to_vec(), *handle.join().unwrap().get_body());
+        }
+    }
+
+    #[test]
+    fn keep_alive_and_pipelining() {
+        let addr = start_server();
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /hello HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\n\r\n").unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert_eq!(3, response.matches("HTTP/1.1 200").count());
+        assert_eq!(2, response.matches("connection: keep-alive").count());
+        assert_eq!(1, response.matches("connection: close").count());
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /hello HTTP/1.0\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("connection: close"));
+        assert!(response.ends_with("\"hello\""));
+    }
+
+    #[test]
+    fn reason_phrases() {
+        let addr = start_server();
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /missing HTTP/1.1\r\n\r\nPUT /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.contains("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+        //204 不带 content-length, 后面紧接着下一个响应
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"OPTIONS /hello HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        let (options, rest) = response.split_once("\r\n\r\n").unwrap();
+        assert!(options.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(!options.contains("content-length"));
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn chunked_requests() {
+        let addr = start_server();
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n4;ext\r\ndefg\r\n0\r\n\r\n").unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("\r\n\r\nabcdefgHTTP/1.1 200"));
+        assert_eq!(2, response.matches("HTTP/1.1 200").count());
+
+        //Content-Length 与 Transfer-Encoding 同时出现时回复 400 并关闭连接
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nGET /hello HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.contains("connection: close"));
+        assert!(!response.contains("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn slow_requests() {
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        server.get_router().register_url("GET", "/hello", hello);
+        server.header_timeout(std::time::Duration::from_millis(300));
+        server.request_limits(*web::RequestLimits::new().max_body_size(4));
+
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        //每次只发一点请求头, 读超时一直不会触发, 但总时间超过 header_timeout
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        let started = std::time::Instant::now();
+        std::io::Write::write_all(&mut stream, b"GET /hello HTTP/1.1\r\n").unwrap();
+        for _ in 0..3 {
+            std::thread::sleep(std::time::Duration::from_millis(150));
+            let _ = std::io::Write::write_all(&mut stream, b"x-drip: 1\r\n");
+        }
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /hello HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+
+    #[test]
+    fn streaming_responses() {
+        let path = std::env::temp_dir().join(format!("rust_web_stream_{}.txt", std::process::id()));
+        std::fs::write(&path, "x".repeat(100000)).unwrap();
+
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        server.get_router().register("GET", "/stream", |_request: web::HttpRequest| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            let chunks = vec![Ok(b"hello".to_vec()), Ok(Vec::new()), Ok(b" world".to_vec())];
+            response.set_stream(futures::stream::iter(chunks));
+            response.set_trailers(|| [("x-checksum".to_string(), "42".to_string())].into_iter().collect());
+            response
+        });
+        let file_path = path.clone();
+        server.get_router().register("GET", "/file", move |_request: web::HttpRequest| {
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_file(std::fs::File::open(&file_path).unwrap()).unwrap();
+            response
+        });
+
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        //HTTP/1.1 使用 chunked 编码, 空的数据块不发送, 最后带上 trailer
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /stream HTTP/1.1\r\n\r\nHEAD /stream HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("transfer-encoding: chunked"));
+        assert!(response.contains("\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\nx-checksum: 42\r\n\r\nHTTP/1.1 200"));
+        assert!(response.ends_with("\r\n\r\n"));
+        assert_eq!(2, response.matches("transfer-encoding: chunked").count());
+
+        //HTTP/1.0 不支持 chunked, 发送完直接关闭连接
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(!response.contains("transfer-encoding"));
+        assert!(response.contains("connection: close"));
+        assert!(response.ends_with("\r\n\r\nhello world"));
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /file HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("content-length: 100000\r\n"));
+        assert!(response.ends_with(&format!("\r\n\r\n{}", "x".repeat(100000))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn changing_files() {
+        let path = std::env::temp_dir().join(format!("rust_web_changing_{}.txt", std::process::id()));
+
+        //set_file 之后文件变长或变短, 相当于发送过程中被修改
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        server.get_router().register_url("GET", "/hello", hello);
+        let file_path = path.clone();
+        server.get_router().register("GET", "/grow", move |_request: web::HttpRequest| {
+            std::fs::write(&file_path, "x".repeat(10)).unwrap();
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_file(std::fs::File::open(&file_path).unwrap()).unwrap();
+            std::fs::write(&file_path, "y".repeat(20)).unwrap();
+            response
+        });
+        let file_path = path.clone();
+        server.get_router().register("GET", "/shrink", move |_request: web::HttpRequest| {
+            std::fs::write(&file_path, "x".repeat(10)).unwrap();
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_file(std::fs::File::open(&file_path).unwrap()).unwrap();
+            std::fs::write(&file_path, "y".repeat(4)).unwrap();
+            response
+        });
+
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /grow HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("content-length: 10\r\n"));
+        assert!(response.contains(&format!("\r\n\r\n{}HTTP/1.1 200", "y".repeat(10))));
+        assert!(response.ends_with("\"hello\""));
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"GET /shrink HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        let _ = std::io::Read::read_to_string(&mut stream, &mut response);
+        assert!(response.ends_with("\r\n\r\nyyyy"));
+        assert_eq!(1, response.matches("HTTP/1.1").count());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn streaming_request_body() {
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        server.request_limits(*web::RequestLimits::new().max_body_size(1000));
+        server.get_router().register("POST", "/count", |mut body: web::RequestBody| async move {
+            let mut total = 0;
+            let mut buf = [0u8; 7];
+            loop {
+                let len = async_std::io::ReadExt::read(&mut body, &mut buf).await?;
+                if len == 0 { break; }
+                total += len;
+            }
+
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_body(format!("count={total}").into_bytes());
+            Ok::<_, web::HttpError>(response)
+        }).stream_body();
+        //不读请求体就返回, 剩下的请求体无法跳过, 连接随后关闭
+        server.get_router().register("POST", "/ignore", |_body: web::RequestBody| {
+            web::HttpResponse::new(web::HttpResponseStatusCode::Accepted)
+        }).stream_body();
+        //multipart 请求体不在 handler 之前解析, 由 handler 自己读取
+        server.get_router().register_async("POST", "/upload", |ctx: web::RequestContext| async move {
+            let mut body = ctx.take_body().ok_or_else(|| web::HttpError::new(web::HttpResponseStatusCode::InternalServerError, "no body"))?;
+            let mut data = Vec::new();
+            async_std::io::ReadExt::read_to_end(&mut body, &mut data).await?;
+
+            let mut response = web::HttpResponse::new(web::HttpResponseStatusCode::OK);
+            response.set_body(format!("{}:{}", ctx.get_json().get_val("name").map(|name| name.to_string()).unwrap_or_default(), data.len()).into_bytes());
+            Ok::<_, web::HttpError>(response)
+        }).stream_body();
+
+        let addr = server.local_addr().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /count HTTP/1.1\r\nContent-Length: 20\r\n\r\n0123456789").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        std::io::Write::write_all(&mut stream, b"0123456789POST /count HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n").unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /count HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.contains("\r\n\r\ncount=20HTTP/1.1 200"));
+        assert!(response.contains("\r\n\r\ncount=3HTTP/1.1 200"));
+        assert!(response.ends_with("\r\n\r\ncount=0"));
+
+        //chunked 请求体超过限制时 handler 读到错误, 客户端收到 413
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, format!("POST /count HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3e8\r\n{}\r\n1\r\nx\r\n0\r\n\r\n", "a".repeat(1000)).as_bytes()).unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, b"POST /ignore HTTP/1.1\r\nContent-Length: 500\r\n\r\nabc").unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 202"));
+        assert!(response.contains("connection: close"));
+
+        let body = "--b\r\ncontent-disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\nhello\r\n--b--\r\n";
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        std::io::Write::write_all(&mut stream, format!("POST /upload?name=a HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len()).as_bytes()).unwrap();
+
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(&format!("\r\n\r\n\"a\":{}", body.len())));
+    }
+}
+
+#[cfg(test)]
+mod tls_tests {
+    use super::*;
+
+    struct TestCert {
+        cert: openssl::x509::X509,
+        key: openssl::pkey::PKey<openssl::pkey::Private>,
+    }
+
+    impl TestCert {
+        //issuer 为 None 时生成自签名的 CA 证书
+        fn new(common_name: &str, alt_names: &[&str], issuer: Option<&TestCert>) -> Self {
+            let key = openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(2048).unwrap()).unwrap();
+
+            let mut name = openssl::x509::X509NameBuilder::new().unwrap();
+            name.append_entry_by_text("CN", common_name).unwrap();
+            let name = name.build();
+
+            let mut builder = openssl::x509::X509Builder::new().unwrap();
+            builder.set_version(2).unwrap();
+            builder.set_serial_number(&openssl::bn::BigNum::from_u32(rand::random::<u32>()).unwrap().to_asn1_integer().unwrap()).unwrap();
+            builder.set_subject_name(&name).unwrap();
+            builder.set_issuer_name(issuer.map(|issuer| issuer.cert.subject_name()).unwrap_or(&name)).unwrap();
+            builder.set_pubkey(&key).unwrap();
+            builder.set_not_before(&openssl::asn1::Asn1Time::days_from_now(0).unwrap()).unwrap();
+            builder.set_not_after(&openssl::asn1::Asn1Time::days_from_now(1).unwrap()).unwrap();
+
+            if issuer.is_none() {
+                builder.append_extension(openssl::x509::extension::BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+            }
+
+            if !alt_names.is_empty() {
+                let mut san = openssl::x509::extension::SubjectAlternativeName::new();
+                for alt_name in alt_names {
+                    if alt_name.parse::<std::net::IpAddr>().is_ok() { san.ip(alt_name); } else { san.dns(alt_name); }
+                }
+                let san = san.build(&builder.x509v3_context(issuer.map(|issuer| issuer.cert.as_ref()), None)).unwrap();
+                builder.append_extension(san).unwrap();
+            }
+
+            builder.sign(issuer.map(|issuer| &issuer.key).unwrap_or(&key), openssl::hash::MessageDigest::sha256()).unwrap();
+
+            Self { cert: builder.build(), key }
+        }
+
+        fn cert_pem(&self) -> Vec<u8> {
+            self.cert.to_pem().unwrap()
+        }
+
+        fn key_pem(&self) -> Vec<u8> {
+            self.key.private_key_to_pem_pkcs8().unwrap()
+        }
+    }
+
+    fn hello(_param: web::Json) -> web::HttpResponse {
+        web::HttpResponse::json(web::Json::from("hello"))
+    }
+
+    fn whoami(ctx: web::RequestContext) -> web::HttpResponse {
+        let Some(cert) = ctx.get_request().get_peer_certificate() else {
+            return web::HttpResponse::json(web::Json::new(web::JsonType::Null));
+        };
+
+        web::HttpResponse::json(web::Json::from([
+            ("subject".to_string(), web::Json::from(cert.get_subject())),
+            ("san".to_string(), web::Json::from(cert.get_subject_alt_names().join(","))),
+            ("fingerprint".to_string(), web::Json::from(cert.get_fingerprint())),
+        ]))
+    }
+
+    fn start_server(config: web::TlsConfig) -> (std::net::SocketAddr, web::TlsAcceptor) {
+        let mut server = async_std::task::block_on(web::HttpServer::new("127.0.0.1:0")).unwrap();
+        let router = server.get_router();
+        router.register_url("GET", "/hello", hello);
+        router.register_handler("GET", "/whoami", whoami);
+        server.use_tls(config).unwrap();
+
+        let addr = server.local_addr().unwrap();
+        let acceptor = server.get_tls_acceptor().unwrap();
+        async_std::task::spawn(async move { server.listen().await });
+
+        (addr, acceptor)
+    }
+
+    //返回服务端证书的 CN
+    fn server_name(addr: std::net::SocketAddr, host: &str) -> String {
+        let mut connector = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls()).unwrap();
+        connector.set_verify(openssl::ssl::SslVerifyMode::NONE);
+
+        let stream = std::net::TcpStream::connect(addr).unwrap();
+        let stream = connector.build().connect(host, stream).unwrap();
+        let cert = stream.ssl().peer_certificate().unwrap();
+        let common_name = cert.subject_name().entries_by_nid(openssl::nid::Nid::COMMONNAME).next().unwrap().data().as_utf8().unwrap();
+
+        common_name.to_string()
+    }
+
+    #[test]
+    fn sni_certificates() {
+        let cert = TestCert::new("default.test", &[], None);
+        let api_cert = TestCert::new("api.test", &[], None);
+        let wildcard_cert = TestCert::new("*.static.test", &[], None);
+
+        let mut config = web::TlsConfig::from_pem(cert.cert_pem(), cert.key_pem());
+        config.add_sni_pem("api.test", api_cert.cert_pem(), api_cert.key_pem())
+            .add_sni_pem("*.static.test", wildcard_cert.cert_pem(), wildcard_cert.key_pem())
+            .min_version(web::TlsVersion::Tls1_2);
+
+        let (addr, _) = start_server(config);
+        assert_eq!("default.test", server_name(addr, "other.test"));
+        assert_eq!("api.test", server_name(addr, "api.test"));
+        assert_eq!("*.static.test", server_name(addr, "img.static.test"));
+    }
+
+    #[test]
+    fn reload_certificates() {
+        let dir = std::env::temp_dir().join(format!("rust_web_tls_{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let cert = TestCert::new("first.test", &[], None);
+        std::fs::write(dir.join("cert.pem"), cert.cert_pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), cert.key_pem()).unwrap();
+
+        let (addr, acceptor) = start_server(web::TlsConfig::from_files(dir.join("cert.pem"), dir.join("key.pem")));
+        assert_eq!("first.test", server_name(addr, "localhost"));
+
+        let cert = TestCert::new("second.test", &[], None);
+        std::fs::write(dir.join("cert.pem"), cert.cert_pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), cert.key_pem()).unwrap();
+        assert_eq!("first.test", server_name(addr, "localhost"));
+
+        acceptor.reload().unwrap();
+        assert_eq!("second.test", server_name(addr, "localhost"));
+
+        std::fs::write(dir.join("key.pem"), "broken").unwrap();
+        assert!(acceptor.reload().is_err());
+        assert_eq!("second.test", server_name(addr, "localhost"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn whoami_request(addr: std::net::SocketAddr, client: &web::HttpClient) -> Result<web::Json, web::BacktraceError> {
+        let mut request = web::HttpRequest::default();
+        request.set_method("GET");
+        request.set_version("HTTP/1.1");
+        request.insert_header("connection", "close");
+
+        let response = client.send(format!("https://{addr}/whoami"), request)?;
+        web::Json::parse(std::str::from_utf8(response.get_body())?)
+    }
+
+    #[test]
+    fn client_certificates() {
+        let ca = TestCert::new("test ca", &[], None);
+        let server_cert = TestCert::new("server", &["localhost", "127.0.0.1"], Some(&ca));
+        let client_cert = TestCert::new("client", &["client.internal"], Some(&ca));
+        let stranger_ca = TestCert::new("stranger ca", &[], None);
+        let stranger_cert = TestCert::new("client", &[], Some(&stranger_ca));
+
+        let mut config = web::TlsConfig::from_pem(server_cert.cert_pem(), server_cert.key_pem());
+        config.client_auth_pem(ca.cert_pem(), web::ClientAuth::Required);
+        let (addr, _) = start_server(config);
+
+        let mut client = web::HttpClient::default();
+        client.add_root_ca_pem(ca.cert_pem()).set_client_cert_pem(client_cert.cert_pem(), client_cert.key_pem());
+        let json = whoami_request(addr, &client).unwrap();
+        assert_eq!("CN=client", String::from(json.get_val("subject").unwrap()));
+        assert_eq!("client.internal", String::from(json.get_val("san").unwrap()));
+
+        let fingerprint = client_cert.cert.digest(openssl::hash::MessageDigest::sha256()).unwrap().iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+        assert_eq!(fingerprint, String::from(json.get_val("fingerprint").unwrap()));
+
+        let mut anonymous = web::HttpClient::default();
+        anonymous.add_root_ca_pem(ca.cert_pem());
+        assert!(whoami_request(addr, &anonymous).is_err());
+
+        let mut stranger = web::HttpClient::default();
+        stranger.add_root_ca_pem(ca.cert_pem()).set_client_cert_pem(stranger_cert.cert_pem(), stranger_cert.key_pem());
+        assert!(whoami_request(addr, &stranger).is_err());
+
+        let mut config = web::TlsConfig::from_pem(server_cert.cert_pem(), server_cert.key_pem());
+        config.client_auth_pem(ca.cert_pem(), web::ClientAuth::Optional);
+        let (addr, _) = start_server(config);
+        assert_eq!(web::JsonType::Null, *whoami_request(addr, &anonymous).unwrap().get());
+        assert_eq!("CN=client", String::from(whoami_request(addr, &client).unwrap().get_val("subject").unwrap()));
+    }
+}
+
 //#[cfg(test)]
 //mod server_tests {
 //    use route_macro_attribute::route;
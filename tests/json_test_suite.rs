@@ -0,0 +1,270 @@
+//用例摘自 https://github.com/nst/JSONTestSuite 的 test_parsing 目录,
+//其中非 UTF-8 的输入无法构造成 &str, 没有收录
+const ACCEPT: &[(&str, &str)] = &[
+    ("y_array_arraysWithSpaces", "[[]   ]"),
+    ("y_array_empty", "[]"),
+    ("y_array_empty-string", r#"[""]"#),
+    ("y_array_false", "[false]"),
+    ("y_array_heterogeneous", r#"[null, 1, "1", {}]"#),
+    ("y_array_null", "[null]"),
+    ("y_array_with_1_and_newline", "[1\n]"),
+    ("y_array_with_leading_space", " [1]"),
+    ("y_array_with_several_null", "[1,null,null,null,2]"),
+    ("y_array_with_trailing_space", "[2] "),
+    ("y_number", "[123e65]"),
+    ("y_number_0e+1", "[0e+1]"),
+    ("y_number_0e1", "[0e1]"),
+    ("y_number_after_space", "[ 4]"),
+    ("y_number_double_close_to_zero", "[-0.000000000000000000000000000000000000000000000000000000000000000000000000000001]"),
+    ("y_number_int_with_exp", "[20e1]"),
+    ("y_number_minus_zero", "[-0]"),
+    ("y_number_negative_int", "[-123]"),
+    ("y_number_negative_one", "[-1]"),
+    ("y_number_real_capital_e", "[1E22]"),
+    ("y_number_real_capital_e_neg_exp", "[1E-2]"),
+    ("y_number_real_capital_e_pos_exp", "[1E+2]"),
+    ("y_number_real_exponent", "[123e45]"),
+    ("y_number_real_fraction_exponent", "[123.456e78]"),
+    ("y_number_real_neg_exp", "[1e-2]"),
+    ("y_number_real_pos_exponent", "[1e+2]"),
+    ("y_number_simple_int", "[123]"),
+    ("y_number_simple_real", "[123.456789]"),
+    ("y_object", r#"{"asd":"sdf", "dfg":"fgh"}"#),
+    ("y_object_basic", r#"{"asd":"sdf"}"#),
+    ("y_object_duplicated_key", r#"{"a":"b","a":"c"}"#),
+    ("y_object_duplicated_key_and_value", r#"{"a":"b","a":"b"}"#),
+    ("y_object_empty", "{}"),
+    ("y_object_empty_key", r#"{"":0}"#),
+    ("y_object_escaped_null_in_key", r#"{"foo\u0000bar": 42}"#),
+    ("y_object_extreme_numbers", r#"{ "min": -1.0e+28, "max": 1.0e+28 }"#),
+    ("y_object_long_strings", r#"{"x":[{"id": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"}], "id": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"}"#),
+    ("y_object_simple", r#"{"a":[]}"#),
+    ("y_object_string_unicode", r#"{"title":"\u041f\u043e\u043b\u0442\u043e\u0440\u0430 \u0417\u0435\u043c\u043b\u0435\u043a\u043e\u043f\u0430" }"#),
+    ("y_object_with_newlines", "{\n\"a\": \"b\"\n}"),
+    ("y_string_1_2_3_bytes_UTF-8_sequences", r#"["\u0060\u012a\u12AB"]"#),
+    ("y_string_accepted_surrogate_pair", r#"["\uD801\udc37"]"#),
+    ("y_string_accepted_surrogate_pairs", r#"["\ud83d\ude39\ud83d\udc8d"]"#),
+    ("y_string_allowed_escapes", r#"["\"\\\/\b\f\n\r\t"]"#),
+    ("y_string_backslash_and_u_escaped_zero", r#"["\\u0000"]"#),
+    ("y_string_backslash_doublequotes", r#"["\""]"#),
+    ("y_string_comments", r#"["a/*b*/c/*d//e"]"#),
+    ("y_string_double_escape_a", r#"["\\a"]"#),
+    ("y_string_double_escape_n", r#"["\\n"]"#),
+    ("y_string_escaped_control_character", r#"["\u0012"]"#),
+    ("y_string_escaped_noncharacter", r#"["\uFFFF"]"#),
+    ("y_string_in_array", r#"["asd"]"#),
+    ("y_string_in_array_with_leading_space", r#"[ "asd"]"#),
+    ("y_string_last_surrogates_1_and_2", r#"["\uDBFF\uDFFF"]"#),
+    ("y_string_nbsp_uescaped", r#"["new\u00A0line"]"#),
+    ("y_string_nonCharacterInUTF-8_U+10FFFF", "[\"\u{10FFFF}\"]"),
+    ("y_string_null_escape", r#"["\u0000"]"#),
+    ("y_string_one-byte-utf-8", r#"["\u002c"]"#),
+    ("y_string_pi", r#"["π"]"#),
+    ("y_string_simple_ascii", r#"["asd "]"#),
+    ("y_string_space", r#"" ""#),
+    ("y_string_three-byte-utf-8", r#"["\u0821"]"#),
+    ("y_string_two-byte-utf-8", r#"["\u0123"]"#),
+    ("y_string_u+2028_line_sep", "[\"\u{2028}\"]"),
+    ("y_string_uEscape", r#"["\u0061\u30af\u30EA\u30b9"]"#),
+    ("y_string_unicode", r#"["\uA66D"]"#),
+    ("y_string_unicode_escaped_double_quote", r#"["\u0022"]"#),
+    ("y_string_utf8", r#"["€𝄞"]"#),
+    ("y_structure_lonely_false", "false"),
+    ("y_structure_lonely_int", "42"),
+    ("y_structure_lonely_negative_real", "-0.1"),
+    ("y_structure_lonely_null", "null"),
+    ("y_structure_lonely_string", r#""asd""#),
+    ("y_structure_lonely_true", "true"),
+    ("y_structure_string_empty", r#""""#),
+    ("y_structure_trailing_newline", "[\"a\"]\n"),
+    ("y_structure_true_in_array", "[true]"),
+    ("y_structure_whitespace_array", " [] "),
+];
+
+const REJECT: &[(&str, &str)] = &[
+    ("n_array_1_true_without_comma", "[1 true]"),
+    ("n_array_colon_instead_of_comma", r#"["": 1]"#),
+    ("n_array_comma_after_close", r#"[""],"#),
+    ("n_array_comma_and_number", "[,1]"),
+    ("n_array_double_comma", "[1,,2]"),
+    ("n_array_double_extra_comma", r#"["x",,]"#),
+    ("n_array_extra_close", r#"["x"]]"#),
+    ("n_array_extra_comma", r#"["",]"#),
+    ("n_array_incomplete", r#"["x""#),
+    ("n_array_incomplete_invalid_value", "[x"),
+    ("n_array_inner_array_no_comma", "[3[4]]"),
+    ("n_array_items_separated_by_semicolon", "[1:2]"),
+    ("n_array_just_comma", "[,]"),
+    ("n_array_just_minus", "[-]"),
+    ("n_array_missing_value", r#"[   , ""]"#),
+    ("n_array_newlines_unclosed", "[\"a\",\n4\n,1,"),
+    ("n_array_number_and_comma", "[1,]"),
+    ("n_array_number_and_several_commas", "[1,,]"),
+    ("n_array_star_inside", "[*]"),
+    ("n_array_unclosed", r#"["""#),
+    ("n_array_unclosed_trailing_comma", "[1,"),
+    ("n_array_unclosed_with_new_lines", "[1,\n1\n,1"),
+    ("n_array_unclosed_with_object_inside", "[{}"),
+    ("n_incomplete_false", "[fals]"),
+    ("n_incomplete_null", "[nul]"),
+    ("n_incomplete_true", "[tru]"),
+    ("n_number_++", "[++1234]"),
+    ("n_number_+1", "[+1]"),
+    ("n_number_+Inf", "[+Inf]"),
+    ("n_number_-01", "[-01]"),
+    ("n_number_-1.0.", "[-1.0.]"),
+    ("n_number_-2.", "[-2.]"),
+    ("n_number_-NaN", "[-NaN]"),
+    ("n_number_.-1", "[.-1]"),
+    ("n_number_.2e-3", "[.2e-3]"),
+    ("n_number_0.1.2", "[0.1.2]"),
+    ("n_number_0.3e+", "[0.3e+]"),
+    ("n_number_0.3e", "[0.3e]"),
+    ("n_number_0.e1", "[0.e1]"),
+    ("n_number_0_capital_E+", "[0E+]"),
+    ("n_number_0_capital_E", "[0E]"),
+    ("n_number_0e+", "[0e+]"),
+    ("n_number_0e", "[0e]"),
+    ("n_number_1.0e+", "[1.0e+]"),
+    ("n_number_1.0e-", "[1.0e-]"),
+    ("n_number_1.0e", "[1.0e]"),
+    ("n_number_1_000", "[1 000.0]"),
+    ("n_number_1eE2", "[1eE2]"),
+    ("n_number_2.e+3", "[2.e+3]"),
+    ("n_number_9.e+", "[9.e+]"),
+    ("n_number_Inf", "[Inf]"),
+    ("n_number_NaN", "[NaN]"),
+    ("n_number_expression", "[1+2]"),
+    ("n_number_hex_1_digit", "[0x1]"),
+    ("n_number_hex_2_digits", "[0x42]"),
+    ("n_number_infinity", "[Infinity]"),
+    ("n_number_minus_infinity", "[-Infinity]"),
+    ("n_number_minus_sign_with_trailing_garbage", "[-foo]"),
+    ("n_number_minus_space_1", "[- 1]"),
+    ("n_number_neg_int_starting_with_zero", "[-012]"),
+    ("n_number_neg_real_without_int_part", "[-.123]"),
+    ("n_number_real_garbage_after_e", "[1ea]"),
+    ("n_number_real_without_fractional_part", "[1.]"),
+    ("n_number_starting_with_dot", "[.123]"),
+    ("n_number_with_alpha", "[1.2a-3]"),
+    ("n_number_with_leading_zero", "[012]"),
+    ("n_object_bad_value", r#"["x", truth]"#),
+    ("n_object_bracket_key", r#"{[: "x"}"#),
+    ("n_object_comma_instead_of_colon", r#"{"x", null}"#),
+    ("n_object_double_colon", r#"{"x"::"b"}"#),
+    ("n_object_garbage_at_end", r#"{"a":"a" 123}"#),
+    ("n_object_key_with_single_quotes", "{key: 'value'}"),
+    ("n_object_missing_colon", r#"{"a" b}"#),
+    ("n_object_missing_key", r#"{:"b"}"#),
+    ("n_object_missing_semicolon", r#"{"a" "b"}"#),
+    ("n_object_missing_value", r#"{"a":"#),
+    ("n_object_no-colon", r#"{"a""#),
+    ("n_object_non_string_key", "{1:1}"),
+    ("n_object_repeated_null_null", "{null:null,null:null}"),
+    ("n_object_several_trailing_commas", r#"{"id":0,,,,,}"#),
+    ("n_object_single_quote", "{'a':0}"),
+    ("n_object_trailing_comma", r#"{"id":0,}"#),
+    ("n_object_trailing_comment", r#"{"a":"b"}/**/"#),
+    ("n_object_two_commas_in_a_row", r#"{"a":"b",,"c":"d"}"#),
+    ("n_object_unquoted_key", r#"{a: "b"}"#),
+    ("n_object_unterminated-value", r#"{"a":"a"#),
+    ("n_object_with_trailing_garbage", r#"{"a": true} "x""#),
+    ("n_single_space", " "),
+    ("n_string_1_surrogate_then_escape", r#"["\uD800\"]"#),
+    ("n_string_1_surrogate_then_escape_u", r#"["\uD800\u"]"#),
+    ("n_string_1_surrogate_then_escape_u1", r#"["\uD800\u1"]"#),
+    ("n_string_escape_x", r#"["\x00"]"#),
+    ("n_string_escaped_backslash_bad", r#"["\\\"]"#),
+    ("n_string_escaped_ctrl_char_tab", "[\"\\\t\"]"),
+    ("n_string_escaped_emoji", r#"["\🌀"]"#),
+    ("n_string_incomplete_escape", r#"["\"]"#),
+    ("n_string_incomplete_escaped_character", r#"["\u00A"]"#),
+    ("n_string_incomplete_surrogate", r#"["\uD834\uDd"]"#),
+    ("n_string_invalid_backslash_esc", r#"["\a"]"#),
+    ("n_string_invalid_unicode_escape", r#"["\uqqqq"]"#),
+    ("n_string_leading_uescaped_thinspace", r#"[\u0020"asd"]"#),
+    ("n_string_no_quotes_with_bad_escape", r#"[\n]"#),
+    ("n_string_single_doublequote", r#"""#),
+    ("n_string_single_quote", "['single quote']"),
+    ("n_string_single_string_no_double_quotes", "abc"),
+    ("n_string_start_escape_unclosed", r#"["\"#),
+    ("n_string_unescaped_ctrl_char", "[\"a\u{0}a\"]"),
+    ("n_string_unescaped_newline", "[\"new\nline\"]"),
+    ("n_string_unescaped_tab", "[\"\t\"]"),
+    ("n_string_unicode_CapitalU", r#""\UA66D""#),
+    ("n_string_with_trailing_garbage", r#"""x"#),
+    ("n_structure_array_trailing_garbage", "[1]x"),
+    ("n_structure_array_with_extra_array_close", "[1]]"),
+    ("n_structure_array_with_unclosed_string", r#"["asd]"#),
+    ("n_structure_ascii-unicode-identifier", "aå"),
+    ("n_structure_capitalized_True", "[True]"),
+    ("n_structure_close_unopened_array", "1]"),
+    ("n_structure_comma_instead_of_closing_brace", r#"{"x": true,"#),
+    ("n_structure_double_array", "[][]"),
+    ("n_structure_end_array", "]"),
+    ("n_structure_lone-open-bracket", "["),
+    ("n_structure_no_data", ""),
+    ("n_structure_null-byte-outside-string", "[\u{0}]"),
+    ("n_structure_number_with_trailing_garbage", "2@"),
+    ("n_structure_object_followed_by_closing_object", "{}}"),
+    ("n_structure_object_unclosed_no_value", r#"{"":"#),
+    ("n_structure_object_with_comment", r#"{"a":/*comment*/"b"}"#),
+    ("n_structure_open_array_apostrophe", "['"),
+    ("n_structure_open_array_comma", "[,"),
+    ("n_structure_open_object", "{"),
+    ("n_structure_open_object_close_array", "{]"),
+    ("n_structure_single_star", "*"),
+    ("n_structure_trailing_#", r#"{"a":"b"}#{}"#),
+    ("n_structure_uescaped_LF_before_string", r#"[\u000A""]"#),
+    ("n_structure_unclosed_array", "[1"),
+    ("n_structure_unclosed_object", r#"{"asd":"asd""#),
+    ("n_structure_whitespace_formfeed", "[\u{c}]"),
+    ("n_structure_whitespace_U+2060_word_joiner", "[\u{2060}]"),
+];
+
+//i_ 开头的用例由实现决定, 这里记录的是本库的选择
+const IMPLEMENTATION_DEFINED: &[(&str, &str, bool)] = &[
+    ("i_number_huge_exp", "[0.4e00669999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999969999999006]", false),
+    ("i_number_neg_int_huge_exp", "[-1e+9999]", false),
+    ("i_number_pos_double_huge_exp", "[1.5e+9999]", false),
+    ("i_number_real_underflow", "[123e-10000000]", true),
+    ("i_number_too_big_neg_int", "[-123123123123123123123123123123]", true),
+    ("i_number_too_big_pos_int", "[100000000000000000000]", true),
+    ("i_string_1st_surrogate_but_2nd_missing", r#"["\uDADA"]"#, false),
+    ("i_string_1st_valid_surrogate_2nd_invalid", r#"["\uD888\u1234"]"#, false),
+    ("i_string_incomplete_surrogate_and_escape_valid", r#"["\uD800\n"]"#, false),
+    ("i_string_inverted_surrogates_U+1D11E", r#"["\uDd1e\uD834"]"#, false),
+    ("i_string_lone_second_surrogate", r#"["\uDFAA"]"#, false),
+];
+
+#[test]
+fn accept_cases() {
+    for (name, input) in ACCEPT {
+        if let Err(e) = rust_web::web::Json::parse(input) {
+            panic!("{name} should be accepted: {input:?}\r\n{e}");
+        }
+    }
+}
+
+#[test]
+fn reject_cases() {
+    for (name, input) in REJECT {
+        assert!(rust_web::web::Json::parse(input).is_err(), "{name} should be rejected: {input:?}");
+    }
+}
+
+#[test]
+fn implementation_defined_cases() {
+    for (name, input, accept) in IMPLEMENTATION_DEFINED {
+        assert_eq!(*accept, rust_web::web::Json::parse(input).is_ok(), "{name}: {input:?}");
+    }
+}
+
+#[test]
+fn deeply_nested_input() {
+    //i_structure_500_nested_arrays
+    assert!(rust_web::web::Json::parse(format!("{}{}", "[".repeat(500), "]".repeat(500))).is_ok());
+
+    assert!(rust_web::web::Json::parse("[".repeat(100000)).is_err());
+    assert!(rust_web::web::Json::parse("{\"a\":".repeat(100000)).is_err());
+}
@@ -0,0 +1,6 @@
+//#[route] 用错时应该在编译期报出清楚的错误
+#[test]
+fn route_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/route_*.rs");
+}
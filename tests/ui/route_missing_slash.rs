@@ -0,0 +1,8 @@
+use rust_web::web;
+
+#[web::route(GET, "users/:id")]
+fn handler(ctx: web::RequestContext) -> web::HttpResponse {
+    web::HttpResponse::json(web::Json::from(ctx.get_param("id").unwrap_or_default()))
+}
+
+fn main() {}